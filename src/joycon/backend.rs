@@ -0,0 +1,123 @@
+//! Pluggable output backends for JoyCon HID reports.
+//!
+//! Everything above this module speaks in terms of 49-byte HID reports. The
+//! traits here decide where those reports go:
+//!
+//! - [`RumbleSink`]: a single open device that accepts output reports and
+//!   yields input reports
+//! - [`DeviceBackend`]: enumerates devices and opens them as sinks
+//!
+//! [`HidBackend`] is the real implementation backed by `hidapi`. The
+//! [`simulated`](super::simulated) module provides an in-memory backend for
+//! running playback without hardware.
+
+use std::ffi::CString;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hidapi::{BusType, HidApi, HidDevice};
+
+use super::types::{DeviceInfo, JoyConError, JOYCON_CHARGING_GRIP};
+
+/// A destination for JoyCon output reports.
+///
+/// Implemented by [`HidDevice`] for real controllers and by
+/// [`SimulatedDevice`](super::SimulatedDevice) for tests and offline work.
+/// Sinks must be `Send` because each JoyCon is driven from its own playback
+/// thread.
+pub trait RumbleSink: Send {
+    /// Writes a single output report, returning the number of bytes written.
+    fn write_report(&mut self, report: &[u8]) -> Result<usize, JoyConError>;
+
+    /// Reads one input report into `buf`, waiting at most `timeout`.
+    ///
+    /// Returns the number of bytes read, or `0` if no report arrived in
+    /// time. Sinks without input reports should return an error rather
+    /// than `0`, which callers treat as "try again".
+    fn read_report(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize, JoyConError>;
+}
+
+impl RumbleSink for HidDevice {
+    fn write_report(&mut self, report: &[u8]) -> Result<usize, JoyConError> {
+        self.write(report)
+            .map_err(|e| JoyConError::HidError(e.to_string()))
+    }
+
+    fn read_report(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize, JoyConError> {
+        let millis = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+        self.read_timeout(buf, millis)
+            .map_err(|e| JoyConError::HidError(e.to_string()))
+    }
+}
+
+/// Enumerates devices and opens them as [`RumbleSink`]s.
+///
+/// [`JoyConManager`](super::JoyConManager) is generic over this trait, so the
+/// same discovery and initialization code runs against real HID devices or
+/// simulated ones. Backends must be `Sync` so playback threads can reconnect
+/// a dropped controller through the shared manager.
+pub trait DeviceBackend: Sync {
+    /// The sink type produced when a device is opened.
+    type Sink: RumbleSink + 'static;
+
+    /// Lists every device currently visible to the backend.
+    ///
+    /// Each call must see devices connected or removed since the last one.
+    /// Filtering by vendor and product ID is done by the manager.
+    fn devices(&self) -> Result<Vec<DeviceInfo>, JoyConError>;
+
+    /// Opens a device previously returned by [`devices`](Self::devices).
+    fn open(&self, info: &DeviceInfo) -> Result<Self::Sink, JoyConError>;
+}
+
+/// The default backend, talking to real controllers through `hidapi`.
+pub struct HidBackend {
+    api: Mutex<HidApi>,
+}
+
+impl HidBackend {
+    /// Initializes the HID API.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JoyConError::HidError`] if the HID API cannot be initialized.
+    pub fn new() -> Result<Self, JoyConError> {
+        let api = HidApi::new().map_err(|e| JoyConError::HidError(e.to_string()))?;
+        Ok(Self {
+            api: Mutex::new(api),
+        })
+    }
+}
+
+impl DeviceBackend for HidBackend {
+    type Sink = HidDevice;
+
+    fn devices(&self) -> Result<Vec<DeviceInfo>, JoyConError> {
+        let mut api = self.api.lock().unwrap_or_else(|e| e.into_inner());
+        api.refresh_devices()
+            .map_err(|e| JoyConError::HidError(e.to_string()))?;
+        Ok(api
+            .device_list()
+            .map(|device_info| DeviceInfo {
+                product_id: device_info.product_id(),
+                interface_number: device_info.interface_number(),
+                usb: matches!(device_info.bus_type(), BusType::Usb)
+                    || device_info.product_id() == JOYCON_CHARGING_GRIP,
+                serial: device_info.serial_number().unwrap_or("Unknown").to_string(),
+                path: device_info.path().to_string_lossy().into_owned(),
+                vendor_id: device_info.vendor_id() as i32,
+                usage_page: device_info.usage_page() as i32,
+            })
+            .collect())
+    }
+
+    fn open(&self, info: &DeviceInfo) -> Result<Self::Sink, JoyConError> {
+        let path = CString::new(info.path.as_str())
+            .map_err(|_| JoyConError::InvalidDevice("Device path contains a NUL byte"))?;
+        self.api
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .open_path(&path)
+            .map_err(|e| JoyConError::HidError(e.to_string()))
+    }
+}
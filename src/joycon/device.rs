@@ -3,18 +3,56 @@
 //! This module provides the [`JoyCon`] struct for controlling individual
 //! Nintendo JoyCon or Pro Controller devices.
 
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
 use hidapi::HidDevice;
 
+use super::backend::RumbleSink;
+use super::hd_rumble::{HIGH_BAND_HZ, LOW_BAND_HZ};
+use super::imu::{
+    ImuCalibration, ImuSample, RawImuSample, IMU_CALIBRATION_ADDRESS, IMU_CALIBRATION_LEN,
+};
+use super::input::{BatteryLevel, InputState, SubcommandReply, STANDARD_FULL_REPORT};
 use super::interface::JoyconInterface;
+use super::lights::{HomeLight, PlayerLights};
+use super::spi::{
+    parse_calibration, parse_serial, ControllerColors, ControllerIdentity, COLORS_ADDRESS,
+    COLORS_LEN, FACTORY_CALIBRATION_ADDRESS, MAX_SPI_READ, SERIAL_ADDRESS, SERIAL_LEN,
+    USER_CALIBRATION_ADDRESS,
+};
 use super::types::{
-    Command, DeviceInfo, JoyConError, JoyConType, Subcommand, JOYCON_CHARGING_GRIP, JOYCON_L_BT,
-    JOYCON_R_BT, PRO_CONTROLLER,
+    Command, DeviceInfo, JoyConError, JoyConType, Motor, Subcommand, JOYCON_CHARGING_GRIP,
+    JOYCON_L_BT, JOYCON_R_BT, PRO_CONTROLLER,
 };
-use crate::midi::RumbleTrack;
+use crate::midi::{RumbleCommand, RumbleTrack};
+
+/// Rumble bytes that leave both motors idle.
+const NEUTRAL_RUMBLE: [u8; 8] = [0x00, 0x01, 0x40, 0x40, 0x00, 0x01, 0x40, 0x40];
+
+/// Size of the buffer input reports are read into.
+const INPUT_REPORT_LEN: usize = 64;
+
+/// Most IMU samples kept for [`JoyCon::take_imu_samples`], one second's
+/// worth; older ones are dropped.
+const MAX_BUFFERED_IMU_SAMPLES: usize = 200;
+
+/// Octave-shifts `frequency` into `band`; `0.0` stays silent.
+fn fit_to_band(frequency: f32, (min, max): (f32, f32)) -> f32 {
+    if frequency <= 0.0 {
+        return 0.0;
+    }
+    let mut freq = frequency;
+    while freq > max {
+        freq /= 2.0;
+    }
+    while freq < min {
+        freq *= 2.0;
+    }
+    freq
+}
 
 /// Represents a connected Nintendo JoyCon or Pro Controller.
 ///
@@ -55,17 +93,39 @@ use crate::midi::RumbleTrack;
 /// # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
 /// ```
 ///
+/// # Output Backends
+///
+/// `JoyCon` is generic over the [`RumbleSink`] its reports are written to.
+/// The default is a real [`HidDevice`]; a
+/// [`SimulatedDevice`](super::SimulatedDevice) records reports in memory
+/// instead, which lets playback run without hardware.
+///
 /// # Thread Safety
 ///
 /// `JoyCon` is not `Sync` due to the underlying HID device handle.
 /// For multi-threaded access, wrap in `Arc<Mutex<JoyCon>>`.
-pub struct JoyCon {
-    /// HID device handle for communication
-    handle: Option<HidDevice>,
+pub struct JoyCon<S: RumbleSink = HidDevice> {
+    /// Output sink for HID reports
+    handle: Option<S>,
     /// Type of JoyCon (Left, Right, Pro Controller, etc.)
     device_type: JoyConType,
+    /// Discovery information the device was created from
+    info: DeviceInfo,
     /// Packet counter for HID communication (wraps at 255)
     timing_byte: u8,
+    /// Most recent state decoded from an input report
+    input: Option<InputState>,
+    /// Rumble bytes of the last rumble report, repeated in subcommand
+    /// reports so they do not interrupt a sounding note
+    rumble_data: [u8; 8],
+    /// Identity read from SPI flash by `read_identity`
+    identity: Option<ControllerIdentity>,
+    /// IMU calibration read from SPI flash by `read_imu_calibration`
+    imu_calibration: Option<ImuCalibration>,
+    /// Whether `enable_imu` turned the motion sensors on
+    imu_enabled: bool,
+    /// Calibrated IMU samples not yet taken, oldest first
+    imu_samples: VecDeque<ImuSample>,
 }
 
 impl JoyCon {
@@ -89,6 +149,28 @@ impl JoyCon {
     /// - The product ID is not recognized as a JoyCon or Pro Controller
     /// - The charging grip interface number is invalid
     pub fn new(device_info: &DeviceInfo) -> Result<Self, JoyConError> {
+        Self::from_device_info(device_info)
+    }
+}
+
+impl<S: RumbleSink> JoyCon<S> {
+    /// Creates a JoyCon that writes its reports to `sink`.
+    ///
+    /// This is how [`JoyConManager`](super::JoyConManager) builds devices for
+    /// any [`DeviceBackend`](super::DeviceBackend), and how tests attach a
+    /// [`SimulatedDevice`](super::SimulatedDevice) directly.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JoyConError::InvalidDevice`] under the same conditions as
+    /// [`JoyCon::new`].
+    pub fn with_sink(device_info: &DeviceInfo, sink: S) -> Result<Self, JoyConError> {
+        let mut joycon = Self::from_device_info(device_info)?;
+        joycon.set_handle(sink);
+        Ok(joycon)
+    }
+
+    fn from_device_info(device_info: &DeviceInfo) -> Result<Self, JoyConError> {
         let device_type = match device_info.product_id {
             JOYCON_L_BT => JoyConType::Left,
             JOYCON_R_BT => JoyConType::Right,
@@ -104,7 +186,14 @@ impl JoyCon {
         Ok(Self {
             handle: None,
             device_type,
+            info: device_info.clone(),
             timing_byte: 0,
+            input: None,
+            rumble_data: NEUTRAL_RUMBLE,
+            identity: None,
+            imu_calibration: None,
+            imu_enabled: false,
+            imu_samples: VecDeque::new(),
         })
     }
 
@@ -148,28 +237,99 @@ impl JoyCon {
     /// Returns [`JoyConError::NotConnected`] if the device handle is not set.
     /// Returns [`JoyConError::HidError`] if the HID write operation fails.
     pub fn rumble(&mut self, frequency: f32, amplitude: f32) -> Result<(), JoyConError> {
-        // Clamp amplitude between 0 and 1 (should already be normalized from track analysis)
-        let clamped_amplitude = amplitude.clamp(0.0, 1.0);
+        // Both bands play the note, each octave-shifted into its own range.
+        self.rumble_bands((frequency, amplitude), (frequency, amplitude))
+    }
 
-        if frequency == 0.0 {
-            return JoyconInterface::send_rumble(self, 0.0, clamped_amplitude);
-        }
+    /// Rumbles with independent notes in the high and low frequency bands.
+    ///
+    /// HD rumble drives two bands at once, so a single JoyCon can play two
+    /// notes: `high` in the high band (about 82-1252 Hz) and `low` in the
+    /// low band (about 41-626 Hz), each as `(frequency, amplitude)`. A note
+    /// outside its band is octave-shifted into it; a frequency of `0.0`
+    /// silences that band.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use musical_joycons::joycon::JoyConManager;
+    /// # let manager = JoyConManager::new()?;
+    /// # let mut joycons = manager.connect_and_initialize_joycons()?;
+    /// # let joycon = joycons.first_mut().unwrap();
+    /// // E5 over C4
+    /// joycon.rumble_bands((659.3, 0.6), (261.6, 0.8))?;
+    /// # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The same as [`rumble`](Self::rumble).
+    pub fn rumble_bands(&mut self, high: (f32, f32), low: (f32, f32)) -> Result<(), JoyConError> {
+        let high = (fit_to_band(high.0, HIGH_BAND_HZ), high.1.clamp(0.0, 1.0));
+        let low = (fit_to_band(low.0, LOW_BAND_HZ), low.1.clamp(0.0, 1.0));
+        JoyconInterface::send_rumble_bands(self, high, low)
+    }
 
-        let wrapped_freq = {
-            let mut freq = frequency;
-            // Shift down octaves until below max
-            while freq > 1252.0 {
-                freq /= 2.0;
-            }
-            // Shift up octaves until above min (excluding 0)
-            while freq > 0.0 && freq < 1.0 {
-                freq *= 2.0;
-            }
+    /// Sends one [`RumbleCommand`], using both bands when it carries a
+    /// [`low_band`](RumbleCommand::low_band) note.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`rumble`](Self::rumble).
+    pub fn rumble_command(&mut self, command: &RumbleCommand) -> Result<(), JoyConError> {
+        match command.low_band {
+            Some(low) => self.rumble_bands((command.frequency, command.amplitude), low),
+            None => self.rumble(command.frequency, command.amplitude),
+        }
+    }
 
-            freq
-        };
+    /// Returns `true` for controllers with a left and a right rumble motor
+    /// that can play different notes, i.e. the Pro Controller.
+    pub fn has_two_motors(&self) -> bool {
+        self.device_type == JoyConType::ProController
+    }
 
-        JoyconInterface::send_rumble(self, wrapped_freq, clamped_amplitude)
+    /// Sends one [`RumbleCommand`] to a single motor of a controller with
+    /// [two](Self::has_two_motors), leaving the other one playing. On other
+    /// controllers this is the same as [`rumble_command`](Self::rumble_command).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use musical_joycons::joycon::{JoyConManager, Motor};
+    /// # use musical_joycons::midi::RumbleCommand;
+    /// # use std::time::Duration;
+    /// # let manager = JoyConManager::new()?;
+    /// # let mut joycons = manager.connect_and_initialize_joycons()?;
+    /// # let pro = joycons.first_mut().unwrap();
+    /// let note = |frequency| RumbleCommand {
+    ///     frequency,
+    ///     amplitude: 0.7,
+    ///     wait_before: Duration::ZERO,
+    ///     low_band: None,
+    /// };
+    /// // A fifth, one note per grip
+    /// pro.rumble_motor(Motor::Left, &note(261.6))?;
+    /// pro.rumble_motor(Motor::Right, &note(392.0))?;
+    /// # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The same as [`rumble`](Self::rumble).
+    pub fn rumble_motor(
+        &mut self,
+        motor: Motor,
+        command: &RumbleCommand,
+    ) -> Result<(), JoyConError> {
+        if !self.has_two_motors() {
+            return self.rumble_command(command);
+        }
+        let high = (command.frequency, command.amplitude);
+        let low = command.low_band.unwrap_or(high);
+        let high = (fit_to_band(high.0, HIGH_BAND_HZ), high.1.clamp(0.0, 1.0));
+        let low = (fit_to_band(low.0, LOW_BAND_HZ), low.1.clamp(0.0, 1.0));
+        JoyconInterface::send_motor_bands(self, motor, high, low)
     }
 
     /// Enables the rumble motor on this JoyCon.
@@ -179,16 +339,354 @@ impl JoyCon {
     ///
     /// # Errors
     ///
-    /// Returns an error if the HID command cannot be sent.
+    /// Returns an error if the HID command cannot be sent or the controller
+    /// does not acknowledge it (see [`send_subcommand`](Self::send_subcommand)).
     pub fn enable_rumble(&mut self) -> Result<(), JoyConError> {
+        self.request(Subcommand::EnableVibration, &[0x01])
+            .map(|_| ())
+    }
+
+    /// Switches the controller to standard full input mode (`0x30`).
+    ///
+    /// The controller then pushes an input report about 60 times per second,
+    /// readable with [`read_input`](Self::read_input),
+    /// [`poll_input`](Self::poll_input) or
+    /// [`input_states`](Self::input_states). It is automatically called by
+    /// [`initialize_device`](Self::initialize_device).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HID command cannot be sent or the controller
+    /// does not acknowledge it (see [`send_subcommand`](Self::send_subcommand)).
+    pub fn enable_input_reports(&mut self) -> Result<(), JoyConError> {
+        self.request(Subcommand::SetInputMode, &[STANDARD_FULL_REPORT])
+            .map(|_| ())
+    }
+
+    /// Sends a subcommand and waits for the controller's reply.
+    ///
+    /// Input reports that arrive while waiting still update
+    /// [`input_state`](Self::input_state). A subcommand that gets no reply
+    /// within 200 ms is sent again, up to three times in all.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JoyConError::NoReply`] if the controller never answers and
+    /// [`JoyConError::Rejected`] if it answers without acknowledging the
+    /// subcommand. Returns [`JoyConError::NotConnected`] or
+    /// [`JoyConError::HidError`] if sending or reading fails.
+    pub fn send_subcommand(
+        &mut self,
+        subcommand: u8,
+        data: &[u8],
+    ) -> Result<SubcommandReply, JoyConError> {
+        JoyconInterface::request(self, subcommand, data)
+    }
+
+    /// [`send_subcommand`](Self::send_subcommand) for a known subcommand.
+    pub(crate) fn request(
+        &mut self,
+        subcommand: Subcommand,
+        data: &[u8],
+    ) -> Result<SubcommandReply, JoyConError> {
+        JoyconInterface::request(self, subcommand as u8, data)
+    }
+
+    /// Reads `len` bytes of SPI flash starting at `address`.
+    ///
+    /// Reads longer than [`MAX_SPI_READ`] are split into several requests.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`send_subcommand`](Self::send_subcommand). Returns
+    /// [`JoyConError::HidError`] if a reply is for a different address or
+    /// too short.
+    pub fn read_spi(&mut self, address: u32, len: usize) -> Result<Vec<u8>, JoyConError> {
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            let chunk_address = address + bytes.len() as u32;
+            let chunk_len = (len - bytes.len()).min(MAX_SPI_READ);
+            let mut request = chunk_address.to_le_bytes().to_vec();
+            request.push(chunk_len as u8);
+
+            let reply = self.request(Subcommand::SpiFlashRead, &request)?;
+            // The reply echoes the address and length before the data.
+            if reply.data.len() < 5 + chunk_len || reply.data[..5] != request[..] {
+                return Err(JoyConError::HidError(format!(
+                    "Unexpected SPI flash reply for {chunk_address:#06x}"
+                )));
+            }
+            bytes.extend_from_slice(&reply.data[5..5 + chunk_len]);
+        }
+        Ok(bytes)
+    }
+
+    /// Reads the serial number, colors and stick calibration from SPI
+    /// flash.
+    ///
+    /// The result is also kept for [`identity`](Self::identity) and
+    /// [`serial`](Self::serial).
+    ///
+    /// # Errors
+    ///
+    /// The same as [`read_spi`](Self::read_spi).
+    pub fn read_identity(&mut self) -> Result<ControllerIdentity, JoyConError> {
+        let serial = self.read_spi(SERIAL_ADDRESS, SERIAL_LEN)?;
+        let colors = self.read_spi(COLORS_ADDRESS, COLORS_LEN)?;
+        let factory = self.read_spi(FACTORY_CALIBRATION_ADDRESS, 18)?;
+        let user = self.read_spi(USER_CALIBRATION_ADDRESS, 22)?;
+        let (left_stick, right_stick) = parse_calibration(&factory, &user);
+
+        let identity = ControllerIdentity {
+            serial: parse_serial(&serial),
+            colors: ControllerColors::parse(&colors),
+            left_stick,
+            right_stick,
+        };
+        self.identity = Some(identity.clone());
+        Ok(identity)
+    }
+
+    /// The identity from the last [`read_identity`](Self::read_identity),
+    /// if any.
+    pub fn identity(&self) -> Option<&ControllerIdentity> {
+        self.identity.as_ref()
+    }
+
+    /// The factory serial number from SPI flash if it has been read,
+    /// otherwise the serial the backend reported.
+    pub fn serial(&self) -> &str {
+        self.identity
+            .as_ref()
+            .and_then(|identity| identity.serial.as_deref())
+            .unwrap_or(&self.info.serial)
+    }
+
+    /// Reads the factory accelerometer and gyroscope calibration from SPI
+    /// flash.
+    ///
+    /// Falls back to the nominal [`ImuCalibration::default`] if the flash
+    /// holds none. The result is kept for converting samples from then on.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`read_spi`](Self::read_spi).
+    pub fn read_imu_calibration(&mut self) -> Result<ImuCalibration, JoyConError> {
+        let bytes = self.read_spi(IMU_CALIBRATION_ADDRESS, IMU_CALIBRATION_LEN)?;
+        let calibration = ImuCalibration::parse(&bytes).unwrap_or_else(|| {
+            log::debug!("No IMU calibration in flash, using nominal values");
+            ImuCalibration::default()
+        });
+        self.imu_calibration = Some(calibration);
+        Ok(calibration)
+    }
+
+    /// The calibration samples are converted with: the one from
+    /// [`read_imu_calibration`](Self::read_imu_calibration), or the
+    /// nominal one if it has not been read.
+    pub fn imu_calibration(&self) -> ImuCalibration {
+        self.imu_calibration.unwrap_or_default()
+    }
+
+    /// Turns on the accelerometer and gyroscope (subcommand `0x40`).
+    ///
+    /// Reads the [IMU calibration](Self::read_imu_calibration) first if it
+    /// has not been read. From then on every input report carries three
+    /// samples, 5 ms apart, which [`read_input`](Self::read_input) and
+    /// [`poll_input`](Self::poll_input) buffer for
+    /// [`take_imu_samples`](Self::take_imu_samples) and
+    /// [`imu_samples`](Self::imu_samples) streams. Requires input reports
+    /// to be [enabled](Self::enable_input_reports).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use musical_joycons::joycon::JoyConManager;
+    /// use std::time::Duration;
+    ///
+    /// let manager = JoyConManager::new()?;
+    /// let mut joycons = manager.connect_and_initialize_joycons()?;
+    /// joycons[0].enable_imu()?;
+    ///
+    /// for sample in joycons[0].imu_samples(Duration::from_secs(1)).take(200) {
+    ///     let [x, y, z] = sample?.angular_rate;
+    ///     println!("{x:7.1} {y:7.1} {z:7.1} °/s");
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The same as [`send_subcommand`](Self::send_subcommand).
+    pub fn enable_imu(&mut self) -> Result<(), JoyConError> {
+        if self.imu_calibration.is_none() {
+            self.read_imu_calibration()?;
+        }
+        self.request(Subcommand::EnableImu, &[0x01])?;
+        self.imu_enabled = true;
+        Ok(())
+    }
+
+    /// Turns the accelerometer and gyroscope off again and discards
+    /// buffered samples.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`send_subcommand`](Self::send_subcommand).
+    pub fn disable_imu(&mut self) -> Result<(), JoyConError> {
+        self.request(Subcommand::EnableImu, &[0x00])?;
+        self.imu_enabled = false;
+        self.imu_samples.clear();
+        Ok(())
+    }
+
+    /// Returns `true` while the IMU is [enabled](Self::enable_imu).
+    pub fn is_imu_enabled(&self) -> bool {
+        self.imu_enabled
+    }
+
+    /// Takes every buffered IMU sample, oldest first.
+    ///
+    /// Samples are buffered by whatever reads input reports, e.g.
+    /// [`poll_input`](Self::poll_input); at most one second's worth is
+    /// kept.
+    pub fn take_imu_samples(&mut self) -> Vec<ImuSample> {
+        self.imu_samples.drain(..).collect()
+    }
+
+    /// Streams IMU samples as they arrive, reading input reports when the
+    /// buffer runs dry.
+    ///
+    /// The iterator ends when no standard input report arrives within
+    /// `timeout`, and after yielding an error.
+    pub fn imu_samples(&mut self, timeout: Duration) -> ImuSamples<'_, S> {
+        ImuSamples {
+            joycon: self,
+            timeout,
+            done: false,
+        }
+    }
+
+    /// Sets the four player lights.
+    ///
+    /// Does not wait for the controller's reply, so it is safe to call
+    /// between notes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HID command cannot be sent.
+    pub fn set_player_lights(&mut self, lights: PlayerLights) -> Result<(), JoyConError> {
+        JoyconInterface::send_command(
+            self,
+            Command::SubCommand,
+            Some(Subcommand::SetPlayerLights),
+            &[lights.to_byte()],
+        )
+    }
+
+    /// Plays a pattern on the Home button LED.
+    ///
+    /// The Left Joy-Con has no Home button and ignores it. Like
+    /// [`set_player_lights`](Self::set_player_lights), this does not wait
+    /// for the reply.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HID command cannot be sent.
+    pub fn set_home_light(&mut self, light: &HomeLight) -> Result<(), JoyConError> {
         JoyconInterface::send_command(
             self,
-            Command::Rumble,
-            Some(Subcommand::EnableVibration),
-            &[0x01],
+            Command::SubCommand,
+            Some(Subcommand::SetHomeLight),
+            &light.to_bytes(),
         )
     }
 
+    /// Reads one input report, waiting at most `timeout`.
+    ///
+    /// Returns `None` if no report arrived in time or the report was not a
+    /// standard input report. A decoded state also becomes the new
+    /// [`input_state`](Self::input_state), and while the
+    /// [IMU is enabled](Self::enable_imu) the report's motion samples are
+    /// buffered for [`take_imu_samples`](Self::take_imu_samples).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JoyConError::NotConnected`] if the device handle is not set.
+    /// Returns [`JoyConError::HidError`] if the HID read fails.
+    pub fn read_input(&mut self, timeout: Duration) -> Result<Option<InputState>, JoyConError> {
+        let handle = self.get_handle_mut().ok_or(JoyConError::NotConnected)?;
+        let mut buf = [0u8; INPUT_REPORT_LEN];
+        let len = handle.read_report(&mut buf, timeout)?;
+        Ok(self.record_report(&buf[..len]))
+    }
+
+    /// Reads every input report already waiting, without blocking, and
+    /// returns the newest state. IMU samples in the reports are buffered
+    /// for [`take_imu_samples`](Self::take_imu_samples).
+    ///
+    /// Returns `None` if no new state arrived since the last read.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`read_input`](Self::read_input).
+    pub fn poll_input(&mut self) -> Result<Option<InputState>, JoyConError> {
+        let mut newest = None;
+        let mut buf = [0u8; INPUT_REPORT_LEN];
+        loop {
+            let handle = self.get_handle_mut().ok_or(JoyConError::NotConnected)?;
+            let len = handle.read_report(&mut buf, Duration::ZERO)?;
+            if len == 0 {
+                break;
+            }
+            if let Some(state) = self.record_report(&buf[..len]) {
+                newest = Some(state);
+            }
+        }
+        Ok(newest)
+    }
+
+    /// The most recent state read from this controller, if any.
+    pub fn input_state(&self) -> Option<InputState> {
+        self.input
+    }
+
+    /// The battery level from the most recent input report, if any.
+    pub fn battery(&self) -> Option<BatteryLevel> {
+        self.input.map(|state| state.battery)
+    }
+
+    /// Asks the controller for a fresh input state.
+    ///
+    /// Re-sends the input mode subcommand once and returns the state from
+    /// the header of its reply, or `None` if the controller does not answer
+    /// within 200 ms.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`send_subcommand`](Self::send_subcommand), except that
+    /// a missing reply is not an error.
+    pub fn query_input(&mut self) -> Result<Option<InputState>, JoyConError> {
+        let subcommand = Subcommand::SetInputMode as u8;
+        match JoyconInterface::request_with_attempts(self, subcommand, &[STANDARD_FULL_REPORT], 1) {
+            Ok(reply) => Ok(Some(reply.state)),
+            Err(JoyConError::NoReply(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Iterates over input states as they arrive.
+    ///
+    /// The iterator ends when no standard input report arrives within
+    /// `timeout`, and after yielding an error.
+    pub fn input_states(&mut self, timeout: Duration) -> InputStates<'_, S> {
+        InputStates {
+            joycon: self,
+            timeout,
+            done: false,
+        }
+    }
+
     /// Plays a C major scale from C5 to C6 as a device test.
     ///
     /// This is useful for verifying that the JoyCon rumble motor is working
@@ -245,13 +743,13 @@ impl JoyCon {
     ///
     /// Returns an error if any rumble command fails to send.
     pub fn play_rumble_track(&mut self, track: RumbleTrack) -> Result<(), JoyConError> {
-        println!("Track duration: {:?}", track.total_duration);
+        log::debug!("Track duration: {:?}", track.total_duration);
 
         for command in track.commands {
             if !command.wait_before.is_zero() {
                 thread::sleep(command.wait_before);
             }
-            self.rumble(command.frequency, command.amplitude)?;
+            self.rumble_command(&command)?;
         }
 
         self.rumble(0.0, 0.0)?;
@@ -314,7 +812,8 @@ impl JoyCon {
     ///
     /// This method:
     /// 1. Enables the rumble motor via HID command
-    /// 2. Plays a test scale to verify functionality
+    /// 2. Switches the controller to standard full input mode
+    /// 3. Plays a test scale to verify functionality
     ///
     /// Call this after connecting to a JoyCon and before sending rumble commands.
     /// [`JoyConManager::connect_and_initialize_joycons`](super::JoyConManager::connect_and_initialize_joycons)
@@ -322,24 +821,31 @@ impl JoyCon {
     ///
     /// # Errors
     ///
-    /// Returns an error if enabling rumble or playing the scale fails.
+    /// Returns an error if enabling rumble or input reports, or playing the
+    /// scale fails.
     pub fn initialize_device(&mut self) -> Result<(), JoyConError> {
         self.enable_rumble()?;
+        self.enable_input_reports()?;
         self.play_scale()
     }
 
-    /// Returns a reference to the underlying HID device handle.
+    /// Returns a reference to the underlying device handle.
     ///
     /// Returns `None` if the device has not been connected yet.
-    pub fn get_handle(&self) -> Option<&HidDevice> {
+    pub fn get_handle(&self) -> Option<&S> {
         self.handle.as_ref()
     }
 
-    /// Sets the HID device handle for this JoyCon.
+    /// Returns a mutable reference to the underlying device handle.
+    pub(crate) fn get_handle_mut(&mut self) -> Option<&mut S> {
+        self.handle.as_mut()
+    }
+
+    /// Sets the device handle for this JoyCon.
     ///
     /// This is called internally by [`JoyConManager`](super::JoyConManager)
     /// after successfully opening the device.
-    pub(crate) fn set_handle(&mut self, device: HidDevice) {
+    pub(crate) fn set_handle(&mut self, device: S) {
         self.handle = Some(device);
     }
 
@@ -355,6 +861,33 @@ impl JoyCon {
         self.device_type
     }
 
+    /// Returns the discovery information this JoyCon was created from.
+    ///
+    /// Includes the serial number and product ID reported by the backend.
+    pub fn device_info(&self) -> &DeviceInfo {
+        &self.info
+    }
+
+    /// Returns `true` if the controller is connected by USB, in a charging
+    /// grip or with a cable.
+    pub fn is_usb(&self) -> bool {
+        self.info.usb
+    }
+
+    /// Performs the USB handshake wired controllers need before they
+    /// accept output reports.
+    ///
+    /// [`JoyConManager`](super::JoyConManager) does this when it opens a
+    /// controller connected by USB; Bluetooth controllers do not need it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JoyConError::NoUsbReply`] if the controller does not
+    /// answer, as when the charging grip slot it belongs to is empty.
+    pub fn usb_handshake(&mut self) -> Result<(), JoyConError> {
+        JoyconInterface::usb_handshake(self)
+    }
+
     /// Returns the current timing byte value.
     ///
     /// The timing byte is a packet counter used in HID communication
@@ -369,6 +902,99 @@ impl JoyCon {
     pub(crate) fn increment_timing_byte(&mut self) {
         self.timing_byte = self.timing_byte.wrapping_add(1);
     }
+
+    /// Replaces the device handle after a reconnection, resetting the
+    /// packet counter, rumble state and last input state.
+    pub(crate) fn reattach(&mut self, info: &DeviceInfo, device: S) {
+        self.info = info.clone();
+        self.handle = Some(device);
+        self.timing_byte = 0;
+        self.rumble_data = NEUTRAL_RUMBLE;
+        self.input = None;
+        self.imu_samples.clear();
+    }
+
+    /// Decodes a report read from the controller: its header becomes the
+    /// new [`input_state`](Self::input_state) and, while the IMU is
+    /// enabled, its motion samples are buffered.
+    pub(crate) fn record_report(&mut self, report: &[u8]) -> Option<InputState> {
+        let state = InputState::parse(report)?;
+        self.input = Some(state);
+        if self.imu_enabled {
+            if let Some(samples) = RawImuSample::parse_report(report) {
+                let calibration = self.imu_calibration();
+                self.imu_samples
+                    .extend(samples.map(|raw| calibration.apply(raw)));
+                let excess = self
+                    .imu_samples
+                    .len()
+                    .saturating_sub(MAX_BUFFERED_IMU_SAMPLES);
+                self.imu_samples.drain(..excess);
+            }
+        }
+        Some(state)
+    }
+
+    /// Rumble bytes of the last rumble report sent.
+    pub(crate) fn rumble_data(&self) -> [u8; 8] {
+        self.rumble_data
+    }
+
+    /// Records the rumble bytes of a rumble report that was sent.
+    pub(crate) fn set_rumble_data(&mut self, data: [u8; 8]) {
+        self.rumble_data = data;
+    }
+}
+
+/// Iterator over a controller's input states, from
+/// [`JoyCon::input_states`].
+pub struct InputStates<'a, S: RumbleSink = HidDevice> {
+    joycon: &'a mut JoyCon<S>,
+    timeout: Duration,
+    done: bool,
+}
+
+impl<S: RumbleSink> Iterator for InputStates<'_, S> {
+    type Item = Result<InputState, JoyConError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.joycon.read_input(self.timeout).transpose();
+        if !matches!(next, Some(Ok(_))) {
+            self.done = true;
+        }
+        next
+    }
+}
+
+/// Stream of a controller's IMU samples, from [`JoyCon::imu_samples`].
+pub struct ImuSamples<'a, S: RumbleSink = HidDevice> {
+    joycon: &'a mut JoyCon<S>,
+    timeout: Duration,
+    done: bool,
+}
+
+impl<S: RumbleSink> Iterator for ImuSamples<'_, S> {
+    type Item = Result<ImuSample, JoyConError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if let Some(sample) = self.joycon.imu_samples.pop_front() {
+                return Some(Ok(sample));
+            }
+            match self.joycon.read_input(self.timeout) {
+                Ok(Some(_)) => {}
+                Ok(None) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
 }
 
 #[cfg(test)]
@@ -382,6 +1008,7 @@ mod tests {
             product_id,
             usage_page: 0,
             interface_number: 0,
+            usb: false,
             serial: String::new(),
         }
     }
@@ -420,6 +1047,253 @@ mod tests {
         assert_eq!(joycon.get_timing_byte(), 0);
     }
 
+    #[test]
+    fn test_fit_to_band() {
+        assert_eq!(fit_to_band(0.0, LOW_BAND_HZ), 0.0);
+        assert_eq!(fit_to_band(880.0, LOW_BAND_HZ), 440.0);
+        assert_eq!(fit_to_band(55.0, HIGH_BAND_HZ), 110.0);
+        assert_eq!(fit_to_band(440.0, HIGH_BAND_HZ), 440.0);
+    }
+
+    #[test]
+    fn test_rumble_bands_encode_separate_notes() {
+        let device = crate::joycon::SimulatedDevice::new();
+        let mut joycon =
+            JoyCon::with_sink(&create_test_device_info(JOYCON_L_BT), device.clone()).unwrap();
+
+        joycon.rumble_bands((659.3, 0.6), (130.8, 0.8)).unwrap();
+        joycon.rumble(659.3, 0.6).unwrap();
+        let reports = device.reports();
+        let (dual, mono) = (&reports[0].data, &reports[1].data);
+
+        // 659.3 Hz → encoded 193 → HF 388 (9 bits), split over bytes 2 and 3.
+        assert_eq!(dual[2], (388 & 0xFF) as u8);
+        assert_eq!(dual[3] & 0x01, 1);
+        assert_eq!(dual[2..4], mono[2..4]);
+        // 130.8 Hz → encoded 119 → LF 55; a single note plays an octave
+        // down there, 329.6 Hz → encoded 161 → LF 97.
+        assert_eq!(dual[4] & 0x7F, 55);
+        assert_eq!(mono[4] & 0x7F, 161 - 0x40);
+    }
+
+    #[test]
+    fn test_pro_controller_motors_play_separately() {
+        let device = crate::joycon::SimulatedDevice::new();
+        let mut joycon =
+            JoyCon::with_sink(&create_test_device_info(PRO_CONTROLLER), device.clone()).unwrap();
+        let note = |frequency| RumbleCommand {
+            frequency,
+            amplitude: 0.5,
+            wait_before: Duration::ZERO,
+            low_band: None,
+        };
+
+        joycon.rumble(440.0, 0.5).unwrap();
+        joycon.rumble_motor(Motor::Left, &note(261.6)).unwrap();
+        joycon.rumble_motor(Motor::Right, &note(392.0)).unwrap();
+        let reports = device.reports();
+
+        // Plain rumble drives both motors alike.
+        assert_eq!(reports[0].data[2..6], reports[0].data[6..10]);
+        // Each motor command leaves the other motor as it was.
+        assert_eq!(reports[1].data[6..10], reports[0].data[6..10]);
+        assert_ne!(reports[1].data[2..6], reports[0].data[2..6]);
+        assert_eq!(reports[2].data[2..6], reports[1].data[2..6]);
+        assert_ne!(reports[2].data[6..10], reports[1].data[6..10]);
+    }
+
+    #[test]
+    fn test_lights_keep_the_current_note() {
+        let device = crate::joycon::SimulatedDevice::new();
+        let mut joycon =
+            JoyCon::with_sink(&create_test_device_info(JOYCON_R_BT), device.clone()).unwrap();
+
+        joycon.set_player_lights(PlayerLights::count(2)).unwrap();
+        joycon.rumble(440.0, 0.5).unwrap();
+        joycon.set_home_light(&HomeLight::pulse()).unwrap();
+        let reports = device.reports();
+
+        assert_eq!(
+            reports[0].data[..12],
+            [0x01, 0, 0x00, 0x01, 0x40, 0x40, 0x00, 0x01, 0x40, 0x40, 0x30, 0x03]
+        );
+        assert_eq!(reports[2].data[10], 0x38);
+        assert_eq!(reports[2].data[11..36], HomeLight::pulse().to_bytes());
+        // The subcommand repeats the sounding note's rumble bytes.
+        assert_eq!(reports[2].data[2..10], reports[1].data[2..10]);
+    }
+
+    #[test]
+    fn test_subcommands_wait_for_acknowledgement() {
+        let device = crate::joycon::SimulatedDevice::new();
+        let mut joycon =
+            JoyCon::with_sink(&create_test_device_info(JOYCON_L_BT), device.clone()).unwrap();
+
+        joycon.enable_rumble().unwrap();
+        let request = &device.reports()[0].data;
+        assert_eq!(request[0], Command::SubCommand as u8);
+        assert_eq!(request[10..12], [Subcommand::EnableVibration as u8, 0x01]);
+
+        device.reply_with(0x02, 0x82, [0x04, 0x33, 0x01]);
+        let reply = joycon.send_subcommand(0x02, &[]).unwrap();
+        assert_eq!(
+            (reply.ack, reply.data[..3].to_vec()),
+            (0x82, vec![0x04, 0x33, 0x01])
+        );
+
+        // A rejected subcommand is retried before it fails.
+        device.reply_with(0x48, 0x00, []);
+        device.clear();
+        assert!(matches!(
+            joycon.enable_rumble(),
+            Err(JoyConError::Rejected {
+                subcommand: 0x48,
+                ack: 0x00
+            })
+        ));
+        assert_eq!(device.reports().len(), 3);
+
+        device.clear();
+        device.ignore_subcommand(0x03);
+        assert!(matches!(
+            joycon.enable_input_reports(),
+            Err(JoyConError::NoReply(0x03))
+        ));
+        assert_eq!(device.reports().len(), 3);
+        assert_eq!(joycon.query_input().unwrap(), None);
+        assert_eq!(device.reports().len(), 4);
+    }
+
+    #[test]
+    fn test_read_identity_from_flash() {
+        let device = crate::joycon::SimulatedDevice::new();
+        let mut joycon =
+            JoyCon::with_sink(&create_test_device_info(JOYCON_L_BT), device.clone()).unwrap();
+        assert_eq!(joycon.serial(), "");
+        device.write_flash(0x6000, b"XCW10012345678\0\0");
+        device.write_flash(0x6050, &[0xFF, 0x3C, 0x28, 0x1E, 0x0A, 0x0A]);
+        device.write_flash(
+            0x603D,
+            &[0x00, 0x06, 0x5E, 0x00, 0x08, 0x80, 0x00, 0x05, 0x50],
+        );
+
+        let identity = joycon.read_identity().unwrap();
+        assert_eq!(identity.serial.as_deref(), Some("XCW10012345678"));
+        assert_eq!(identity.colors.body.name(), Some("neon red"));
+        assert_eq!(identity.right_stick, None);
+        let left = identity.left_stick.unwrap();
+        assert_eq!((left.center.x, left.center.y), (0x800, 0x800));
+        assert_eq!((left.above.x, left.above.y), (0x600, 0x5E0));
+        assert_eq!(joycon.serial(), "XCW10012345678");
+        assert_eq!(joycon.identity(), Some(&identity));
+
+        // A long read is split into requests of at most 29 bytes.
+        device.clear();
+        assert_eq!(joycon.read_spi(0x6000, 40).unwrap()[..3], *b"XCW");
+        let requests: Vec<u8> = device.reports().iter().map(|r| r.data[15]).collect();
+        assert_eq!(requests, [29, 11]);
+    }
+
+    #[test]
+    fn test_input_reports() {
+        use crate::joycon::{BatteryLevel, Button};
+
+        let device = crate::joycon::SimulatedDevice::new();
+        let mut joycon =
+            JoyCon::with_sink(&create_test_device_info(JOYCON_R_BT), device.clone()).unwrap();
+        joycon.enable_input_reports().unwrap();
+        let request = &device.reports()[0].data;
+        assert_eq!(request[0], Command::SubCommand as u8);
+        assert_eq!(request[10..12], [Subcommand::SetInputMode as u8, 0x30]);
+
+        let mut state = InputState {
+            battery: BatteryLevel::Medium,
+            ..InputState::default()
+        };
+        device.push_input(state.to_report());
+        state.buttons.insert(Button::A);
+        device.push_input(state.to_report());
+        device.push_input([0x3F, 0x00]);
+
+        assert_eq!(joycon.poll_input().unwrap(), Some(state));
+        assert_eq!(joycon.poll_input().unwrap(), None);
+        assert_eq!(joycon.input_state(), Some(state));
+
+        device.push_input(state.to_report());
+        device.push_input(state.to_report());
+        assert_eq!(joycon.input_states(Duration::ZERO).count(), 2);
+
+        device.push_input(
+            InputState {
+                battery: BatteryLevel::Critical,
+                ..state
+            }
+            .to_report(),
+        );
+        assert!(joycon.query_input().unwrap().is_some());
+        assert_eq!(joycon.battery(), Some(BatteryLevel::Critical));
+    }
+
+    #[test]
+    fn test_imu_samples_are_calibrated() {
+        let device = crate::joycon::SimulatedDevice::new();
+        let mut joycon =
+            JoyCon::with_sink(&create_test_device_info(JOYCON_L_BT), device.clone()).unwrap();
+        let report = |accel_z: i16| {
+            let sample = RawImuSample {
+                accel: [0, 0, accel_z],
+                gyro: [100, 0, 0],
+            };
+            let mut report = InputState::default().to_report();
+            RawImuSample::write_report(&[sample; 3], &mut report);
+            report
+        };
+
+        // Samples are ignored until the IMU is on.
+        device.push_input(report(4096));
+        joycon.poll_input().unwrap();
+        assert!(joycon.take_imu_samples().is_empty());
+
+        // Accelerometer origin 96 on Z (so 4 g reads 16480), gyroscope
+        // sensitivity 1036 on X.
+        let mut calibration = [0u8; 24];
+        calibration[4..6].copy_from_slice(&96i16.to_le_bytes());
+        for (i, sensitivity) in [16384i16, 16384, 16480, 1036, 13371, 13371]
+            .into_iter()
+            .enumerate()
+        {
+            let at = [6, 8, 10, 18, 20, 22][i];
+            calibration[at..at + 2].copy_from_slice(&sensitivity.to_le_bytes());
+        }
+        device.write_flash(0x6020, &calibration);
+        joycon.enable_imu().unwrap();
+        assert!(joycon.is_imu_enabled());
+        let requests = device.reports();
+        assert_eq!(requests[0].data[10], Subcommand::SpiFlashRead as u8);
+        assert_eq!(
+            requests.last().unwrap().data[10..12],
+            [Subcommand::EnableImu as u8, 0x01]
+        );
+
+        device.push_input(report(4096 + 96));
+        device.push_input(report(4096 + 96));
+        joycon.poll_input().unwrap();
+        let samples = joycon.take_imu_samples();
+        assert_eq!(samples.len(), 6);
+        assert_eq!(samples[0].acceleration[2], 1.0);
+        assert!((samples[0].angular_rate[0] - 90.35).abs() < 0.01);
+
+        device.push_input(report(0));
+        let streamed: Vec<_> = joycon.imu_samples(Duration::ZERO).collect();
+        assert_eq!(streamed.len(), 3);
+        assert!(streamed[0].as_ref().unwrap().acceleration[2] < 0.0);
+
+        joycon.disable_imu().unwrap();
+        device.push_input(report(4096));
+        joycon.poll_input().unwrap();
+        assert!(joycon.take_imu_samples().is_empty());
+    }
+
     #[test]
     fn test_rumble_parameters() {
         let mut joycon = JoyCon::new(&create_test_device_info(JOYCON_L_BT)).unwrap();
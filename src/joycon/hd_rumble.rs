@@ -0,0 +1,251 @@
+//! HD rumble encoding.
+//!
+//! Each motor takes 4 bytes describing two bands, each with its own
+//! frequency and amplitude:
+//!
+//! | Byte | Bits | Contents |
+//! |------|------|----------|
+//! | 0 | 0-7 | High-band frequency, low 8 bits |
+//! | 1 | 0 | High-band frequency, bit 8 |
+//! | 1 | 1-7 | High-band amplitude |
+//! | 2 | 0-6 | Low-band frequency |
+//! | 2 | 7 | Low-band amplitude, lowest bit |
+//! | 3 | 0-7 | Low-band amplitude, remaining bits plus `0x40` |
+//!
+//! Both frequencies come from the same logarithmic scale,
+//! `round(log2(f / 10) * 32)`, at 32 steps per octave. The high band
+//! stores `(scale - 0x60) * 4` and covers 81.75-1252 Hz; the low band
+//! stores `scale - 0x40` and covers 40.875-626.5 Hz.
+//!
+//! Amplitudes are encoded as a code from 0 to 100 in three ranges, each
+//! logarithmic: 4 steps per doubling below about 0.12, 16 steps up to about
+//! 0.23 and 32 steps above, reaching 1.0 at 100. The high band stores the
+//! code times 2, the low band splits it over bytes 2 and 3.
+//!
+//! # Example
+//!
+//! ```
+//! use musical_joycons::joycon::hd_rumble;
+//!
+//! // A4 at full amplitude in both bands
+//! let bytes = hd_rumble::encode((440.0, 1.0), (440.0, 1.0));
+//! assert_eq!(bytes, [0x3C, 0xC9, 0x6F, 0x72]);
+//!
+//! let (high, low) = hd_rumble::decode(bytes);
+//! assert!((high.0 - 440.0).abs() < 5.0 && high.1 == 1.0);
+//! assert!((low.0 - 440.0).abs() < 5.0 && low.1 == 1.0);
+//! ```
+
+/// Frequency range of the high band, in Hz.
+pub const HIGH_BAND_HZ: (f32, f32) = (81.75, 1252.0);
+
+/// Frequency range of the low band, in Hz.
+pub const LOW_BAND_HZ: (f32, f32) = (40.875, 626.5);
+
+/// Rumble bytes that leave a motor idle: 320 Hz and 160 Hz at zero
+/// amplitude.
+pub const NEUTRAL: [u8; 4] = [0x00, 0x01, 0x40, 0x40];
+
+/// Largest amplitude code, for an amplitude of 1.0.
+pub const MAX_AMPLITUDE_CODE: u8 = 100;
+
+/// Frequency of a silent high band.
+const NEUTRAL_HIGH_HZ: f32 = 320.0;
+
+/// Frequency of a silent low band.
+const NEUTRAL_LOW_HZ: f32 = 160.0;
+
+/// First amplitude code of the middle range.
+const MEDIUM_AMPLITUDE_CODE: u8 = 16;
+
+/// First amplitude code of the top range.
+const HIGH_AMPLITUDE_CODE: u8 = 32;
+
+/// Position of `frequency` on the logarithmic frequency scale.
+fn frequency_scale(frequency: f32) -> f32 {
+    (frequency / 10.0).log2() * 32.0
+}
+
+/// Frequency at a position of the logarithmic frequency scale.
+fn scale_frequency(scale: f32) -> f32 {
+    10.0 * (scale / 32.0).exp2()
+}
+
+/// Encodes a high-band frequency as its 9-bit value, clamped to
+/// [`HIGH_BAND_HZ`].
+pub fn encode_high_frequency(frequency: f32) -> u16 {
+    let step = (frequency_scale(frequency) - 0x60 as f32).round();
+    (step.clamp(1.0, 127.0) as u16) * 4
+}
+
+/// Decodes a 9-bit high-band frequency value, in Hz.
+pub fn decode_high_frequency(value: u16) -> f32 {
+    scale_frequency(f32::from(value & 0x1FF) / 4.0 + 0x60 as f32)
+}
+
+/// Encodes a low-band frequency as its 7-bit value, clamped to
+/// [`LOW_BAND_HZ`].
+pub fn encode_low_frequency(frequency: f32) -> u8 {
+    let step = (frequency_scale(frequency) - 0x40 as f32).round();
+    step.clamp(1.0, 127.0) as u8
+}
+
+/// Decodes a 7-bit low-band frequency value, in Hz.
+pub fn decode_low_frequency(value: u8) -> f32 {
+    scale_frequency(f32::from(value & 0x7F) + 0x40 as f32)
+}
+
+/// Encodes an amplitude, clamped to `0.0..=1.0`, as a code from 0 to
+/// [`MAX_AMPLITUDE_CODE`].
+pub fn encode_amplitude(amplitude: f32) -> u8 {
+    let amplitude = amplitude.clamp(0.0, 1.0);
+    let code = if amplitude >= decode_amplitude(HIGH_AMPLITUDE_CODE) {
+        (amplitude * 8.7).log2() * 32.0
+    } else if amplitude >= decode_amplitude(MEDIUM_AMPLITUDE_CODE) {
+        (amplitude * 17.0).log2() * 16.0
+    } else if amplitude > 0.0 {
+        (amplitude * 100.0).log2() * 4.0 + 1.0
+    } else {
+        0.0
+    };
+    code.round().clamp(0.0, f32::from(MAX_AMPLITUDE_CODE)) as u8
+}
+
+/// Decodes an amplitude code; codes above [`MAX_AMPLITUDE_CODE`] are
+/// clamped to 1.0.
+pub fn decode_amplitude(code: u8) -> f32 {
+    let code = code.min(MAX_AMPLITUDE_CODE);
+    let amplitude = match code {
+        0 => 0.0,
+        c if c < MEDIUM_AMPLITUDE_CODE => 0.01 * ((f32::from(c) - 1.0) / 4.0).exp2(),
+        c if c < HIGH_AMPLITUDE_CODE => (f32::from(c) / 16.0).exp2() / 17.0,
+        c => (f32::from(c) / 32.0).exp2() / 8.7,
+    };
+    amplitude.min(1.0)
+}
+
+/// Encodes one motor's `(frequency, amplitude)` for each band.
+///
+/// Frequencies outside a band are clamped to it; callers that want notes
+/// to stay in tune octave-shift them first. A band with no frequency or no
+/// amplitude is encoded as in [`NEUTRAL`].
+pub fn encode(high: (f32, f32), low: (f32, f32)) -> [u8; 4] {
+    let silent = |(frequency, amplitude): (f32, f32)| frequency <= 0.0 || amplitude <= 0.0;
+    let (high_frequency, high_amplitude) = if silent(high) {
+        (NEUTRAL_HIGH_HZ, 0)
+    } else {
+        (high.0, encode_amplitude(high.1))
+    };
+    let (low_frequency, low_amplitude) = if silent(low) {
+        (NEUTRAL_LOW_HZ, 0)
+    } else {
+        (low.0, encode_amplitude(low.1))
+    };
+
+    let hf = encode_high_frequency(high_frequency);
+    let lf = encode_low_frequency(low_frequency);
+    [
+        (hf & 0xFF) as u8,
+        high_amplitude << 1 | (hf >> 8) as u8,
+        (low_amplitude & 0x01) << 7 | lf,
+        0x40 + (low_amplitude >> 1),
+    ]
+}
+
+/// Decodes one motor's bytes into `(frequency, amplitude)` for the high
+/// and low band.
+pub fn decode(bytes: [u8; 4]) -> ((f32, f32), (f32, f32)) {
+    let hf = u16::from(bytes[0]) | u16::from(bytes[1] & 0x01) << 8;
+    let high_amplitude = bytes[1] >> 1;
+    let lf = bytes[2] & 0x7F;
+    let low_amplitude = bytes[3].saturating_sub(0x40) << 1 | bytes[2] >> 7;
+    (
+        (decode_high_frequency(hf), decode_amplitude(high_amplitude)),
+        (decode_low_frequency(lf), decode_amplitude(low_amplitude)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() <= tolerance
+    }
+
+    #[test]
+    fn known_byte_vectors() {
+        assert_eq!(encode((0.0, 0.0), (0.0, 0.0)), NEUTRAL);
+        assert_eq!(encode((440.0, 0.0), (440.0, 0.0)), NEUTRAL);
+        assert_eq!(encode((440.0, 1.0), (440.0, 1.0)), [0x3C, 0xC9, 0x6F, 0x72]);
+
+        // 659.3 Hz sits at 193 on the scale: HF 388 (bit 8 set), and
+        // 130.8 Hz at 119: LF 55.
+        let bytes = encode((659.3, 0.5), (130.8, 0.5));
+        assert_eq!(encode_high_frequency(659.3), 388);
+        assert_eq!(bytes[0], 0x84);
+        assert_eq!(bytes[1] & 0x01, 1);
+        assert_eq!(bytes[2] & 0x7F, 55);
+
+        // The smallest amplitude sets the low band's split-off bit.
+        assert_eq!(encode_amplitude(0.01), 1);
+        assert_eq!(
+            encode((320.0, 0.01), (160.0, 0.01)),
+            [0x00, 0x03, 0xC0, 0x40]
+        );
+
+        let ((high_frequency, high_amplitude), (low_frequency, low_amplitude)) = decode(NEUTRAL);
+        assert!(close(high_frequency, 320.0, 0.01));
+        assert!(close(low_frequency, 160.0, 0.01));
+        assert_eq!((high_amplitude, low_amplitude), (0.0, 0.0));
+    }
+
+    #[test]
+    fn frequencies_stay_in_band() {
+        assert_eq!(encode_high_frequency(2000.0), 0x1FC);
+        assert_eq!(encode_high_frequency(20.0), 0x004);
+        assert_eq!(encode_low_frequency(880.0), 0x7F);
+        assert_eq!(encode_low_frequency(20.0), 0x01);
+
+        assert!(close(decode_high_frequency(0x004), HIGH_BAND_HZ.0, 0.1));
+        assert!(close(decode_high_frequency(0x1FC), HIGH_BAND_HZ.1, 1.0));
+        assert!(close(decode_low_frequency(0x01), LOW_BAND_HZ.0, 0.1));
+        assert!(close(decode_low_frequency(0x7F), LOW_BAND_HZ.1, 0.5));
+    }
+
+    #[test]
+    fn every_code_round_trips() {
+        for code in 0..=MAX_AMPLITUDE_CODE {
+            assert_eq!(
+                encode_amplitude(decode_amplitude(code)),
+                code,
+                "code {code}"
+            );
+        }
+        for step in 1..=127u16 {
+            let hf = step * 4;
+            assert_eq!(encode_high_frequency(decode_high_frequency(hf)), hf);
+        }
+        for lf in 1..=127u8 {
+            assert_eq!(encode_low_frequency(decode_low_frequency(lf)), lf);
+        }
+        assert_eq!(decode_amplitude(MAX_AMPLITUDE_CODE), 1.0);
+        assert_eq!(encode_amplitude(2.0), MAX_AMPLITUDE_CODE);
+
+        // Whole motor states survive a decode and re-encode.
+        for bytes in [NEUTRAL, [0x3C, 0xC9, 0x6F, 0x72], [0x84, 0x65, 0xB7, 0x58]] {
+            let (high, low) = decode(bytes);
+            assert_eq!(encode(high, low), bytes);
+        }
+    }
+
+    #[test]
+    fn amplitude_ranges_meet() {
+        // The documented range boundaries, and monotonic in between.
+        assert!(close(decode_amplitude(MEDIUM_AMPLITUDE_CODE), 0.12, 0.005));
+        assert!(close(decode_amplitude(HIGH_AMPLITUDE_CODE), 0.23, 0.005));
+        for code in 1..=MAX_AMPLITUDE_CODE {
+            assert!(decode_amplitude(code) > decode_amplitude(code - 1));
+        }
+    }
+}
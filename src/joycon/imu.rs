@@ -0,0 +1,277 @@
+//! Motion sensor (IMU) data.
+//!
+//! Once [`JoyCon::enable_imu`](super::JoyCon::enable_imu) turns the
+//! 6-axis sensor on, every standard full input report (`0x30`) carries
+//! three samples taken 5 ms apart, oldest first, after the 13-byte header:
+//!
+//! ```text
+//! [Header x13][Sample 0 x12][Sample 1 x12][Sample 2 x12]
+//! Sample: [Accel X][Accel Y][Accel Z][Gyro X][Gyro Y][Gyro Z]  (i16, little-endian)
+//! ```
+//!
+//! [`RawImuSample::parse_report`] decodes the raw values and an
+//! [`ImuCalibration`] from SPI flash turns them into an [`ImuSample`] in
+//! g and degrees per second. Axes are the controller's own, so a Left and
+//! a Right Joy-Con held the same way report mirrored values.
+//!
+//! # Example
+//!
+//! ```
+//! use musical_joycons::joycon::{ImuCalibration, InputState, RawImuSample};
+//!
+//! let resting = RawImuSample {
+//!     accel: [0, 0, 4096],
+//!     gyro: [0, 0, 0],
+//! };
+//! let mut report = InputState::default().to_report();
+//! RawImuSample::write_report(&[resting; 3], &mut report);
+//!
+//! let samples = RawImuSample::parse_report(&report).unwrap();
+//! let sample = ImuCalibration::default().apply(samples[2]);
+//! assert_eq!(sample.acceleration, [0.0, 0.0, 1.0]);
+//! ```
+
+use std::time::Duration;
+
+use super::input::{HEADER_LEN, NFC_IR_REPORT, REPORT_LEN, STANDARD_FULL_REPORT};
+
+/// Samples in each input report.
+pub const IMU_SAMPLES_PER_REPORT: usize = 3;
+
+/// Time between two consecutive samples.
+pub const IMU_SAMPLE_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Address of the factory IMU calibration.
+pub(crate) const IMU_CALIBRATION_ADDRESS: u32 = 0x6020;
+/// Length of the IMU calibration.
+pub(crate) const IMU_CALIBRATION_LEN: usize = 24;
+
+/// Length of one sample in a report.
+const SAMPLE_LEN: usize = 12;
+
+/// Acceleration, in g, of a raw value of `sensitivity - origin`.
+const ACCEL_RANGE_G: f32 = 4.0;
+
+/// Angular rate, in degrees per second, of a raw value of
+/// `sensitivity - origin`.
+const GYRO_RANGE_DPS: f32 = 936.0;
+
+/// Accelerometer sensitivity of an uncalibrated controller.
+const DEFAULT_ACCEL_SENSITIVITY: i16 = 16384;
+
+/// Gyroscope sensitivity of an uncalibrated controller.
+const DEFAULT_GYRO_SENSITIVITY: i16 = 13371;
+
+/// One uncalibrated 6-axis sample, as the controller sends it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RawImuSample {
+    /// Accelerometer X, Y and Z.
+    pub accel: [i16; 3],
+    /// Gyroscope X, Y and Z.
+    pub gyro: [i16; 3],
+}
+
+impl RawImuSample {
+    /// Decodes the three samples of a full input report (`0x30` or
+    /// `0x31`), oldest first.
+    ///
+    /// Returns `None` for other reports and for reports too short to hold
+    /// the samples.
+    pub fn parse_report(report: &[u8]) -> Option<[Self; IMU_SAMPLES_PER_REPORT]> {
+        if report.len() < REPORT_LEN || !matches!(report[0], STANDARD_FULL_REPORT | NFC_IR_REPORT) {
+            return None;
+        }
+        Some(std::array::from_fn(|i| {
+            Self::decode(&report[HEADER_LEN + i * SAMPLE_LEN..][..SAMPLE_LEN])
+        }))
+    }
+
+    /// Writes three samples into a full input report, the inverse of
+    /// [`parse_report`](Self::parse_report).
+    ///
+    /// Useful for feeding a [`SimulatedDevice`](super::SimulatedDevice)
+    /// with reports from [`InputState::to_report`](super::InputState::to_report).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `report` is shorter than a full input report.
+    pub fn write_report(samples: &[Self; IMU_SAMPLES_PER_REPORT], report: &mut [u8]) {
+        for (i, sample) in samples.iter().enumerate() {
+            let start = HEADER_LEN + i * SAMPLE_LEN;
+            report[start..start + SAMPLE_LEN].copy_from_slice(&sample.encode());
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        let axis = |i: usize| i16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]]);
+        Self {
+            accel: [axis(0), axis(1), axis(2)],
+            gyro: [axis(3), axis(4), axis(5)],
+        }
+    }
+
+    fn encode(self) -> [u8; SAMPLE_LEN] {
+        let mut bytes = [0u8; SAMPLE_LEN];
+        for (i, value) in self.accel.into_iter().chain(self.gyro).enumerate() {
+            bytes[i * 2..i * 2 + 2].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// One calibrated 6-axis sample.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImuSample {
+    /// Acceleration along X, Y and Z, in g. A controller at rest reads
+    /// about 1 g in total, from gravity.
+    pub acceleration: [f32; 3],
+    /// Angular rate around X, Y and Z, in degrees per second.
+    pub angular_rate: [f32; 3],
+}
+
+/// Length of an acceleration or angular rate vector, such as those of an
+/// [`ImuSample`].
+pub(crate) fn magnitude(vector: [f32; 3]) -> f32 {
+    vector.iter().map(|v| v * v).sum::<f32>().sqrt()
+}
+
+/// Factory calibration of a controller's accelerometer and gyroscope.
+///
+/// Each axis maps `origin` to zero and `sensitivity` to 4 g or
+/// 936 °/s. The [`Default`] is the nominal calibration, used when the
+/// flash holds none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImuCalibration {
+    /// Accelerometer reading at rest on each axis.
+    pub accel_origin: [i16; 3],
+    /// Accelerometer reading of 4 g on each axis.
+    pub accel_sensitivity: [i16; 3],
+    /// Gyroscope reading at rest on each axis.
+    pub gyro_origin: [i16; 3],
+    /// Gyroscope reading of 936 °/s on each axis.
+    pub gyro_sensitivity: [i16; 3],
+}
+
+impl Default for ImuCalibration {
+    fn default() -> Self {
+        Self {
+            accel_origin: [0; 3],
+            accel_sensitivity: [DEFAULT_ACCEL_SENSITIVITY; 3],
+            gyro_origin: [0; 3],
+            gyro_sensitivity: [DEFAULT_GYRO_SENSITIVITY; 3],
+        }
+    }
+}
+
+impl ImuCalibration {
+    /// Decodes the 24-byte calibration block: accelerometer origin and
+    /// sensitivity, then gyroscope origin and sensitivity. Returns `None`
+    /// if the block is erased or unusable.
+    pub(crate) fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < IMU_CALIBRATION_LEN
+            || bytes[..IMU_CALIBRATION_LEN] == [0xFF; IMU_CALIBRATION_LEN]
+        {
+            return None;
+        }
+        let triple = |slot: usize| -> [i16; 3] {
+            std::array::from_fn(|i| {
+                let at = slot * 6 + i * 2;
+                i16::from_le_bytes([bytes[at], bytes[at + 1]])
+            })
+        };
+        let calibration = Self {
+            accel_origin: triple(0),
+            accel_sensitivity: triple(1),
+            gyro_origin: triple(2),
+            gyro_sensitivity: triple(3),
+        };
+        let usable = (0..3).all(|i| {
+            calibration.accel_sensitivity[i] != calibration.accel_origin[i]
+                && calibration.gyro_sensitivity[i] != calibration.gyro_origin[i]
+        });
+        usable.then_some(calibration)
+    }
+
+    /// Converts a raw sample to g and degrees per second.
+    pub fn apply(&self, raw: RawImuSample) -> ImuSample {
+        let scale = |value: i16, origin: i16, sensitivity: i16, range: f32| {
+            let offset = f32::from(value) - f32::from(origin);
+            offset * range / (f32::from(sensitivity) - f32::from(origin))
+        };
+        ImuSample {
+            acceleration: std::array::from_fn(|i| {
+                scale(
+                    raw.accel[i],
+                    self.accel_origin[i],
+                    self.accel_sensitivity[i],
+                    ACCEL_RANGE_G,
+                )
+            }),
+            angular_rate: std::array::from_fn(|i| {
+                scale(
+                    raw.gyro[i],
+                    self.gyro_origin[i],
+                    self.gyro_sensitivity[i],
+                    GYRO_RANGE_DPS,
+                )
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joycon::InputState;
+
+    #[test]
+    fn parses_captured_samples() {
+        // A Right Joy-Con lying flat and turning slowly around Z.
+        let mut report = InputState::default().to_report();
+        report[13..25].copy_from_slice(&[
+            0xF4, 0xFF, 0x1C, 0x00, 0x02, 0x10, 0x05, 0x00, 0xFB, 0xFF, 0xC8, 0x00,
+        ]);
+        let samples = RawImuSample::parse_report(&report).unwrap();
+        assert_eq!(samples[0].accel, [-12, 28, 4098]);
+        assert_eq!(samples[0].gyro, [5, -5, 200]);
+        assert_eq!(samples[1], RawImuSample::default());
+
+        let mut round_trip = InputState::default().to_report();
+        RawImuSample::write_report(&samples, &mut round_trip);
+        assert_eq!(round_trip, report);
+
+        assert!(RawImuSample::parse_report(&report[..40]).is_none());
+        report[0] = 0x21;
+        assert!(RawImuSample::parse_report(&report).is_none());
+    }
+
+    #[test]
+    fn calibration_scales_each_axis() {
+        let mut block = Vec::new();
+        for values in [
+            [20i16, -30, 10],
+            [16404, 16354, 16394],
+            [-8, 4, 12],
+            [13363, 13375, 13383],
+        ] {
+            block.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+        }
+        let calibration = ImuCalibration::parse(&block).unwrap();
+        assert_eq!(calibration.accel_origin, [20, -30, 10]);
+        assert_eq!(calibration.gyro_sensitivity, [13363, 13375, 13383]);
+
+        let sample = calibration.apply(RawImuSample {
+            accel: [20, -30, 4106],
+            gyro: [-8, 13375, -13359],
+        });
+        assert_eq!(sample.acceleration[..2], [0.0, 0.0]);
+        assert!((sample.acceleration[2] - 1.0).abs() < 1e-6);
+        assert_eq!(sample.angular_rate[0], 0.0);
+        assert_eq!(sample.angular_rate[1], 936.0);
+        assert!((sample.angular_rate[2] + 936.0).abs() < 1e-3);
+
+        assert_eq!(ImuCalibration::parse(&[0xFF; 24]), None);
+        assert_eq!(ImuCalibration::parse(&[0x00; 24]), None);
+        assert_eq!(ImuCalibration::parse(&block[..20]), None);
+    }
+}
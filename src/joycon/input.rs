@@ -0,0 +1,576 @@
+//! Controller input reports.
+//!
+//! After [`JoyCon::enable_input_reports`](super::JoyCon::enable_input_reports)
+//! switches a controller to standard full input mode (`0x30`), it pushes an
+//! input report about 60 times per second. Each report starts with the same
+//! 12-byte header, which [`InputState::parse`] decodes into buttons, analog
+//! sticks, battery and connection state. Subcommand replies (`0x21`) carry
+//! the same header and parse the same way; [`SubcommandReply::parse`] also
+//! decodes their acknowledgement and data.
+//!
+//! # Report Header
+//!
+//! ```text
+//! [ID][Timer][Battery|Connection][Buttons x3][Left stick x3][Right stick x3][Vibrator]
+//! ```
+//!
+//! Stick positions are packed as two 12-bit values in three bytes.
+//!
+//! # Example
+//!
+//! ```
+//! use musical_joycons::joycon::{BatteryLevel, Button, InputState};
+//!
+//! let mut state = InputState::default();
+//! state.buttons.insert(Button::A);
+//! state.battery = BatteryLevel::Low;
+//!
+//! let parsed = InputState::parse(&state.to_report()).unwrap();
+//! assert!(parsed.buttons.contains(Button::A));
+//! assert_eq!(parsed.battery, BatteryLevel::Low);
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::types::Motor;
+
+/// Report ID of a subcommand reply.
+pub(crate) const SUBCOMMAND_REPLY: u8 = 0x21;
+
+/// Report ID of a standard full input report.
+pub(crate) const STANDARD_FULL_REPORT: u8 = 0x30;
+
+/// Report ID of an input report with NFC/IR data.
+pub(crate) const NFC_IR_REPORT: u8 = 0x31;
+
+/// Length of the header shared by all standard input reports.
+pub(crate) const HEADER_LEN: usize = 13;
+
+/// Length of a full input report.
+pub(crate) const REPORT_LEN: usize = 49;
+
+/// Offset of the data in a subcommand reply, after the acknowledgement
+/// byte and the echoed subcommand ID.
+const REPLY_DATA_START: usize = 15;
+
+/// A physical button.
+///
+/// The discriminant is the button's bit in [`Buttons`]; the three report
+/// bytes are right-side, shared and left-side buttons in that order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    /// Y (Right Joy-Con).
+    Y = 0,
+    /// X (Right Joy-Con).
+    X = 1,
+    /// B (Right Joy-Con).
+    B = 2,
+    /// A (Right Joy-Con).
+    A = 3,
+    /// SR on the Right Joy-Con rail.
+    RightSr = 4,
+    /// SL on the Right Joy-Con rail.
+    RightSl = 5,
+    /// R shoulder.
+    R = 6,
+    /// ZR trigger.
+    Zr = 7,
+    /// Minus.
+    Minus = 8,
+    /// Plus.
+    Plus = 9,
+    /// Right stick press.
+    RightStick = 10,
+    /// Left stick press.
+    LeftStick = 11,
+    /// Home.
+    Home = 12,
+    /// Capture.
+    Capture = 13,
+    /// Set while the Joy-Con sits in the charging grip.
+    ChargingGrip = 15,
+    /// D-pad down (Left Joy-Con).
+    Down = 16,
+    /// D-pad up (Left Joy-Con).
+    Up = 17,
+    /// D-pad right (Left Joy-Con).
+    Right = 18,
+    /// D-pad left (Left Joy-Con).
+    Left = 19,
+    /// SR on the Left Joy-Con rail.
+    LeftSr = 20,
+    /// SL on the Left Joy-Con rail.
+    LeftSl = 21,
+    /// L shoulder.
+    L = 22,
+    /// ZL trigger.
+    Zl = 23,
+}
+
+impl Button {
+    /// Every button, in bit order.
+    pub const ALL: [Button; 23] = [
+        Button::Y,
+        Button::X,
+        Button::B,
+        Button::A,
+        Button::RightSr,
+        Button::RightSl,
+        Button::R,
+        Button::Zr,
+        Button::Minus,
+        Button::Plus,
+        Button::RightStick,
+        Button::LeftStick,
+        Button::Home,
+        Button::Capture,
+        Button::ChargingGrip,
+        Button::Down,
+        Button::Up,
+        Button::Right,
+        Button::Left,
+        Button::LeftSr,
+        Button::LeftSl,
+        Button::L,
+        Button::Zl,
+    ];
+
+    /// Lower-case name used in configuration, e.g. `"zr"` or `"left-sl"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Button::Y => "y",
+            Button::X => "x",
+            Button::B => "b",
+            Button::A => "a",
+            Button::RightSr => "right-sr",
+            Button::RightSl => "right-sl",
+            Button::R => "r",
+            Button::Zr => "zr",
+            Button::Minus => "minus",
+            Button::Plus => "plus",
+            Button::RightStick => "right-stick",
+            Button::LeftStick => "left-stick",
+            Button::Home => "home",
+            Button::Capture => "capture",
+            Button::ChargingGrip => "charging-grip",
+            Button::Down => "down",
+            Button::Up => "up",
+            Button::Right => "right",
+            Button::Left => "left",
+            Button::LeftSr => "left-sr",
+            Button::LeftSl => "left-sl",
+            Button::L => "l",
+            Button::Zl => "zl",
+        }
+    }
+
+    /// The half of a Pro Controller the button sits on, or `None` for the
+    /// charging grip flag.
+    pub fn side(self) -> Option<Motor> {
+        match self {
+            Button::Y
+            | Button::X
+            | Button::B
+            | Button::A
+            | Button::RightSr
+            | Button::RightSl
+            | Button::R
+            | Button::Zr
+            | Button::Plus
+            | Button::RightStick
+            | Button::Home => Some(Motor::Right),
+            Button::ChargingGrip => None,
+            _ => Some(Motor::Left),
+        }
+    }
+
+    fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error returned when parsing an unknown [`Button`] name.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown button {0:?}")]
+pub struct ParseButtonError(String);
+
+impl FromStr for Button {
+    type Err = ParseButtonError;
+
+    /// Parses a [`name`](Button::name), ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Button::ALL
+            .into_iter()
+            .find(|button| button.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| ParseButtonError(s.to_string()))
+    }
+}
+
+/// A set of pressed buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Buttons(u32);
+
+impl Buttons {
+    /// Returns `true` if `button` is pressed.
+    pub fn contains(self, button: Button) -> bool {
+        self.0 & button.mask() != 0
+    }
+
+    /// Marks `button` as pressed.
+    pub fn insert(&mut self, button: Button) {
+        self.0 |= button.mask();
+    }
+
+    /// Returns `true` if nothing is pressed.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Buttons pressed now that were not pressed in `previous`.
+    pub fn pressed_since(self, previous: Buttons) -> Buttons {
+        Buttons(self.0 & !previous.0)
+    }
+
+    /// The pressed buttons on one half of the controller, as if that half
+    /// were a Joy-Con of its own.
+    pub fn on_side(self, side: Motor) -> Buttons {
+        self.iter().filter(|b| b.side() == Some(side)).collect()
+    }
+
+    /// Iterates over the pressed buttons in bit order.
+    pub fn iter(self) -> impl Iterator<Item = Button> {
+        Button::ALL.into_iter().filter(move |&b| self.contains(b))
+    }
+}
+
+impl FromIterator<Button> for Buttons {
+    fn from_iter<I: IntoIterator<Item = Button>>(iter: I) -> Self {
+        let mut buttons = Buttons::default();
+        for button in iter {
+            buttons.insert(button);
+        }
+        buttons
+    }
+}
+
+/// Raw 12-bit analog stick position.
+///
+/// Values are uncalibrated; a centred stick reads roughly `(2048, 2048)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StickPosition {
+    /// Horizontal axis, `0..=4095`, increasing to the right.
+    pub x: u16,
+    /// Vertical axis, `0..=4095`, increasing upwards.
+    pub y: u16,
+}
+
+impl Default for StickPosition {
+    fn default() -> Self {
+        Self { x: 2048, y: 2048 }
+    }
+}
+
+impl StickPosition {
+    /// The position mapped to `-1.0..=1.0` around the nominal centre.
+    pub fn normalized(self) -> (f32, f32) {
+        let axis = |v: u16| ((f32::from(v) - 2048.0) / 2048.0).clamp(-1.0, 1.0);
+        (axis(self.x), axis(self.y))
+    }
+
+    pub(crate) fn decode(bytes: &[u8]) -> Self {
+        Self {
+            x: u16::from(bytes[0]) | (u16::from(bytes[1] & 0x0F) << 8),
+            y: u16::from(bytes[1] >> 4) | (u16::from(bytes[2]) << 4),
+        }
+    }
+
+    pub(crate) fn encode(self) -> [u8; 3] {
+        let (x, y) = (self.x & 0x0FFF, self.y & 0x0FFF);
+        [
+            (x & 0xFF) as u8,
+            ((x >> 8) as u8) | (((y & 0x0F) as u8) << 4),
+            (y >> 4) as u8,
+        ]
+    }
+}
+
+/// Battery charge as reported by the controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum BatteryLevel {
+    /// Empty; the controller is about to switch off.
+    Empty,
+    /// Critically low.
+    Critical,
+    /// Low.
+    Low,
+    /// Medium.
+    Medium,
+    /// Full.
+    #[default]
+    Full,
+}
+
+impl BatteryLevel {
+    fn from_nibble(nibble: u8) -> Self {
+        match nibble >> 1 {
+            0 => Self::Empty,
+            1 => Self::Critical,
+            2 => Self::Low,
+            3 => Self::Medium,
+            _ => Self::Full,
+        }
+    }
+
+    fn to_nibble(self) -> u8 {
+        (self as u8) << 1
+    }
+}
+
+impl fmt::Display for BatteryLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Empty => "empty",
+            Self::Critical => "critical",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::Full => "full",
+        })
+    }
+}
+
+/// Controller state from one input report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InputState {
+    /// Report counter, incremented by the controller for every report.
+    pub timer: u8,
+    /// Battery charge.
+    pub battery: BatteryLevel,
+    /// The battery is charging.
+    pub charging: bool,
+    /// The controller reports itself as a Joy-Con (as opposed to a Pro
+    /// Controller or charging grip).
+    pub is_joycon: bool,
+    /// The controller is powered by the Switch or USB.
+    pub externally_powered: bool,
+    /// Pressed buttons.
+    pub buttons: Buttons,
+    /// Left stick (Left Joy-Con and Pro Controller).
+    pub left_stick: StickPosition,
+    /// Right stick (Right Joy-Con and Pro Controller).
+    pub right_stick: StickPosition,
+}
+
+impl InputState {
+    /// Parses the header of a standard input report.
+    ///
+    /// Accepts full input reports (`0x30`, `0x31`) and subcommand replies
+    /// (`0x21`). Returns `None` for other report types and for reports
+    /// shorter than the header.
+    pub fn parse(report: &[u8]) -> Option<Self> {
+        if report.len() < HEADER_LEN
+            || !matches!(
+                report[0],
+                SUBCOMMAND_REPLY | STANDARD_FULL_REPORT | NFC_IR_REPORT
+            )
+        {
+            return None;
+        }
+        let battery = report[2] >> 4;
+        let connection = report[2] & 0x0F;
+        Some(Self {
+            timer: report[1],
+            battery: BatteryLevel::from_nibble(battery),
+            charging: battery & 0x01 != 0,
+            is_joycon: (connection >> 1) & 0x03 == 0x03,
+            externally_powered: connection & 0x01 != 0,
+            buttons: Buttons(
+                u32::from(report[3]) | u32::from(report[4]) << 8 | u32::from(report[5]) << 16,
+            ),
+            left_stick: StickPosition::decode(&report[6..9]),
+            right_stick: StickPosition::decode(&report[9..12]),
+        })
+    }
+
+    /// Encodes the state as a `0x30` report, the inverse of
+    /// [`parse`](Self::parse).
+    ///
+    /// Useful for feeding a [`SimulatedDevice`](super::SimulatedDevice).
+    pub fn to_report(&self) -> Vec<u8> {
+        let mut report = vec![0u8; REPORT_LEN];
+        report[0] = STANDARD_FULL_REPORT;
+        report[1] = self.timer;
+        let battery = self.battery.to_nibble() | u8::from(self.charging);
+        let connection =
+            if self.is_joycon { 0x06 } else { 0x00 } | u8::from(self.externally_powered);
+        report[2] = battery << 4 | connection;
+        report[3..6].copy_from_slice(&self.buttons.0.to_le_bytes()[..3]);
+        report[6..9].copy_from_slice(&self.left_stick.encode());
+        report[9..12].copy_from_slice(&self.right_stick.encode());
+        report
+    }
+}
+
+/// A controller's reply to a subcommand (input report `0x21`).
+///
+/// After the standard header come the acknowledgement byte, the ID of the
+/// subcommand being answered and up to 35 bytes of reply data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubcommandReply {
+    /// Controller state from the report header.
+    pub state: InputState,
+    /// Acknowledgement byte: bit 7 set means the subcommand was accepted,
+    /// the low bits identify the type of `data`.
+    pub ack: u8,
+    /// ID of the subcommand this reply answers.
+    pub subcommand: u8,
+    /// Reply data, zero-padded to the end of the report.
+    pub data: Vec<u8>,
+}
+
+impl SubcommandReply {
+    /// Parses a `0x21` report. Returns `None` for other reports and for
+    /// reports too short to name the subcommand.
+    pub fn parse(report: &[u8]) -> Option<Self> {
+        if report.first() != Some(&SUBCOMMAND_REPLY) || report.len() < REPLY_DATA_START {
+            return None;
+        }
+        Some(Self {
+            state: InputState::parse(report)?,
+            ack: report[REPLY_DATA_START - 2],
+            subcommand: report[REPLY_DATA_START - 1],
+            data: report[REPLY_DATA_START..].to_vec(),
+        })
+    }
+
+    /// Returns `true` if the controller accepted the subcommand.
+    pub fn is_ack(&self) -> bool {
+        self.ack & 0x80 != 0
+    }
+
+    /// Encodes the reply as a `0x21` report, the inverse of
+    /// [`parse`](Self::parse). Data beyond the report length is dropped.
+    pub fn to_report(&self) -> Vec<u8> {
+        let mut report = self.state.to_report();
+        report[0] = SUBCOMMAND_REPLY;
+        report[REPLY_DATA_START - 2] = self.ack;
+        report[REPLY_DATA_START - 1] = self.subcommand;
+        let len = self.data.len().min(REPORT_LEN - REPLY_DATA_START);
+        report[REPLY_DATA_START..REPLY_DATA_START + len].copy_from_slice(&self.data[..len]);
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_captured_report() {
+        // A Right Joy-Con with medium battery, A and Plus held, stick at rest.
+        let mut report = [0u8; REPORT_LEN];
+        report[..12].copy_from_slice(&[
+            0x30, 0x5A, 0x6E, 0x08, 0x02, 0x00, 0x00, 0x00, 0x00, 0x2E, 0xF8, 0x75,
+        ]);
+        let state = InputState::parse(&report).unwrap();
+
+        assert_eq!(state.timer, 0x5A);
+        assert_eq!(state.battery, BatteryLevel::Medium);
+        assert!(!state.charging);
+        assert!(state.is_joycon);
+        assert!(!state.externally_powered);
+        assert_eq!(
+            state.buttons.iter().collect::<Vec<_>>(),
+            [Button::A, Button::Plus]
+        );
+        assert_eq!(state.right_stick, StickPosition { x: 0x82E, y: 0x75F });
+
+        assert_eq!(InputState::parse(&[0x3F; REPORT_LEN]), None);
+        assert_eq!(InputState::parse(&report[..8]), None);
+    }
+
+    #[test]
+    fn buttons_split_by_side() {
+        let buttons: Buttons = [Button::A, Button::Plus, Button::Zl, Button::Capture]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            buttons.on_side(Motor::Right).iter().collect::<Vec<_>>(),
+            [Button::A, Button::Plus]
+        );
+        assert_eq!(
+            buttons.on_side(Motor::Left).iter().collect::<Vec<_>>(),
+            [Button::Capture, Button::Zl]
+        );
+    }
+
+    #[test]
+    fn report_round_trips() {
+        let state = InputState {
+            timer: 7,
+            battery: BatteryLevel::Critical,
+            charging: true,
+            is_joycon: false,
+            externally_powered: true,
+            buttons: [Button::Zl, Button::Home, Button::Y].into_iter().collect(),
+            left_stick: StickPosition { x: 0, y: 4095 },
+            right_stick: StickPosition { x: 1234, y: 3210 },
+        };
+        assert_eq!(InputState::parse(&state.to_report()), Some(state));
+    }
+
+    #[test]
+    fn subcommand_reply_round_trips() {
+        let reply = SubcommandReply {
+            state: InputState {
+                battery: BatteryLevel::Low,
+                ..InputState::default()
+            },
+            ack: 0x90,
+            subcommand: 0x10,
+            data: vec![0x00, 0x60, 0x00, 0x00, 0x02, 0xAB, 0xCD],
+        };
+        let report = reply.to_report();
+        assert_eq!(report.len(), REPORT_LEN);
+        assert_eq!(report[13..17], [0x90, 0x10, 0x00, 0x60]);
+
+        let parsed = SubcommandReply::parse(&report).unwrap();
+        assert!(parsed.is_ack());
+        assert_eq!(parsed.state, reply.state);
+        assert_eq!(parsed.data[..7], reply.data[..]);
+        assert_eq!(parsed.data.len(), REPORT_LEN - REPLY_DATA_START);
+
+        assert!(SubcommandReply::parse(&reply.state.to_report()).is_none());
+        assert!(!SubcommandReply {
+            ack: 0x00,
+            ..parsed
+        }
+        .is_ack());
+    }
+
+    #[test]
+    fn button_names_round_trip() {
+        for button in Button::ALL {
+            assert_eq!(button.name().parse(), Ok(button));
+        }
+        assert_eq!("ZR".parse(), Ok(Button::Zr));
+        assert!("turbo".parse::<Button>().is_err());
+    }
+
+    #[test]
+    fn pressed_since_reports_new_presses_only() {
+        let before: Buttons = [Button::A, Button::B].into_iter().collect();
+        let now: Buttons = [Button::B, Button::X].into_iter().collect();
+        assert_eq!(
+            now.pressed_since(before).iter().collect::<Vec<_>>(),
+            [Button::X]
+        );
+        assert!(before.pressed_since(before).is_empty());
+    }
+}
@@ -1,8 +1,8 @@
 //! Low-level JoyCon HID communication interface.
 //!
 //! This module handles the raw HID protocol for communicating with JoyCon devices.
-//! It implements the rumble encoding algorithm and packet formatting required by
-//! the JoyCon firmware.
+//! It handles the packet formatting required by the JoyCon firmware; the rumble
+//! bytes themselves come from [`hd_rumble`](super::hd_rumble).
 //!
 //! # Protocol Details
 //!
@@ -12,15 +12,38 @@
 //! - Bytes 2-9: Rumble data (4 bytes per motor, left then right)
 //! - Bytes 10+: Subcommand data (if applicable)
 //!
+//! The controller answers every subcommand with a `0x21` input report that
+//! echoes the subcommand ID; [`JoyconInterface::request`] waits for it.
+//!
+//! Controllers connected by USB first need a handshake of `0x80` reports,
+//! each answered by a `0x81` report echoing the command; see
+//! [`JoyconInterface::usb_handshake`].
+//!
 //! # Rumble Encoding
 //!
-//! The JoyCon uses a proprietary rumble encoding:
-//! - Frequency is encoded logarithmically
-//! - Amplitude has different encoding for high/low frequency components
-//! - Left and Right JoyCons use different byte offsets
+//! Each motor takes 4 bytes of [`hd_rumble`](super::hd_rumble) data:
+//! - Left and Right JoyCons use different byte offsets; a Pro Controller
+//!   has a motor at each
+
+use std::time::{Duration, Instant};
 
+use super::backend::RumbleSink;
 use super::device::JoyCon;
-use super::types::{Command, JoyConError, JoyConType, Subcommand};
+use super::hd_rumble;
+use super::input::SubcommandReply;
+use super::types::{
+    Command, JoyConError, JoyConType, Motor, Subcommand, UsbCommand, USB_COMMAND, USB_REPLY,
+};
+
+/// How long to wait for the reply to a subcommand before sending it again.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// How many times a subcommand is sent before giving up on a reply.
+const REPLY_ATTEMPTS: u32 = 3;
+
+/// How long to sleep when a sink has no report yet, so waiting for a
+/// reply does not spin on sinks that return without waiting.
+const REPLY_POLL_INTERVAL: Duration = Duration::from_millis(2);
 
 /// Internal interface for JoyCon HID communication.
 ///
@@ -43,22 +66,170 @@ impl JoyconInterface {
     /// ```text
     /// [Command][Timing][Rumble x8][Subcommand][Data...]
     /// ```
-    pub fn send_command(
-        joycon: &mut JoyCon,
+    pub fn send_command<S: RumbleSink>(
+        joycon: &mut JoyCon<S>,
         command: Command,
         subcommand: Option<Subcommand>,
         data: &[u8],
+    ) -> Result<(), JoyConError> {
+        Self::send_raw_command(joycon, command, subcommand.map(|s| s as u8), data)
+    }
+
+    /// Sends a subcommand and waits for the controller to acknowledge it.
+    ///
+    /// Reports read while waiting still update the JoyCon's
+    /// [`input_state`](JoyCon::input_state). If no reply with a matching
+    /// subcommand ID arrives within 200 ms, or the reply does not
+    /// acknowledge it, the subcommand is sent again, up to three times in
+    /// all.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JoyConError::NoReply`] if every attempt timed out and
+    /// [`JoyConError::Rejected`] if the last reply does not acknowledge the
+    /// subcommand, besides the errors of sending and reading.
+    pub fn request<S: RumbleSink>(
+        joycon: &mut JoyCon<S>,
+        subcommand: u8,
+        data: &[u8],
+    ) -> Result<SubcommandReply, JoyConError> {
+        Self::request_with_attempts(joycon, subcommand, data, REPLY_ATTEMPTS)
+    }
+
+    /// [`request`](Self::request) that sends the subcommand at most
+    /// `attempts` times.
+    pub fn request_with_attempts<S: RumbleSink>(
+        joycon: &mut JoyCon<S>,
+        subcommand: u8,
+        data: &[u8],
+        attempts: u32,
+    ) -> Result<SubcommandReply, JoyConError> {
+        let mut error = JoyConError::NoReply(subcommand);
+        for attempt in 1..=attempts {
+            Self::send_raw_command(joycon, Command::SubCommand, Some(subcommand), data)?;
+            match Self::await_reply(joycon, subcommand)? {
+                Some(reply) if reply.is_ack() => return Ok(reply),
+                Some(reply) => {
+                    log::debug!(
+                        "Subcommand {:#04x} rejected with {:#04x} (attempt {}/{})",
+                        subcommand,
+                        reply.ack,
+                        attempt,
+                        attempts
+                    );
+                    error = JoyConError::Rejected {
+                        subcommand,
+                        ack: reply.ack,
+                    };
+                }
+                None => {
+                    log::debug!(
+                        "No reply to subcommand {:#04x} (attempt {}/{})",
+                        subcommand,
+                        attempt,
+                        attempts
+                    );
+                    error = JoyConError::NoReply(subcommand);
+                }
+            }
+        }
+        Err(error)
+    }
+
+    /// Reads reports until the reply to `subcommand` arrives or
+    /// [`REPLY_TIMEOUT`] passes.
+    fn await_reply<S: RumbleSink>(
+        joycon: &mut JoyCon<S>,
+        subcommand: u8,
+    ) -> Result<Option<SubcommandReply>, JoyConError> {
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        let mut buf = [0u8; 64];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let handle = joycon.get_handle_mut().ok_or(JoyConError::NotConnected)?;
+            let len = handle.read_report(&mut buf, remaining)?;
+            let report = &buf[..len];
+            joycon.record_report(report);
+            if let Some(reply) = SubcommandReply::parse(report) {
+                if reply.subcommand == subcommand {
+                    return Ok(Some(reply));
+                }
+            }
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            if len == 0 {
+                std::thread::sleep(REPLY_POLL_INTERVAL.min(remaining));
+            }
+        }
+    }
+
+    /// Switches a controller connected by USB to talking HID over its
+    /// cable: status, handshake, 3 Mbit/s, handshake again, then USB-only
+    /// mode so it does not time out.
+    pub fn usb_handshake<S: RumbleSink>(joycon: &mut JoyCon<S>) -> Result<(), JoyConError> {
+        for command in [
+            UsbCommand::Status,
+            UsbCommand::Handshake,
+            UsbCommand::HighSpeed,
+            UsbCommand::Handshake,
+        ] {
+            Self::usb_request(joycon, command)?;
+        }
+        Self::write_to_joycon(joycon, &[USB_COMMAND, UsbCommand::UsbOnly as u8])
+    }
+
+    /// Sends a `0x80` command and waits for its `0x81` answer, retrying
+    /// like [`request`](Self::request).
+    fn usb_request<S: RumbleSink>(
+        joycon: &mut JoyCon<S>,
+        command: UsbCommand,
+    ) -> Result<(), JoyConError> {
+        let command = command as u8;
+        let mut buf = [0u8; 64];
+        for attempt in 1..=REPLY_ATTEMPTS {
+            Self::write_to_joycon(joycon, &[USB_COMMAND, command])?;
+            let deadline = Instant::now() + REPLY_TIMEOUT;
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let handle = joycon.get_handle_mut().ok_or(JoyConError::NotConnected)?;
+                let len = handle.read_report(&mut buf, remaining)?;
+                if len >= 2 && buf[..2] == [USB_REPLY, command] {
+                    return Ok(());
+                }
+                if remaining.is_zero() {
+                    break;
+                }
+                if len == 0 {
+                    std::thread::sleep(REPLY_POLL_INTERVAL.min(remaining));
+                }
+            }
+            log::debug!(
+                "No reply to USB command {:#04x} (attempt {}/{})",
+                command,
+                attempt,
+                REPLY_ATTEMPTS
+            );
+        }
+        Err(JoyConError::NoUsbReply(command))
+    }
+
+    fn send_raw_command<S: RumbleSink>(
+        joycon: &mut JoyCon<S>,
+        command: Command,
+        subcommand: Option<u8>,
+        data: &[u8],
     ) -> Result<(), JoyConError> {
         let mut buf = [0u8; 49];
         buf[0] = command as u8;
         buf[1] = joycon.get_timing_byte();
 
-        // Standard rumble data for neutral state
-        let rumble_base = [0x00, 0x01, 0x40, 0x40, 0x00, 0x01, 0x40, 0x40];
-        buf[2..10].copy_from_slice(&rumble_base);
+        // Repeat the current rumble state so a subcommand does not cut a
+        // sounding note short.
+        buf[2..10].copy_from_slice(&joycon.rumble_data());
 
         if let Some(subcmd) = subcommand {
-            buf[10] = subcmd as u8;
+            buf[10] = subcmd;
             if !data.is_empty() {
                 let end = (11 + data.len()).min(49);
                 buf[11..end].copy_from_slice(&data[..end - 11]);
@@ -70,112 +241,87 @@ impl JoyconInterface {
         Ok(())
     }
 
-    /// Sends a rumble command with the specified frequency and amplitude.
-    ///
-    /// This method encodes the frequency and amplitude into the JoyCon's
-    /// proprietary rumble format and sends it as a HID packet.
-    ///
-    /// # Frequency Encoding
-    ///
-    /// The JoyCon encodes frequency logarithmically:
-    /// ```text
-    /// encoded_freq = round(log2(frequency / 10) * 32)
-    /// ```
-    ///
-    /// This is then split into high-frequency (HF) and low-frequency (LF)
-    /// components based on hardware-specific thresholds.
+    /// Sends independent `(frequency, amplitude)` pairs for the high and low
+    /// rumble bands.
     ///
-    /// # Amplitude Encoding
+    /// Both bands are encoded with [`hd_rumble::encode`], which clamps
+    /// each frequency to its band, and sent as a HID packet.
     ///
-    /// Amplitude encoding varies by intensity:
-    /// - High (>0.23): `round(log2(amplitude * 8.7) * 32)`
-    /// - Medium (>0.12): `round(log2(amplitude * 17.0) * 16)`
-    /// - Low: Same as medium
+    /// # Motor Offsets
     ///
-    /// # JoyCon-Specific Offsets
-    ///
-    /// - Left JoyCon / Pro Controller: Rumble data at bytes 2-5
+    /// - Left JoyCon: Rumble data at bytes 2-5
     /// - Right JoyCon: Rumble data at bytes 6-9
-    ///
-    /// # Arguments
-    ///
-    /// * `joycon` - The JoyCon device to send rumble to
-    /// * `frequency` - Frequency in Hz (clamped to 0-1252)
-    /// * `amplitude` - Amplitude from 0.0 to 1.0
-    pub fn send_rumble(
-        joycon: &mut JoyCon,
-        frequency: f32,
-        amplitude: f32,
+    /// - Pro Controller: The same data for both motors
+    pub fn send_rumble_bands<S: RumbleSink>(
+        joycon: &mut JoyCon<S>,
+        high: (f32, f32),
+        low: (f32, f32),
     ) -> Result<(), JoyConError> {
-        let mut buf = [0u8; 49];
-        buf[0] = Command::Rumble as u8;
-        buf[1] = joycon.get_timing_byte();
-
-        let frequency = frequency.clamp(0.0, 1252.0);
-        let encoded_freq = if frequency > 0.0 {
-            ((frequency / 10.0).log2() * 32.0).round() as u8
-        } else {
-            0
-        };
-
-        // Convert to HF/LF ranges according to formulas, using u16 for HF calculations
-        let hf: u16 = if encoded_freq > 0x60 {
-            let diff = u16::from(encoded_freq - 0x60);
-            diff.saturating_mul(4)
-        } else {
-            0
-        };
-        let lf = encoded_freq.saturating_sub(0x40);
-
-        // Then encode the amplitude according to the ranges
-        let amplitude = amplitude.clamp(0.0, 1.0);
-        let encoded_amp = if amplitude > 0.23 {
-            ((amplitude * 8.7).log2() * 32.0).round() as u8
-        } else if amplitude > 0.12 {
-            ((amplitude * 17.0).log2() * 16.0).round() as u8
-        } else {
-            // For very low amplitudes, use the same formula as medium range
-            ((amplitude * 17.0).log2() * 16.0).round() as u8
-        };
-
-        let hf_amp = encoded_amp.saturating_mul(2);
-        let lf_amp = encoded_amp.saturating_div(2).saturating_add(0x40);
-
-        // Pack data according to the correct format for each Joycon type
-        let offset = match joycon.get_type() {
-            JoyConType::Right => 6, // Right JoyCon data starts at byte 6
-            JoyConType::Left | JoyConType::ProController => 2, // Left JoyCon/Pro Controller data starts at byte 2
-            JoyConType::Other(_) => 2, // Default to the same offset as Left/ProController
+        let motor = hd_rumble::encode(high, low);
+        let (left, right) = match joycon.get_type() {
+            JoyConType::Right => (hd_rumble::NEUTRAL, motor),
+            JoyConType::ProController => (motor, motor),
+            JoyConType::Left | JoyConType::Other(_) => (motor, hd_rumble::NEUTRAL),
         };
+        Self::send_rumble_data(joycon, left, right)
+    }
 
-        buf[offset] = (hf & 0xFF) as u8; // Low byte of HF
-        buf[offset + 1] = hf_amp; // High frequency amplitude
-        buf[offset + 2] = lf; // Low frequency
-        buf[offset + 3] = lf_amp; // Low frequency amplitude
+    /// Sends `(frequency, amplitude)` pairs to one motor of a controller
+    /// with two, such as a Pro Controller, leaving the other motor playing
+    /// what it was.
+    pub fn send_motor_bands<S: RumbleSink>(
+        joycon: &mut JoyCon<S>,
+        motor: Motor,
+        high: (f32, f32),
+        low: (f32, f32),
+    ) -> Result<(), JoyConError> {
+        let bands = hd_rumble::encode(high, low);
+        let current = joycon.rumble_data();
+        let mut left = [0u8; 4];
+        let mut right = [0u8; 4];
+        left.copy_from_slice(&current[..4]);
+        right.copy_from_slice(&current[4..]);
+        match motor {
+            Motor::Left => left = bands,
+            Motor::Right => right = bands,
+        }
+        Self::send_rumble_data(joycon, left, right)
+    }
 
-        // Fill in the other side with neutral data
-        let other_offset = if offset == 2 { 6 } else { 2 };
-        buf[other_offset..other_offset + 4].copy_from_slice(&[0x00, 0x01, 0x40, 0x40]);
+    /// Sends a rumble report with the left and right motors' bytes.
+    fn send_rumble_data<S: RumbleSink>(
+        joycon: &mut JoyCon<S>,
+        left: [u8; 4],
+        right: [u8; 4],
+    ) -> Result<(), JoyConError> {
+        let mut buf = [0u8; 49];
+        buf[0] = Command::Rumble as u8;
+        buf[1] = joycon.get_timing_byte();
+        buf[2..6].copy_from_slice(&left);
+        buf[6..10].copy_from_slice(&right);
         Self::write_to_joycon(joycon, &buf)?;
+
+        let mut rumble = [0u8; 8];
+        rumble.copy_from_slice(&buf[2..10]);
+        joycon.set_rumble_data(rumble);
         joycon.increment_timing_byte();
         Ok(())
     }
 
-    fn write_to_joycon(joycon: &JoyCon, buf: &[u8]) -> Result<(), JoyConError> {
-        let handle = joycon.get_handle().ok_or(JoyConError::NotConnected)?;
-
-        match handle.write(buf) {
-            Ok(written) => {
-                if written != buf.len() {
-                    return Err(JoyConError::HidError(format!(
-                        "Incomplete write: {} of {} bytes",
-                        written,
-                        buf.len()
-                    )));
-                }
-                Ok(())
-            }
-            Err(e) => Err(JoyConError::HidError(e.to_string())),
+    fn write_to_joycon<S: RumbleSink>(
+        joycon: &mut JoyCon<S>,
+        buf: &[u8],
+    ) -> Result<(), JoyConError> {
+        let handle = joycon.get_handle_mut().ok_or(JoyConError::NotConnected)?;
+
+        let written = handle.write_report(buf)?;
+        if written != buf.len() {
+            return Err(JoyConError::HidError(format!(
+                "Incomplete write: {} of {} bytes",
+                written,
+                buf.len()
+            )));
         }
+        Ok(())
     }
 }
@@ -0,0 +1,210 @@
+//! Player lights and Home button LED patterns.
+//!
+//! Every controller has four player lights, set with subcommand `0x30`.
+//! The Right Joy-Con and Pro Controller also have a Home button LED, set
+//! with subcommand `0x38`, that can play a sequence of up to 15 fading
+//! "mini cycles".
+
+/// Maximum number of mini cycles in a [`HomeLight`] pattern.
+pub const MAX_HOME_LIGHT_CYCLES: usize = 15;
+
+/// Length of the encoded [`HomeLight`] subcommand argument.
+const HOME_LIGHT_LEN: usize = 25;
+
+/// State of the four player lights.
+///
+/// Bit 0 is the light nearest the rail's top end. A light that is both on
+/// and flashing flashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PlayerLights {
+    on: u8,
+    flashing: u8,
+}
+
+impl PlayerLights {
+    /// Every light off.
+    pub const OFF: PlayerLights = PlayerLights { on: 0, flashing: 0 };
+
+    /// Lights from bit masks of the lights that are on and flashing; only
+    /// the low four bits are used.
+    pub fn new(on: u8, flashing: u8) -> Self {
+        Self {
+            on: on & 0x0F,
+            flashing: flashing & 0x0F,
+        }
+    }
+
+    /// Shows `n` as a light count: 1-4 light that many lights, 5-8 flash
+    /// `n - 4` lights and larger values wrap around. Zero turns them off.
+    pub fn count(n: usize) -> Self {
+        if n == 0 {
+            return Self::OFF;
+        }
+        let lit = (n - 1) % 4 + 1;
+        let mask = (1u8 << lit) - 1;
+        if (n - 1) % 8 < 4 {
+            Self::new(mask, 0)
+        } else {
+            Self::new(0, mask)
+        }
+    }
+
+    /// Lights that are on.
+    pub fn on(self) -> u8 {
+        self.on
+    }
+
+    /// Lights that are flashing.
+    pub fn flashing(self) -> u8 {
+        self.flashing
+    }
+
+    /// The subcommand argument byte: flashing lights in the high nibble,
+    /// lit ones in the low nibble.
+    pub fn to_byte(self) -> u8 {
+        self.flashing << 4 | self.on
+    }
+}
+
+/// One step of a [`HomeLight`] pattern.
+///
+/// All values are 4-bit; larger values are clamped to 15.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HomeLightCycle {
+    /// Brightness, from 0 (off) to 15 (full).
+    pub intensity: u8,
+    /// Time to fade to `intensity`, in multiples of the pattern's step.
+    pub fade: u8,
+    /// Time to hold `intensity`, in multiples of the pattern's step.
+    pub hold: u8,
+}
+
+/// A Home button LED pattern.
+///
+/// The LED starts at `start_intensity` and plays `cycles` in order,
+/// `repeat` times. All values are 4-bit; larger values are clamped to 15.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HomeLight {
+    /// Base duration of a cycle, from 1 (8 ms) to 15 (175 ms); 0 turns the
+    /// LED off.
+    pub step: u8,
+    /// Brightness before the first cycle.
+    pub start_intensity: u8,
+    /// How many times to play the cycles; 0 repeats forever.
+    pub repeat: u8,
+    /// Up to [`MAX_HOME_LIGHT_CYCLES`] steps; extra ones are ignored.
+    pub cycles: Vec<HomeLightCycle>,
+}
+
+impl HomeLight {
+    /// The LED off.
+    pub fn off() -> Self {
+        Self::default()
+    }
+
+    /// The LED held at `intensity`.
+    pub fn steady(intensity: u8) -> Self {
+        let cycle = HomeLightCycle {
+            intensity,
+            fade: 1,
+            hold: 1,
+        };
+        Self {
+            step: 1,
+            start_intensity: intensity,
+            repeat: 0,
+            cycles: vec![cycle, cycle],
+        }
+    }
+
+    /// A single flash of about half a second that fades back to off.
+    pub fn pulse() -> Self {
+        Self {
+            step: 8,
+            start_intensity: 0,
+            repeat: 1,
+            cycles: vec![
+                HomeLightCycle {
+                    intensity: 15,
+                    fade: 1,
+                    hold: 2,
+                },
+                HomeLightCycle {
+                    intensity: 0,
+                    fade: 2,
+                    hold: 1,
+                },
+            ],
+        }
+    }
+
+    /// The 25-byte subcommand argument.
+    ///
+    /// Two header bytes (cycle count and step, start intensity and repeat
+    /// count) are followed by the cycles in pairs: one byte with both
+    /// intensities, then a fade/hold byte for each.
+    pub fn to_bytes(&self) -> [u8; HOME_LIGHT_LEN] {
+        let nibble = |value: u8| value.min(0x0F);
+        let cycles = &self.cycles[..self.cycles.len().min(MAX_HOME_LIGHT_CYCLES)];
+
+        let mut buf = [0u8; HOME_LIGHT_LEN];
+        buf[0] = (cycles.len() as u8) << 4 | nibble(self.step);
+        buf[1] = nibble(self.start_intensity) << 4 | nibble(self.repeat);
+        let mut pos = 2;
+        for pair in cycles.chunks(2) {
+            let second = pair.get(1).copied().unwrap_or_default();
+            buf[pos] = nibble(pair[0].intensity) << 4 | nibble(second.intensity);
+            pos += 1;
+            for cycle in pair {
+                buf[pos] = nibble(cycle.fade) << 4 | nibble(cycle.hold);
+                pos += 1;
+            }
+        }
+        buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_light_counts() {
+        assert_eq!(PlayerLights::count(0).to_byte(), 0x00);
+        assert_eq!(PlayerLights::count(1).to_byte(), 0x01);
+        assert_eq!(PlayerLights::count(3).to_byte(), 0x07);
+        assert_eq!(PlayerLights::count(4).to_byte(), 0x0F);
+        assert_eq!(PlayerLights::count(6).to_byte(), 0x30);
+        assert_eq!(PlayerLights::count(9), PlayerLights::count(1));
+        assert_eq!(PlayerLights::new(0xFF, 0x12).to_byte(), 0x2F);
+    }
+
+    #[test]
+    fn home_light_encoding() {
+        assert_eq!(HomeLight::off().to_bytes(), [0; HOME_LIGHT_LEN]);
+
+        let pulse = HomeLight::pulse().to_bytes();
+        assert_eq!(pulse[..5], [0x28, 0x01, 0xF0, 0x12, 0x21]);
+        assert!(pulse[5..].iter().all(|&b| b == 0));
+
+        // Fifteen cycles fill the buffer exactly; more are dropped.
+        let full = HomeLight {
+            step: 1,
+            start_intensity: 0,
+            repeat: 0,
+            cycles: vec![
+                HomeLightCycle {
+                    intensity: 0x1F,
+                    fade: 1,
+                    hold: 1,
+                };
+                20
+            ],
+        }
+        .to_bytes();
+        assert_eq!(full[0], 0xF1);
+        assert_eq!(full[2], 0xFF);
+        assert_eq!(full[23], 0xF0);
+        assert_eq!(full[24], 0x11);
+    }
+}
@@ -2,16 +2,22 @@
 
 use std::time::Duration;
 
-use hidapi::HidApi;
-
+use super::backend::{DeviceBackend, HidBackend, RumbleSink};
 use super::device::JoyCon;
-use super::types::{DeviceInfo, JoyConError, JOYCON_L_BT, JOYCON_R_BT, PRO_CONTROLLER, VENDOR_ID};
+use super::types::{
+    DeviceInfo, JoyConError, JOYCON_CHARGING_GRIP, JOYCON_L_BT, JOYCON_R_BT, PRO_CONTROLLER,
+    VENDOR_ID,
+};
 
 const MAX_RETRIES: u32 = 5;
 const RETRY_DELAY: Duration = Duration::from_secs(5);
 
 /// Manages discovery and connection to JoyCon devices.
 ///
+/// The manager is generic over a [`DeviceBackend`]. [`JoyConManager::new`]
+/// uses the real HID backend; [`JoyConManager::with_backend`] accepts any
+/// other backend, such as a [`SimulatedBackend`](super::SimulatedBackend).
+///
 /// # Example
 ///
 /// ```no_run
@@ -21,8 +27,8 @@ const RETRY_DELAY: Duration = Duration::from_secs(5);
 /// let joycons = manager.connect_and_initialize_joycons().expect("No JoyCons found");
 /// println!("Found {} JoyCon(s)", joycons.len());
 /// ```
-pub struct JoyConManager {
-    api: HidApi,
+pub struct JoyConManager<B: DeviceBackend = HidBackend> {
+    backend: B,
 }
 
 impl JoyConManager {
@@ -32,46 +38,57 @@ impl JoyConManager {
     ///
     /// Returns an error if the HID API cannot be initialized.
     pub fn new() -> Result<Self, JoyConError> {
-        let api = HidApi::new().map_err(|e| JoyConError::HidError(e.to_string()))?;
-        Ok(Self { api })
+        Ok(Self::with_backend(HidBackend::new()?))
+    }
+}
+
+impl<B: DeviceBackend> JoyConManager<B> {
+    /// Creates a manager that discovers devices through `backend`.
+    pub fn with_backend(backend: B) -> Self {
+        Self { backend }
+    }
+
+    /// Lists the supported controllers the backend currently sees.
+    fn supported_devices(&self) -> Result<Vec<DeviceInfo>, JoyConError> {
+        Ok(self
+            .backend
+            .devices()?
+            .into_iter()
+            .filter(|info| {
+                info.vendor_id == VENDOR_ID as i32
+                    && matches!(
+                        info.product_id,
+                        JOYCON_L_BT | JOYCON_R_BT | PRO_CONTROLLER | JOYCON_CHARGING_GRIP
+                    )
+            })
+            .collect())
     }
 
     /// Scans for connected JoyCon devices.
     ///
-    /// Returns a list of discovered and opened JoyCon devices.
-    pub fn scan_for_devices(&self) -> Result<Vec<JoyCon>, JoyConError> {
+    /// Returns a list of discovered and opened JoyCon devices. Controllers
+    /// connected by USB — in a charging grip, where each Joy-Con is its own
+    /// device, or by cable — get their [USB
+    /// handshake](JoyCon::usb_handshake); empty grip slots are skipped.
+    pub fn scan_for_devices(&self) -> Result<Vec<JoyCon<B::Sink>>, JoyConError> {
         let mut joycons = Vec::new();
 
-        for device_info in self.api.device_list() {
-            if device_info.vendor_id() == VENDOR_ID {
-                match device_info.product_id() {
-                    id @ (JOYCON_L_BT | JOYCON_R_BT | PRO_CONTROLLER) => {
-                        println!(
-                            "Found device: VID={:04x} PID={:04x}",
-                            device_info.vendor_id(),
-                            device_info.product_id()
-                        );
-
-                        if let Ok(device) = self.api.open_path(device_info.path()) {
-                            let info = DeviceInfo {
-                                product_id: id,
-                                interface_number: device_info.interface_number(),
-                                serial: device_info
-                                    .serial_number()
-                                    .unwrap_or("Unknown")
-                                    .to_string(),
-                                path: device_info.path().to_string_lossy().into_owned(),
-                                vendor_id: device_info.vendor_id() as i32,
-                                usage_page: device_info.usage_page() as i32,
-                            };
-
-                            if let Ok(mut joycon) = JoyCon::new(&info) {
-                                joycon.set_handle(device);
-                                joycons.push(joycon);
-                            }
+        for info in self.supported_devices()? {
+            log::debug!(
+                "Found device: VID={:04x} PID={:04x}",
+                info.vendor_id,
+                info.product_id
+            );
+
+            if let Ok(device) = self.backend.open(&info) {
+                if let Ok(mut joycon) = JoyCon::with_sink(&info, device) {
+                    if joycon.is_usb() {
+                        if let Err(e) = joycon.usb_handshake() {
+                            log::debug!("Skipping USB device {}: {}", info.path, e);
+                            continue;
                         }
                     }
-                    _ => continue,
+                    joycons.push(joycon);
                 }
             }
         }
@@ -83,28 +100,48 @@ impl JoyConManager {
     ///
     /// This method will retry scanning for devices up to 5 times with 5 second
     /// delays between attempts. Once devices are found, they are initialized
-    /// and ready for use.
+    /// and ready for use; a controller that fails to initialize is left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns `JoyConError::NotConnected` if no devices are found after all retries,
+    /// or the initialization error if no controller could be initialized.
+    pub fn connect_and_initialize_joycons(&self) -> Result<Vec<JoyCon<B::Sink>>, JoyConError> {
+        self.connect_and_initialize_at_most(None)
+    }
+
+    /// Like [`connect_and_initialize_joycons`](Self::connect_and_initialize_joycons),
+    /// but keeps only the first `max` controllers found (at least one).
+    ///
+    /// The others are closed before initialization, so they are not
+    /// configured and do not play the test scale.
     ///
     /// # Errors
     ///
     /// Returns `JoyConError::NotConnected` if no devices are found after all retries.
-    pub fn connect_and_initialize_joycons(&self) -> Result<Vec<JoyCon>, JoyConError> {
+    pub fn connect_and_initialize_at_most(
+        &self,
+        max: Option<usize>,
+    ) -> Result<Vec<JoyCon<B::Sink>>, JoyConError> {
         let mut tries = 0;
 
-        println!("Scanning for JoyCons...");
+        log::info!("Scanning for JoyCons...");
 
         while tries < MAX_RETRIES {
             match self.scan_for_devices() {
                 Ok(mut joycons) => {
                     if !joycons.is_empty() {
+                        if let Some(max) = max {
+                            joycons.truncate(max.max(1));
+                        }
                         self.initialize_joycons(&mut joycons)?;
                         return Ok(joycons);
                     }
-                    self.print_retry_message(tries);
+                    self.log_retry_message(tries);
                 }
                 Err(e) => {
-                    eprintln!("Error scanning for devices: {}", e);
-                    self.print_retry_message(tries);
+                    log::warn!("Error scanning for devices: {}", e);
+                    self.log_retry_message(tries);
                 }
             }
 
@@ -115,25 +152,142 @@ impl JoyConManager {
         Err(JoyConError::NotConnected)
     }
 
-    fn initialize_joycons(&self, joycons: &mut [JoyCon]) -> Result<(), JoyConError> {
-        println!("Found {} JoyCon(s)!", joycons.len());
+    /// Re-opens a JoyCon whose connection dropped and re-enables rumble,
+    /// input reports and, if it was on, the IMU.
+    ///
+    /// The device is looked up by its previous path, then by the serial
+    /// the backend reports, and only then by the factory serial read from
+    /// each candidate's SPI flash ([`JoyCon::identity`]), so a controller
+    /// that re-paired under a new path is still found. Devices at the
+    /// `claimed` paths are open elsewhere, e.g. played by another thread,
+    /// and are never opened or probed. The test scale is not played again.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JoyConError::NotConnected`] if the controller is not
+    /// visible yet, or the error of opening or enabling it.
+    pub fn reconnect(
+        &self,
+        joycon: &mut JoyCon<B::Sink>,
+        claimed: &[String],
+    ) -> Result<(), JoyConError> {
+        let previous = joycon.device_info().clone();
+        let flash_serial = joycon
+            .identity()
+            .and_then(|identity| identity.serial.clone());
+        let candidates: Vec<DeviceInfo> = self
+            .supported_devices()?
+            .into_iter()
+            .filter(|info| info.product_id == previous.product_id && !claimed.contains(&info.path))
+            .collect();
+        let known_serial = !previous.serial.is_empty() && previous.serial != "Unknown";
+        let info = candidates
+            .iter()
+            .find(|info| info.path == previous.path)
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .find(|info| known_serial && info.serial == previous.serial)
+            })
+            .cloned()
+            .or_else(|| {
+                let serial = flash_serial?;
+                candidates
+                    .into_iter()
+                    .find(|info| self.flash_serial(info).as_deref() == Some(serial.as_str()))
+            })
+            .ok_or(JoyConError::NotConnected)?;
+
+        let device = self.backend.open(&info)?;
+        joycon.reattach(&info, device);
+        if joycon.is_usb() {
+            joycon.usb_handshake()?;
+        }
+        joycon.enable_rumble()?;
+        joycon.enable_input_reports()?;
+        if joycon.is_imu_enabled() {
+            joycon.enable_imu()?;
+        }
+        Ok(())
+    }
+
+    /// Opens `info` just long enough to read the factory serial from its
+    /// SPI flash.
+    fn flash_serial(&self, info: &DeviceInfo) -> Option<String> {
+        let mut joycon = JoyCon::with_sink(info, self.backend.open(info).ok()?).ok()?;
+        if joycon.is_usb() {
+            joycon.usb_handshake().ok()?;
+        }
+        joycon.read_identity().ok()?.serial
+    }
+
+    /// Starts watching for controllers being connected and removed.
+    ///
+    /// The controllers visible now are the starting point; each
+    /// [`DeviceWatcher::poll`] reports what changed since the previous one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend cannot list devices.
+    pub fn watch(&self) -> Result<DeviceWatcher<'_, B>, JoyConError> {
+        Ok(DeviceWatcher {
+            manager: self,
+            known: self.supported_devices()?,
+        })
+    }
+
+    /// Initializes every controller, leaving out those that fail. Fails
+    /// with the last error if none is left.
+    fn initialize_joycons(&self, joycons: &mut Vec<JoyCon<B::Sink>>) -> Result<(), JoyConError> {
+        log::info!("Found {} JoyCon(s)!", joycons.len());
 
-        for (i, joycon) in joycons.iter_mut().enumerate() {
-            println!("Initializing JoyCon {}", i + 1);
+        let mut last_error = None;
+        let mut i = 0;
+        joycons.retain_mut(|joycon| {
+            i += 1;
+            log::debug!("Initializing JoyCon {}", i);
             match joycon.initialize_device() {
-                Ok(()) => println!("JoyCon {} initialized successfully", i + 1),
-                Err(e) => eprintln!("Failed to initialize JoyCon {}: {}", i + 1, e),
+                Ok(()) => log::info!("JoyCon {} initialized successfully", i),
+                Err(e) => {
+                    log::error!("Failed to initialize JoyCon {}, leaving it out: {}", i, e);
+                    last_error = Some(e);
+                    return false;
+                }
             }
-        }
+            match joycon.read_identity() {
+                Ok(identity) => log::info!(
+                    "🎨 JoyCon {} is a {} {:?} (serial {})",
+                    i,
+                    identity.colors.body,
+                    joycon.get_type(),
+                    joycon.serial()
+                ),
+                Err(e) => log::debug!("Could not read JoyCon {} flash: {}", i, e),
+            }
+            // The input mode reply carries the battery level.
+            if let Some(state) = joycon.input_state() {
+                log::info!(
+                    "🔋 JoyCon {} battery: {}{}",
+                    i,
+                    state.battery,
+                    if state.charging { " (charging)" } else { "" }
+                );
+            }
+            true
+        });
 
-        Ok(())
+        match last_error {
+            Some(e) if joycons.is_empty() => Err(e),
+            _ => Ok(()),
+        }
     }
 
-    fn print_retry_message(&self, tries: u32) {
-        eprintln!("No JoyCons found. Are they connected to your PC?");
-        eprintln!("  - Check your Bluetooth devices connected");
-        eprintln!("  - Make sure the JoyCon is charged");
-        println!(
+    fn log_retry_message(&self, tries: u32) {
+        log::warn!("No JoyCons found. Are they connected to your PC?");
+        log::warn!("  - Check your Bluetooth devices connected");
+        log::warn!("  - Or attach them to a charging grip or USB cable");
+        log::warn!("  - Make sure the JoyCon is charged");
+        log::info!(
             "Retrying in {} seconds... (Attempt {}/{})",
             RETRY_DELAY.as_secs(),
             tries + 1,
@@ -141,3 +295,71 @@ impl JoyConManager {
         );
     }
 }
+
+/// Re-opens a JoyCon whose connection dropped.
+///
+/// Implemented by [`JoyConManager`]; playback threads use it when
+/// [`PlaybackOptions::reconnect`](crate::midi::PlaybackOptions::reconnect)
+/// is set.
+pub(crate) trait Reconnect<S: RumbleSink>: Sync {
+    /// Tries once to re-open `joycon` and make it playable again, leaving
+    /// the devices at the `claimed` paths alone.
+    fn reconnect(&self, joycon: &mut JoyCon<S>, claimed: &[String]) -> Result<(), JoyConError>;
+}
+
+impl<B: DeviceBackend> Reconnect<B::Sink> for JoyConManager<B> {
+    fn reconnect(
+        &self,
+        joycon: &mut JoyCon<B::Sink>,
+        claimed: &[String],
+    ) -> Result<(), JoyConError> {
+        JoyConManager::reconnect(self, joycon, claimed)
+    }
+}
+
+/// A controller connected or removed, from [`DeviceWatcher::poll`].
+#[derive(Debug, Clone)]
+pub enum DeviceChange {
+    /// A controller appeared.
+    Connected(DeviceInfo),
+    /// A controller disappeared.
+    Disconnected(DeviceInfo),
+}
+
+/// Watches a [`JoyConManager`]'s backend for controllers coming and going.
+///
+/// Devices are told apart by their path. Created by [`JoyConManager::watch`].
+pub struct DeviceWatcher<'a, B: DeviceBackend = HidBackend> {
+    manager: &'a JoyConManager<B>,
+    known: Vec<DeviceInfo>,
+}
+
+impl<B: DeviceBackend> DeviceWatcher<'_, B> {
+    /// Lists the controllers connected and removed since the last poll.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend cannot list devices.
+    pub fn poll(&mut self) -> Result<Vec<DeviceChange>, JoyConError> {
+        let current = self.manager.supported_devices()?;
+        let is_in =
+            |list: &[DeviceInfo], info: &DeviceInfo| list.iter().any(|i| i.path == info.path);
+
+        let mut changes: Vec<DeviceChange> = self
+            .known
+            .iter()
+            .filter(|info| !is_in(&current, info))
+            .cloned()
+            .map(DeviceChange::Disconnected)
+            .collect();
+        changes.extend(
+            current
+                .iter()
+                .filter(|info| !is_in(&self.known, info))
+                .cloned()
+                .map(DeviceChange::Connected),
+        );
+        self.known = current;
+        Ok(changes)
+    }
+}
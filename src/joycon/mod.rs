@@ -46,6 +46,58 @@
 //! # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
 //! ```
 //!
+//! # Reading Input
+//!
+//! [`JoyCon::enable_input_reports`] switches a controller to standard full
+//! input mode; [`JoyCon::poll_input`] and [`JoyCon::input_states`] then
+//! decode its reports into [`InputState`]s (buttons, sticks, battery):
+//!
+//! ```no_run
+//! use musical_joycons::joycon::{Button, JoyConManager};
+//! use std::time::Duration;
+//!
+//! let manager = JoyConManager::new()?;
+//! let mut joycons = manager.connect_and_initialize_joycons()?;
+//!
+//! for state in joycons[0].input_states(Duration::from_secs(5)) {
+//!     if state?.buttons.contains(Button::A) {
+//!         println!("A pressed");
+//!         break;
+//!     }
+//! }
+//! # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+//! ```
+//!
+//! # Motion Sensors
+//!
+//! [`JoyCon::enable_imu`] turns on the accelerometer and gyroscope; input
+//! reports then carry [`ImuSample`]s, calibrated from SPI flash, which
+//! [`JoyCon::imu_samples`] streams and [`JoyCon::take_imu_samples`]
+//! collects.
+//!
+//! # Running Without Hardware
+//!
+//! [`JoyConManager`] and [`JoyCon`] are generic over a [`DeviceBackend`] /
+//! [`RumbleSink`]. Swap in a [`SimulatedBackend`] to run discovery and
+//! playback against in-memory devices that record every report:
+//!
+//! ```
+//! use musical_joycons::joycon::{JoyConManager, JoyConType, SimulatedBackend, SimulatedDevice};
+//!
+//! let device = SimulatedDevice::new();
+//! let manager = JoyConManager::with_backend(
+//!     SimulatedBackend::new().with_device(JoyConType::Right, device.clone()),
+//! );
+//! let joycons = manager.scan_for_devices()?;
+//! assert_eq!(joycons.len(), 1);
+//! # Ok::<(), musical_joycons::joycon::JoyConError>(())
+//! ```
+//!
+//! # HD Rumble
+//!
+//! [`JoyCon::rumble_bands`] plays a note in each of the two HD rumble
+//! bands; the [`hd_rumble`] module encodes and decodes the bytes behind it.
+//!
 //! # Supported Devices
 //!
 //! This module supports:
@@ -63,12 +115,31 @@
 //! - [`JoyConError::HidError`]: Low-level HID communication failure
 //! - [`JoyConError::InvalidRumble`]: Invalid frequency/amplitude parameters
 
+mod backend;
 mod device;
+pub mod hd_rumble;
+mod imu;
+mod input;
 mod interface;
+mod lights;
 mod manager;
+mod simulated;
+mod spi;
 mod types;
 
 // Re-export public types
-pub use self::device::JoyCon;
-pub use self::manager::JoyConManager;
-pub use self::types::{DeviceInfo, JoyConError, JoyConType};
+pub use self::backend::{DeviceBackend, HidBackend, RumbleSink};
+pub use self::device::{ImuSamples, InputStates, JoyCon};
+pub(crate) use self::imu::magnitude;
+pub use self::imu::{
+    ImuCalibration, ImuSample, RawImuSample, IMU_SAMPLES_PER_REPORT, IMU_SAMPLE_INTERVAL,
+};
+pub use self::input::{
+    BatteryLevel, Button, Buttons, InputState, ParseButtonError, StickPosition, SubcommandReply,
+};
+pub use self::lights::{HomeLight, HomeLightCycle, PlayerLights, MAX_HOME_LIGHT_CYCLES};
+pub(crate) use self::manager::Reconnect;
+pub use self::manager::{DeviceChange, DeviceWatcher, JoyConManager};
+pub use self::simulated::{RecordedReport, SimulatedBackend, SimulatedDevice};
+pub use self::spi::{Color, ControllerColors, ControllerIdentity, StickCalibration, MAX_SPI_READ};
+pub use self::types::{DeviceInfo, JoyConError, JoyConType, Motor};
@@ -0,0 +1,594 @@
+//! In-memory JoyCon simulation for hardware-free playback.
+//!
+//! A [`SimulatedDevice`] accepts output reports exactly like a real
+//! controller and records each one with the time it was written. Input
+//! reports queued with [`SimulatedDevice::push_input`] are handed out to
+//! readers in order, and every subcommand is acknowledged with a `0x21`
+//! reply whose header repeats the last queued input state, and USB `0x80`
+//! commands get their `0x81` answers. SPI flash reads
+//! are answered from [`SimulatedDevice::write_flash`] data. A
+//! [`SimulatedBackend`] exposes a fixed set of simulated devices to
+//! [`JoyConManager`](super::JoyConManager), so the full discovery →
+//! initialization → playback pipeline runs without Bluetooth.
+//! [`SimulatedDevice::disconnect`] takes a device away until
+//! [`SimulatedDevice::reconnect`], to exercise hot-plugging.
+//!
+//! # Example
+//!
+//! ```
+//! use musical_joycons::joycon::{JoyConManager, JoyConType, SimulatedBackend, SimulatedDevice};
+//!
+//! let left = SimulatedDevice::new();
+//! let backend = SimulatedBackend::new().with_device(JoyConType::Left, left.clone());
+//! let manager = JoyConManager::with_backend(backend);
+//!
+//! let mut joycons = manager.scan_for_devices()?;
+//! joycons[0].rumble(440.0, 0.5)?;
+//!
+//! assert_eq!(left.reports().len(), 1);
+//! # Ok::<(), musical_joycons::joycon::JoyConError>(())
+//! ```
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::backend::{DeviceBackend, RumbleSink};
+use super::input::{InputState, SubcommandReply};
+use super::types::{
+    DeviceInfo, JoyConError, JoyConType, Subcommand, JOYCON_CHARGING_GRIP, JOYCON_L_BT,
+    JOYCON_R_BT, PRO_CONTROLLER, USB_COMMAND, USB_REPLY, VENDOR_ID,
+};
+
+/// A single output report captured by a [`SimulatedDevice`].
+#[derive(Debug, Clone)]
+pub struct RecordedReport {
+    /// When the report was written.
+    pub timestamp: Instant,
+    /// The raw report bytes (49 bytes for every report the library sends).
+    pub data: Vec<u8>,
+}
+
+impl RecordedReport {
+    /// Returns `true` if this is a rumble-only report (command `0x10`).
+    pub fn is_rumble(&self) -> bool {
+        self.data.first() == Some(&0x10)
+    }
+}
+
+/// Subcommand ID of an SPI flash read.
+const SPI_FLASH_READ: u8 = Subcommand::SpiFlashRead as u8;
+
+/// Per-subcommand `(ack, data)` replies; `None` never replies.
+type ReplyTable = HashMap<u8, Option<(u8, Vec<u8>)>>;
+
+/// A fake JoyCon that records every report written to it.
+///
+/// Cloning a `SimulatedDevice` yields another handle to the same report log
+/// and input queue, so a test can keep one clone while the other is moved
+/// into a playback thread.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedDevice {
+    reports: Arc<Mutex<Vec<RecordedReport>>>,
+    input: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    /// Header state of subcommand replies.
+    state: Arc<Mutex<InputState>>,
+    replies: Arc<Mutex<ReplyTable>>,
+    /// SPI flash contents; unwritten bytes read as erased (`0xFF`).
+    flash: Arc<Mutex<BTreeMap<u32, u8>>>,
+    disconnected: Arc<AtomicBool>,
+}
+
+impl SimulatedDevice {
+    /// Creates a simulated device with an empty report log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a snapshot of every report written so far, oldest first.
+    pub fn reports(&self) -> Vec<RecordedReport> {
+        self.reports
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Discards all recorded reports.
+    pub fn clear(&self) {
+        self.reports
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// Queues an input report for the next read, e.g. from
+    /// [`InputState::to_report`](super::InputState::to_report).
+    pub fn push_input(&self, report: impl Into<Vec<u8>>) {
+        let report = report.into();
+        if let Some(state) = InputState::parse(&report) {
+            *self.state.lock().unwrap_or_else(|e| e.into_inner()) = state;
+        }
+        self.input
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push_back(report);
+    }
+
+    /// Answers `subcommand` with the given acknowledgement byte and data
+    /// from now on, instead of a plain `0x80` acknowledgement.
+    pub fn reply_with(&self, subcommand: u8, ack: u8, data: impl Into<Vec<u8>>) {
+        self.replies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(subcommand, Some((ack, data.into())));
+    }
+
+    /// Stores `bytes` in the simulated SPI flash at `address`.
+    pub fn write_flash(&self, address: u32, bytes: &[u8]) {
+        let mut flash = self.flash.lock().unwrap_or_else(|e| e.into_inner());
+        for (offset, &byte) in bytes.iter().enumerate() {
+            flash.insert(address + offset as u32, byte);
+        }
+    }
+
+    /// The reply to an SPI flash read: the echoed address and length, then
+    /// the data.
+    fn read_flash(&self, request: &[u8]) -> (u8, Vec<u8>) {
+        let address = u32::from_le_bytes([request[0], request[1], request[2], request[3]]);
+        let flash = self.flash.lock().unwrap_or_else(|e| e.into_inner());
+        let mut data = request[..5].to_vec();
+        data.extend(
+            (0..u32::from(request[4])).map(|i| flash.get(&(address + i)).copied().unwrap_or(0xFF)),
+        );
+        (0x90, data)
+    }
+
+    /// Drops the connection: reads and writes fail and the backend stops
+    /// listing the device until [`reconnect`](Self::reconnect).
+    pub fn disconnect(&self) {
+        self.disconnected.store(true, Ordering::Relaxed);
+    }
+
+    /// Restores a connection dropped by [`disconnect`](Self::disconnect).
+    pub fn reconnect(&self) {
+        self.disconnected.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns `true` while the device is disconnected.
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected.load(Ordering::Relaxed)
+    }
+
+    /// Fails while the device is disconnected.
+    fn check_connected(&self) -> Result<(), JoyConError> {
+        if self.is_disconnected() {
+            return Err(JoyConError::HidError(
+                "simulated device disconnected".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Never answers `subcommand`, like a controller that dropped it.
+    pub fn ignore_subcommand(&self, subcommand: u8) {
+        self.replies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(subcommand, None);
+    }
+
+    /// Queues the reply to a subcommand or USB command report, if it gets
+    /// one.
+    fn reply_to(&self, report: &[u8]) {
+        if let [USB_COMMAND, command @ 0x01..=0x03, ..] = *report {
+            self.input
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push_back(vec![USB_REPLY, command]);
+            return;
+        }
+        let Some(&subcommand) = report.get(10).filter(|_| report[0] == 0x01) else {
+            return;
+        };
+        let reply = self
+            .replies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&subcommand)
+            .cloned()
+            .unwrap_or_else(|| match subcommand {
+                SPI_FLASH_READ if report.len() >= 16 => Some(self.read_flash(&report[11..16])),
+                _ => Some((0x80, Vec::new())),
+            });
+        if let Some((ack, data)) = reply {
+            let reply = SubcommandReply {
+                state: *self.state.lock().unwrap_or_else(|e| e.into_inner()),
+                ack,
+                subcommand,
+                data,
+            };
+            self.input
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push_back(reply.to_report());
+        }
+    }
+}
+
+impl RumbleSink for SimulatedDevice {
+    fn write_report(&mut self, report: &[u8]) -> Result<usize, JoyConError> {
+        self.check_connected()?;
+        self.reports
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(RecordedReport {
+                timestamp: Instant::now(),
+                data: report.to_vec(),
+            });
+        self.reply_to(report);
+        Ok(report.len())
+    }
+
+    /// Pops the oldest queued input report. Returns `0` right away instead
+    /// of waiting when the queue is empty.
+    fn read_report(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize, JoyConError> {
+        self.check_connected()?;
+        let Some(report) = self
+            .input
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+        else {
+            return Ok(0);
+        };
+        let len = report.len().min(buf.len());
+        buf[..len].copy_from_slice(&report[..len]);
+        Ok(len)
+    }
+}
+
+/// A [`DeviceBackend`] serving a fixed list of [`SimulatedDevice`]s.
+///
+/// Disconnected devices are left out of [`devices`](DeviceBackend::devices)
+/// and cannot be opened.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedBackend {
+    devices: Vec<(DeviceInfo, SimulatedDevice)>,
+}
+
+impl SimulatedBackend {
+    /// Creates a backend with no devices attached.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attaches a simulated controller of the given type, paired over
+    /// Bluetooth.
+    ///
+    /// Devices are reported in the order they were added.
+    pub fn with_device(self, device_type: JoyConType, device: SimulatedDevice) -> Self {
+        let product_id = match device_type {
+            JoyConType::Left => JOYCON_L_BT,
+            JoyConType::Right => JOYCON_R_BT,
+            JoyConType::ProController => PRO_CONTROLLER,
+            JoyConType::Other(id) => id,
+        };
+        self.attach(product_id, -1, false, device)
+    }
+
+    /// Attaches a simulated controller connected by USB: a Joy-Con in a
+    /// charging grip, or a Pro Controller on a cable.
+    pub fn with_usb_device(self, device_type: JoyConType, device: SimulatedDevice) -> Self {
+        let (product_id, interface_number) = match device_type {
+            JoyConType::Left => (JOYCON_CHARGING_GRIP, 1),
+            JoyConType::Right => (JOYCON_CHARGING_GRIP, 0),
+            JoyConType::ProController => (PRO_CONTROLLER, 0),
+            JoyConType::Other(id) => (id, 0),
+        };
+        self.attach(product_id, interface_number, true, device)
+    }
+
+    fn attach(
+        mut self,
+        product_id: u16,
+        interface_number: i32,
+        usb: bool,
+        device: SimulatedDevice,
+    ) -> Self {
+        let index = self.devices.len();
+        let info = DeviceInfo {
+            product_id,
+            interface_number,
+            usb,
+            serial: format!("SIM-{index:04}"),
+            path: format!("sim://{index}"),
+            vendor_id: VENDOR_ID as i32,
+            usage_page: 1,
+        };
+        self.devices.push((info, device));
+        self
+    }
+}
+
+impl DeviceBackend for SimulatedBackend {
+    type Sink = SimulatedDevice;
+
+    fn devices(&self) -> Result<Vec<DeviceInfo>, JoyConError> {
+        Ok(self
+            .devices
+            .iter()
+            .filter(|(_, device)| !device.is_disconnected())
+            .map(|(info, _)| info.clone())
+            .collect())
+    }
+
+    fn open(&self, info: &DeviceInfo) -> Result<Self::Sink, JoyConError> {
+        self.devices
+            .iter()
+            .find(|(candidate, device)| candidate.path == info.path && !device.is_disconnected())
+            .map(|(_, device)| device.clone())
+            .ok_or(JoyConError::NotConnected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joycon::{DeviceChange, JoyConManager};
+
+    #[test]
+    fn records_reports_in_order() {
+        let mut device = SimulatedDevice::new();
+        let observer = device.clone();
+
+        device.write_report(&[0x10, 0x00]).unwrap();
+        device.write_report(&[0x01, 0x01]).unwrap();
+
+        let reports = observer.reports();
+        assert_eq!(reports.len(), 2);
+        assert!(reports[0].is_rumble());
+        assert!(!reports[1].is_rumble());
+        assert!(reports[0].timestamp <= reports[1].timestamp);
+
+        observer.clear();
+        assert!(device.reports().is_empty());
+    }
+
+    #[test]
+    fn backend_lists_and_opens_devices() {
+        let left = SimulatedDevice::new();
+        let backend = SimulatedBackend::new()
+            .with_device(JoyConType::Left, left.clone())
+            .with_device(JoyConType::Right, SimulatedDevice::new());
+
+        let infos = backend.devices().unwrap();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].product_id, JOYCON_L_BT);
+        assert_eq!(infos[1].product_id, JOYCON_R_BT);
+
+        let mut opened = backend.open(&infos[0]).unwrap();
+        opened.write_report(&[0x10]).unwrap();
+        assert_eq!(left.reports().len(), 1);
+    }
+
+    #[test]
+    fn subcommands_are_acknowledged() {
+        let mut device = SimulatedDevice::new();
+        let mut buf = [0u8; 49];
+        let mut subcommand = [0u8; 49];
+        subcommand[0] = 0x01;
+        subcommand[10] = 0x30;
+
+        device.write_report(&subcommand).unwrap();
+        let len = device.read_report(&mut buf, Duration::ZERO).unwrap();
+        let reply = SubcommandReply::parse(&buf[..len]).unwrap();
+        assert_eq!((reply.ack, reply.subcommand), (0x80, 0x30));
+
+        device.reply_with(0x30, 0x00, [0x12]);
+        device.write_report(&subcommand).unwrap();
+        let len = device.read_report(&mut buf, Duration::ZERO).unwrap();
+        let reply = SubcommandReply::parse(&buf[..len]).unwrap();
+        assert!(!reply.is_ack());
+        assert_eq!(reply.data[0], 0x12);
+
+        device.ignore_subcommand(0x30);
+        device.write_report(&subcommand).unwrap();
+        device.write_report(&[0x10, 0x00]).unwrap();
+        assert_eq!(device.read_report(&mut buf, Duration::ZERO).unwrap(), 0);
+    }
+
+    #[test]
+    fn input_reports_are_read_in_order() {
+        let mut device = SimulatedDevice::new();
+        device.clone().push_input([0x30, 0x01]);
+        device.push_input(vec![0x21]);
+
+        let mut buf = [0u8; 49];
+        assert_eq!(device.read_report(&mut buf, Duration::ZERO).unwrap(), 2);
+        assert_eq!(buf[..2], [0x30, 0x01]);
+        assert_eq!(device.read_report(&mut buf, Duration::ZERO).unwrap(), 1);
+        assert_eq!(device.read_report(&mut buf, Duration::ZERO).unwrap(), 0);
+    }
+
+    #[test]
+    fn watcher_sees_devices_come_and_go() {
+        let right = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new()
+                .with_device(JoyConType::Left, SimulatedDevice::new())
+                .with_device(JoyConType::Right, right.clone()),
+        );
+        let mut watcher = manager.watch().unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+
+        right.disconnect();
+        let changes = watcher.poll().unwrap();
+        assert!(
+            matches!(&changes[..], [DeviceChange::Disconnected(info)] if info.product_id == JOYCON_R_BT)
+        );
+        assert!(manager.scan_for_devices().unwrap().len() == 1);
+
+        right.reconnect();
+        let changes = watcher.poll().unwrap();
+        assert!(matches!(&changes[..], [DeviceChange::Connected(info)] if info.path == "sim://1"));
+        assert!(watcher.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn reconnect_finds_a_re_paired_controller_by_flash_serial() {
+        // The same controller before and after re-pairing under a new
+        // path, with another Left Joy-Con listed in between.
+        let before = SimulatedDevice::new();
+        let other = SimulatedDevice::new();
+        let after = SimulatedDevice::new();
+        before.write_flash(0x6000, b"XCW10012345678\0\0");
+        other.write_flash(0x6000, b"XCW10087654321\0\0");
+        after.write_flash(0x6000, b"XCW10012345678\0\0");
+        after.disconnect();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new()
+                .with_device(JoyConType::Left, before.clone())
+                .with_device(JoyConType::Left, other)
+                .with_device(JoyConType::Left, after.clone()),
+        );
+        let mut joycon = manager.scan_for_devices().unwrap().remove(0);
+        joycon.read_identity().unwrap();
+
+        before.disconnect();
+        assert!(manager.reconnect(&mut joycon, &[]).is_err());
+
+        after.reconnect();
+        manager.reconnect(&mut joycon, &[]).unwrap();
+        assert_eq!(joycon.device_info().path, "sim://2");
+    }
+
+    #[test]
+    fn reconnect_leaves_claimed_controllers_alone() {
+        let before = SimulatedDevice::new();
+        let playing = SimulatedDevice::new();
+        let after = SimulatedDevice::new();
+        before.write_flash(0x6000, b"XCW10012345678\0\0");
+        playing.write_flash(0x6000, b"XCW10012345678\0\0");
+        after.write_flash(0x6000, b"XCW10012345678\0\0");
+        after.disconnect();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new()
+                .with_device(JoyConType::Left, before.clone())
+                .with_device(JoyConType::Left, playing.clone())
+                .with_device(JoyConType::Left, after.clone()),
+        );
+        let mut joycon = manager.scan_for_devices().unwrap().remove(0);
+        joycon.read_identity().unwrap();
+
+        // The other Left Joy-Con is being played, so it is not probed even
+        // though its flash matches.
+        before.disconnect();
+        let claimed = ["sim://1".to_string()];
+        assert!(manager.reconnect(&mut joycon, &claimed).is_err());
+        assert!(playing.reports().is_empty());
+
+        after.reconnect();
+        manager.reconnect(&mut joycon, &claimed).unwrap();
+        assert_eq!(joycon.device_info().path, "sim://2");
+        assert!(playing.reports().is_empty());
+    }
+
+    #[test]
+    fn reconnect_prefers_the_backend_serial() {
+        let before = SimulatedDevice::new();
+        let other = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new()
+                .with_device(JoyConType::Left, before.clone())
+                .with_device(JoyConType::Left, other.clone()),
+        );
+        let mut joycon = manager.scan_for_devices().unwrap().remove(0);
+        // The path changed, but the backend still reports the serial, so
+        // no other controller is probed.
+        let mut info = joycon.device_info().clone();
+        info.path = "sim://gone".into();
+        joycon.reattach(&info, before.clone());
+        manager.reconnect(&mut joycon, &[]).unwrap();
+        assert_eq!(joycon.device_info().path, "sim://0");
+        assert!(other.reports().is_empty());
+    }
+
+    #[test]
+    fn only_kept_controllers_are_initialized() {
+        let left = SimulatedDevice::new();
+        let right = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new()
+                .with_device(JoyConType::Left, left.clone())
+                .with_device(JoyConType::Right, right.clone()),
+        );
+
+        let joycons = manager.connect_and_initialize_at_most(Some(1)).unwrap();
+        assert_eq!(joycons.len(), 1);
+        assert!(!left.reports().is_empty());
+        assert!(right.reports().is_empty());
+    }
+
+    #[test]
+    fn controllers_that_fail_to_initialize_are_left_out() {
+        let left = SimulatedDevice::new();
+        let right = SimulatedDevice::new();
+        left.ignore_subcommand(0x48);
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new()
+                .with_device(JoyConType::Left, left.clone())
+                .with_device(JoyConType::Right, right.clone()),
+        );
+
+        let joycons = manager.connect_and_initialize_joycons().unwrap();
+        let types: Vec<JoyConType> = joycons.iter().map(|j| j.get_type()).collect();
+        assert_eq!(types, [JoyConType::Right]);
+
+        right.ignore_subcommand(0x48);
+        assert!(matches!(
+            manager.connect_and_initialize_joycons(),
+            Err(JoyConError::NoReply(0x48))
+        ));
+    }
+
+    #[test]
+    fn charging_grip_joycons_get_the_usb_handshake() {
+        let left = SimulatedDevice::new();
+        let right = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new()
+                .with_usb_device(JoyConType::Left, left.clone())
+                .with_usb_device(JoyConType::Right, right.clone()),
+        );
+
+        let mut joycons = manager.scan_for_devices().unwrap();
+        let types: Vec<JoyConType> = joycons.iter().map(|j| j.get_type()).collect();
+        assert_eq!(types, [JoyConType::Left, JoyConType::Right]);
+        assert!(joycons.iter().all(|j| j.is_usb()));
+
+        joycons[0].rumble(440.0, 0.5).unwrap();
+        let commands: Vec<Vec<u8>> = left.reports().into_iter().map(|r| r.data).collect();
+        assert_eq!(
+            commands[..5],
+            [
+                [0x80, 0x01],
+                [0x80, 0x02],
+                [0x80, 0x03],
+                [0x80, 0x02],
+                [0x80, 0x04]
+            ]
+        );
+        assert!(commands[5].starts_with(&[0x10]));
+        assert_eq!(right.reports().len(), 5);
+
+        // Bluetooth controllers need no handshake.
+        let bluetooth = SimulatedDevice::new();
+        JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Left, bluetooth.clone()),
+        )
+        .scan_for_devices()
+        .unwrap();
+        assert!(bluetooth.reports().is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+//! Controller data stored in SPI flash.
+//!
+//! Every controller keeps its factory serial number, body and button
+//! colors and analog stick calibration in its SPI flash, readable with
+//! subcommand `0x10`. [`JoyCon::read_identity`](super::JoyCon::read_identity)
+//! reads them all into a [`ControllerIdentity`]:
+//!
+//! ```no_run
+//! use musical_joycons::joycon::JoyConManager;
+//!
+//! let manager = JoyConManager::new()?;
+//! let mut joycons = manager.connect_and_initialize_joycons()?;
+//! let identity = joycons[0].read_identity()?;
+//! println!(
+//!     "{} {:?} ({})",
+//!     identity.colors.body,
+//!     joycons[0].get_type(),
+//!     joycons[0].serial()
+//! );
+//! # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+//! ```
+//!
+//! # Flash Layout
+//!
+//! | Address | Length | Contents |
+//! |---------|--------|----------|
+//! | `0x6000` | 16 | Serial number (unset if the first byte is `0x80` or more) |
+//! | `0x603D` | 18 | Factory calibration of the left and right sticks |
+//! | `0x6050` | 12 | Body, button, left grip and right grip colors |
+//! | `0x8010` | 22 | User calibration of the left and right sticks |
+
+use std::fmt;
+
+use super::input::StickPosition;
+
+/// Most bytes one SPI flash read returns.
+pub const MAX_SPI_READ: usize = 0x1D;
+
+/// Address of the factory serial number.
+pub(crate) const SERIAL_ADDRESS: u32 = 0x6000;
+/// Length of the serial number.
+pub(crate) const SERIAL_LEN: usize = 16;
+/// Address of the factory stick calibration (left, then right).
+pub(crate) const FACTORY_CALIBRATION_ADDRESS: u32 = 0x603D;
+/// Address of the body, button and grip colors.
+pub(crate) const COLORS_ADDRESS: u32 = 0x6050;
+/// Length of the color block.
+pub(crate) const COLORS_LEN: usize = 12;
+/// Address of the user stick calibration (left, then right).
+pub(crate) const USER_CALIBRATION_ADDRESS: u32 = 0x8010;
+
+/// Length of one stick's calibration.
+const CALIBRATION_LEN: usize = 9;
+/// Marks a user calibration as present.
+const USER_CALIBRATION_MAGIC: [u8; 2] = [0xB2, 0xA1];
+
+/// Official colors with a name, as stored in flash.
+const NAMED_COLORS: [(Color, &str); 9] = [
+    (Color::rgb(0x82, 0x82, 0x82), "grey"),
+    (Color::rgb(0xFF, 0x3C, 0x28), "neon red"),
+    (Color::rgb(0x0A, 0xB9, 0xE6), "neon blue"),
+    (Color::rgb(0xE6, 0xFF, 0x00), "neon yellow"),
+    (Color::rgb(0x1E, 0xDC, 0x00), "neon green"),
+    (Color::rgb(0xFF, 0x32, 0x78), "neon pink"),
+    (Color::rgb(0xB4, 0x00, 0xE6), "neon purple"),
+    (Color::rgb(0xFA, 0xA0, 0x05), "neon orange"),
+    (Color::rgb(0xE1, 0x0F, 0x00), "red"),
+];
+
+/// A 24-bit color from flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    /// Red.
+    pub r: u8,
+    /// Green.
+    pub g: u8,
+    /// Blue.
+    pub b: u8,
+}
+
+impl Color {
+    /// Creates a color from its components.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// The name of an official controller color, e.g. `"neon red"`.
+    pub fn name(self) -> Option<&'static str> {
+        NAMED_COLORS
+            .iter()
+            .find(|(color, _)| *color == self)
+            .map(|&(_, name)| name)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self::rgb(bytes[0], bytes[1], bytes[2])
+    }
+}
+
+impl fmt::Display for Color {
+    /// Writes the color's [`name`](Self::name), or `#rrggbb` if it has none.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b),
+        }
+    }
+}
+
+/// A controller's colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ControllerColors {
+    /// Body color.
+    pub body: Color,
+    /// Button color.
+    pub buttons: Color,
+    /// Left grip color (Pro Controller only).
+    pub left_grip: Option<Color>,
+    /// Right grip color (Pro Controller only).
+    pub right_grip: Option<Color>,
+}
+
+impl ControllerColors {
+    /// Decodes the 12-byte color block. Grip colors are kept only when set
+    /// (not erased to `0xFF`).
+    pub(crate) fn parse(bytes: &[u8]) -> Self {
+        let grip = |offset: usize| {
+            let color = &bytes[offset..offset + 3];
+            (color != [0xFF; 3]).then(|| Color::from_bytes(color))
+        };
+        Self {
+            body: Color::from_bytes(&bytes[0..3]),
+            buttons: Color::from_bytes(&bytes[3..6]),
+            left_grip: grip(6),
+            right_grip: grip(9),
+        }
+    }
+}
+
+/// Calibration of one analog stick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StickCalibration {
+    /// Raw position at rest.
+    pub center: StickPosition,
+    /// How far each axis travels above the centre.
+    pub above: StickPosition,
+    /// How far each axis travels below the centre.
+    pub below: StickPosition,
+}
+
+impl StickCalibration {
+    /// Decodes the left stick's 9 calibration bytes (above, centre, below).
+    pub(crate) fn parse_left(bytes: &[u8]) -> Option<Self> {
+        Self::parse(bytes, [1, 0, 2])
+    }
+
+    /// Decodes the right stick's 9 calibration bytes (centre, below, above).
+    pub(crate) fn parse_right(bytes: &[u8]) -> Option<Self> {
+        Self::parse(bytes, [0, 2, 1])
+    }
+
+    /// `order` gives the slot of the centre, above and below values.
+    fn parse(bytes: &[u8], order: [usize; 3]) -> Option<Self> {
+        if bytes.len() < CALIBRATION_LEN || bytes[..CALIBRATION_LEN] == [0xFF; CALIBRATION_LEN] {
+            return None;
+        }
+        let slot = |i: usize| StickPosition::decode(&bytes[order[i] * 3..order[i] * 3 + 3]);
+        Some(Self {
+            center: slot(0),
+            above: slot(1),
+            below: slot(2),
+        })
+    }
+
+    /// Maps a raw position to `-1.0..=1.0` on each axis using this
+    /// calibration.
+    pub fn normalize(&self, position: StickPosition) -> (f32, f32) {
+        let axis = |value: u16, center: u16, above: u16, below: u16| {
+            let offset = f32::from(value) - f32::from(center);
+            let range = if offset >= 0.0 { above } else { below };
+            if range == 0 {
+                return 0.0;
+            }
+            (offset / f32::from(range)).clamp(-1.0, 1.0)
+        };
+        (
+            axis(position.x, self.center.x, self.above.x, self.below.x),
+            axis(position.y, self.center.y, self.above.y, self.below.y),
+        )
+    }
+}
+
+/// What a controller's SPI flash says about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerIdentity {
+    /// Factory serial number, if one is set.
+    pub serial: Option<String>,
+    /// Body, button and grip colors.
+    pub colors: ControllerColors,
+    /// Left stick calibration (Left Joy-Con and Pro Controller): the user
+    /// calibration if there is one, otherwise the factory one.
+    pub left_stick: Option<StickCalibration>,
+    /// Right stick calibration (Right Joy-Con and Pro Controller).
+    pub right_stick: Option<StickCalibration>,
+}
+
+/// Decodes a serial number. Unset serials start with a byte of `0x80` or
+/// more; the rest is NUL-padded ASCII.
+pub(crate) fn parse_serial(bytes: &[u8]) -> Option<String> {
+    if bytes.first().is_none_or(|&b| b >= 0x80) {
+        return None;
+    }
+    let serial: String = bytes
+        .iter()
+        .filter(|&&b| b != 0)
+        .map(|&b| char::from(b))
+        .collect();
+    (!serial.is_empty()).then_some(serial)
+}
+
+/// Picks each stick's calibration from the 22-byte user block, falling
+/// back to the 18-byte factory block.
+pub(crate) fn parse_calibration(
+    factory: &[u8],
+    user: &[u8],
+) -> (Option<StickCalibration>, Option<StickCalibration>) {
+    let user_stick = |offset: usize| {
+        let block = &user[offset..offset + 2 + CALIBRATION_LEN];
+        (block[..2] == USER_CALIBRATION_MAGIC).then(|| &block[2..])
+    };
+    let left = user_stick(0).unwrap_or(&factory[..CALIBRATION_LEN]);
+    let right = user_stick(11).unwrap_or(&factory[CALIBRATION_LEN..]);
+    (
+        StickCalibration::parse_left(left),
+        StickCalibration::parse_right(right),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_have_names() {
+        let colors = ControllerColors::parse(&[
+            0xFF, 0x3C, 0x28, 0x1E, 0x0A, 0x0A, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        ]);
+        assert_eq!(colors.body.to_string(), "neon red");
+        assert_eq!(colors.buttons.to_string(), "#1e0a0a");
+        assert_eq!(colors.left_grip, None);
+    }
+
+    #[test]
+    fn serial_numbers() {
+        assert_eq!(
+            parse_serial(b"XBW17001234567\0\0").as_deref(),
+            Some("XBW17001234567")
+        );
+        assert_eq!(parse_serial(&[0xFF; 16]), None);
+        assert_eq!(parse_serial(&[0; 16]), None);
+    }
+
+    #[test]
+    fn calibration_prefers_user_data() {
+        let encode = |positions: [StickPosition; 3]| -> Vec<u8> {
+            positions.iter().flat_map(|p| p.encode()).collect()
+        };
+        let center = StickPosition { x: 2000, y: 2100 };
+        let above = StickPosition { x: 1500, y: 1400 };
+        let below = StickPosition { x: 1300, y: 1200 };
+
+        let mut factory = encode([above, center, below]);
+        factory.extend(encode([center, below, above]));
+        let mut user = vec![0xFF; 22];
+
+        let (left, right) = parse_calibration(&factory, &user);
+        let expected = StickCalibration {
+            center,
+            above,
+            below,
+        };
+        assert_eq!(left, Some(expected));
+        assert_eq!(right, Some(expected));
+
+        // A user calibration for the left stick overrides the factory one.
+        let user_center = StickPosition { x: 2048, y: 2048 };
+        user[..2].copy_from_slice(&USER_CALIBRATION_MAGIC);
+        user[2..11].copy_from_slice(&encode([above, user_center, below]));
+        let (left, right) = parse_calibration(&factory, &user);
+        assert_eq!(left.unwrap().center, user_center);
+        assert_eq!(right, Some(expected));
+
+        let (x, y) = expected.normalize(StickPosition { x: 3500, y: 900 });
+        assert_eq!(x, 1.0);
+        assert_eq!(y, -1.0);
+        assert_eq!(expected.normalize(center), (0.0, 0.0));
+    }
+}
@@ -30,6 +30,26 @@ pub(crate) enum Command {
     SubCommand = 0x01,
 }
 
+/// Report ID of commands to controllers connected by USB.
+pub(crate) const USB_COMMAND: u8 = 0x80;
+
+/// Report ID of the controller's answers to [`USB_COMMAND`] reports.
+pub(crate) const USB_REPLY: u8 = 0x81;
+
+/// Commands sent in [`USB_COMMAND`] reports to controllers connected by USB.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum UsbCommand {
+    /// Asks for the connection status and MAC address.
+    Status = 0x01,
+    /// Starts talking to the controller over its UART.
+    Handshake = 0x02,
+    /// Switches the UART to 3 Mbit/s.
+    HighSpeed = 0x03,
+    /// Keeps the controller in USB HID mode without timing out. Not
+    /// answered.
+    UsbOnly = 0x04,
+}
+
 /// HID subcommands for JoyCon configuration.
 #[derive(Debug, Clone, Copy)]
 #[allow(dead_code)]
@@ -38,6 +58,9 @@ pub(crate) enum Subcommand {
     EnableImu = 0x40,
     SetInputMode = 0x03,
     GetDeviceInfo = 0x02,
+    SpiFlashRead = 0x10,
+    SetPlayerLights = 0x30,
+    SetHomeLight = 0x38,
 }
 
 /// Identifies the type of JoyCon device.
@@ -53,6 +76,16 @@ pub enum JoyConType {
     Other(u16),
 }
 
+/// One half of a controller with two rumble motors, such as a Pro
+/// Controller: the motor in that grip, and the buttons on that side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Motor {
+    /// The left grip
+    Left,
+    /// The right grip
+    Right,
+}
+
 /// Information about a connected HID device.
 #[derive(Debug, Clone)]
 pub struct DeviceInfo {
@@ -60,11 +93,13 @@ pub struct DeviceInfo {
     pub product_id: u16,
     /// Interface number (used for charging grip detection)
     pub interface_number: i32,
+    /// Whether the device is connected by USB (a charging grip or cable)
+    /// rather than Bluetooth
+    pub usb: bool,
     /// Serial number of the device
     pub serial: String,
-    /// HID device path (reserved for future use)
-    #[allow(dead_code)]
-    pub(crate) path: String,
+    /// Backend-specific device path
+    pub path: String,
     /// Vendor ID (reserved for future use)
     #[allow(dead_code)]
     pub(crate) vendor_id: i32,
@@ -91,4 +126,22 @@ pub enum JoyConError {
     /// Invalid rumble frequency or amplitude parameters.
     #[error("Invalid rumble parameters: {0}")]
     InvalidRumble(&'static str),
+
+    /// The controller never answered a subcommand, even after retrying.
+    #[error("No reply to subcommand {0:#04x}")]
+    NoReply(u8),
+
+    /// A controller connected by USB never answered a `0x80` command, as
+    /// when a charging grip slot is empty.
+    #[error("No reply to USB command {0:#04x}")]
+    NoUsbReply(u8),
+
+    /// The controller answered a subcommand without acknowledging it.
+    #[error("Subcommand {subcommand:#04x} rejected (ack byte {ack:#04x})")]
+    Rejected {
+        /// ID of the rejected subcommand.
+        subcommand: u8,
+        /// The acknowledgement byte of the reply.
+        ack: u8,
+    },
 }
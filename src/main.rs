@@ -1,8 +1,879 @@
-use musical_joycons::midi::play_midi_file;
-use std::io;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use musical_joycons::joycon::{BatteryLevel, Button, JoyConManager};
+use musical_joycons::midi::conductor::ConductorOptions;
+use musical_joycons::midi::events::PartRole;
+use musical_joycons::midi::expression::ExpressionOptions;
+use musical_joycons::midi::instrument::{play_instrument, InstrumentOptions, Scale, StickMode};
+#[cfg(feature = "live")]
+use musical_joycons::midi::live::{
+    list_midi_ports, play_live, ChannelRouting, LiveOptions, LiveSource,
+};
+use musical_joycons::midi::render::render_midi_file;
+use musical_joycons::midi::rhythm::{play_rhythm_game, Difficulty, Judgement, RhythmOptions};
+use musical_joycons::midi::{
+    analyze_midi, parse_midi_to_rumble_with, play_midi_file_with, play_playlist_with,
+    AnalysisReport, ButtonMap, ConversionOptions, EventSender, PlaybackAction, PlaybackEvent,
+    PlaybackOptions, Playlist, RepeatMode, Verbosity, VoicePolicy,
+};
+use serde::Serialize;
+use std::io::{self, Write};
 use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
 
-fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Play MIDI files through Nintendo JoyCon rumble motors.
+///
+/// Running without arguments prompts for a file, so the binary still works
+/// by dragging a MIDI file onto the terminal.
+#[derive(Debug, Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// MIDI files, directories or .m3u lists to play (shorthand for `play`)
+    files: Vec<PathBuf>,
+
+    #[command(flatten)]
+    play: PlayArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Play MIDI files, directories or .m3u lists on connected JoyCons
+    Play {
+        /// MIDI files, directories or .m3u lists, played in order
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        #[command(flatten)]
+        play: PlayArgs,
+    },
+    /// Print part analysis and the playback plan without connecting
+    Analyze {
+        /// MIDI files, directories or .m3u lists to analyze
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Print a JSON report instead of the human-readable summary
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        song: SongArgs,
+    },
+    /// Render what the JoyCons would play to a stereo WAV file
+    Render {
+        /// MIDI file to render
+        file: PathBuf,
+
+        /// Output WAV path [default: input with a .wav extension]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        song: SongArgs,
+    },
+    /// Play a MIDI keyboard or DAW through the JoyCons in real time
+    #[cfg(feature = "live")]
+    Live(LiveArgs),
+    /// Play the JoyCons as an instrument with their buttons and stick
+    Instrument(InstrumentArgs),
+    /// Play a rhythm game: press a button in time with the song's melody
+    Game {
+        /// MIDI file to play
+        file: PathBuf,
+
+        /// How many of the melody's notes to hit
+        #[arg(long, value_enum, default_value_t = Level::Normal)]
+        difficulty: Level,
+
+        /// Use at most this many controllers, one per player
+        #[arg(short = 'n', long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+        joycons: Option<u16>,
+
+        #[command(flatten)]
+        song: SongArgs,
+    },
+    /// List connected JoyCons and Pro Controllers
+    ListDevices,
+}
+
+#[cfg(feature = "live")]
+#[derive(Debug, Clone, Args)]
+struct LiveArgs {
+    /// Input port to listen on, by name or index [default: first port]
+    #[arg(long, conflicts_with_all = ["virtual_port", "raw"])]
+    port: Option<String>,
+
+    /// Create a virtual input port with this name instead
+    #[arg(long = "virtual", value_name = "NAME", conflicts_with = "raw")]
+    virtual_port: Option<String>,
+
+    /// Read a raw MIDI byte stream (device node, FIFO or file)
+    #[arg(long, value_name = "PATH")]
+    raw: Option<PathBuf>,
+
+    /// Route a MIDI channel to an output, both counted from 1; a Pro Controller is two outputs, right motor first (repeatable)
+    #[arg(long = "route", value_name = "CH:JOYCON", value_parser = parse_route)]
+    routes: Vec<(u8, usize)>,
+
+    /// Which held note each JoyCon plays
+    #[arg(long, value_enum, default_value_t = Priority::Last)]
+    priority: Priority,
+
+    /// Transpose every note by this many semitones
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    transpose: i32,
+
+    /// Use at most this many controllers
+    #[arg(short = 'n', long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    joycons: Option<u16>,
+
+    /// List MIDI input ports and exit
+    #[arg(long)]
+    list_ports: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+struct InstrumentArgs {
+    /// Scale the buttons play
+    #[arg(long, value_enum, default_value_t = ScaleName::Major)]
+    scale: ScaleName,
+
+    /// Root note of the scale, as a MIDI note number (60 = middle C)
+    #[arg(long, value_name = "NOTE", default_value_t = 60, value_parser = clap::value_parser!(u8).range(0..=127))]
+    root: u8,
+
+    /// What pushing the stick up or down does
+    #[arg(long, value_enum, default_value_t = Stick::Octave)]
+    stick: Stick,
+
+    /// Largest pitch bend with `--stick bend`, in semitones
+    #[arg(long, value_name = "SEMITONES", default_value_t = 2.0, value_parser = parse_bend_range)]
+    bend_range: f32,
+
+    /// Which held button each JoyCon plays
+    #[arg(long, value_enum, default_value_t = Priority::Last)]
+    priority: Priority,
+
+    /// Play each controller's notes on its partner (1 with 2, 3 with 4, ...)
+    #[arg(long)]
+    partner: bool,
+
+    /// Save the performance to this MIDI file
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Use at most this many controllers
+    #[arg(short = 'n', long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    joycons: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ScaleName {
+    Major,
+    Minor,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+    Chromatic,
+}
+
+impl From<ScaleName> for Scale {
+    fn from(scale: ScaleName) -> Self {
+        match scale {
+            ScaleName::Major => Scale::Major,
+            ScaleName::Minor => Scale::Minor,
+            ScaleName::MajorPentatonic => Scale::MajorPentatonic,
+            ScaleName::MinorPentatonic => Scale::MinorPentatonic,
+            ScaleName::Blues => Scale::Blues,
+            ScaleName::Chromatic => Scale::Chromatic,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Stick {
+    Octave,
+    Bend,
+}
+
+impl From<Stick> for StickMode {
+    fn from(stick: Stick) -> Self {
+        match stick {
+            Stick::Octave => StickMode::Octave,
+            Stick::Bend => StickMode::Bend,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Level {
+    Easy,
+    Normal,
+    Hard,
+    Expert,
+}
+
+impl From<Level> for Difficulty {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Easy => Difficulty::Easy,
+            Level::Normal => Difficulty::Normal,
+            Level::Hard => Difficulty::Hard,
+            Level::Expert => Difficulty::Expert,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Priority {
+    First,
+    Last,
+    Highest,
+    Lowest,
+}
+
+impl From<Priority> for VoicePolicy {
+    fn from(priority: Priority) -> Self {
+        match priority {
+            Priority::First => VoicePolicy::First,
+            Priority::Last => VoicePolicy::Last,
+            Priority::Highest => VoicePolicy::Highest,
+            Priority::Lowest => VoicePolicy::Lowest,
+        }
+    }
+}
+
+/// Note priority for chords in a converted part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Voice {
+    First,
+    Last,
+    Highest,
+    Lowest,
+    Arpeggiate,
+}
+
+impl Voice {
+    fn policy(self, arpeggio_step: Duration) -> VoicePolicy {
+        match self {
+            Voice::First => VoicePolicy::First,
+            Voice::Last => VoicePolicy::Last,
+            Voice::Highest => VoicePolicy::Highest,
+            Voice::Lowest => VoicePolicy::Lowest,
+            Voice::Arpeggiate => VoicePolicy::Arpeggiate {
+                step: arpeggio_step,
+            },
+        }
+    }
+}
+
+/// Options shared by every command that converts a song.
+#[derive(Debug, Clone, Args)]
+struct SongArgs {
+    /// Force this part index (from `analyze`) as the primary part
+    #[arg(long, value_name = "PART")]
+    primary: Option<usize>,
+
+    /// Force this part index (from `analyze`) as the secondary part
+    #[arg(long, value_name = "PART")]
+    secondary: Option<usize>,
+
+    /// Transpose every note by this many semitones
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    transpose: i32,
+
+    /// Playback speed multiplier (2.0 = twice as fast)
+    #[arg(long, default_value_t = 1.0, value_parser = parse_tempo_scale)]
+    tempo_scale: f32,
+
+    /// Play chords as two notes per JoyCon (upper in the high rumble band, lower in the low band)
+    #[arg(long)]
+    two_voice: bool,
+
+    /// Which chord note to play, for every part or one part index (repeatable)
+    #[arg(long = "voice", value_name = "[PART:]POLICY", value_parser = parse_voice)]
+    voices: Vec<(Option<usize>, Voice)>,
+
+    /// Choose the chord note policy of parts without `--voice` from their features
+    #[arg(long)]
+    auto_voice: bool,
+
+    /// Time each chord tone sounds with `--voice arpeggiate`, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 125, value_parser = clap::value_parser!(u64).range(1..))]
+    arpeggio_ms: u64,
+}
+
+#[derive(Debug, Clone, Args)]
+struct PlayArgs {
+    #[command(flatten)]
+    song: SongArgs,
+
+    /// Use at most this many controllers
+    #[arg(short = 'n', long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    joycons: Option<u16>,
+
+    /// Play the playlist in random order
+    #[arg(long)]
+    shuffle: bool,
+
+    /// Repeat the current song or the whole playlist
+    #[arg(long, value_enum, default_value_t = Repeat::Off)]
+    repeat: Repeat,
+
+    /// Bind a Joy-Con button to a playback action, e.g. `home=next-song` or `capture=none` (repeatable)
+    #[arg(long = "button", value_name = "BUTTON=ACTION", value_parser = parse_button_binding)]
+    buttons: Vec<(Button, Option<PlaybackAction>)>,
+
+    /// Ignore the Joy-Con buttons during playback
+    #[arg(long, conflicts_with = "buttons")]
+    no_buttons: bool,
+
+    /// Leave the player lights and Home LED alone during playback
+    #[arg(long)]
+    no_lights: bool,
+
+    /// Fail the song when a controller disconnects instead of waiting for it
+    #[arg(long)]
+    no_reconnect: bool,
+
+    /// Warn when a controller's battery is at or below this level
+    #[arg(long, value_enum, value_name = "LEVEL", default_value_t = BatteryWarning::Low)]
+    low_battery: BatteryWarning,
+
+    /// Tilt a controller to bend its pitch and shake it to accent its notes
+    #[arg(long)]
+    expression: bool,
+
+    /// Largest pitch bend with `--expression`, in semitones
+    #[arg(long, value_name = "SEMITONES", default_value_t = 2.0, requires = "expression", value_parser = parse_bend_range)]
+    bend_range: f32,
+
+    /// Swing a controller like a baton to set the tempo (the one playing the first output unless OUTPUT is given)
+    #[arg(long, value_name = "OUTPUT", num_args = 0..=1, default_missing_value = "1", value_parser = clap::value_parser!(u16).range(1..))]
+    conduct: Option<u16>,
+}
+
+impl PlayArgs {
+    fn expression(&self) -> Option<ExpressionOptions> {
+        self.expression.then(|| ExpressionOptions {
+            bend_range: self.bend_range,
+            ..ExpressionOptions::default()
+        })
+    }
+
+    fn conductor(&self) -> Option<ConductorOptions> {
+        self.conduct.map(|output| ConductorOptions {
+            joycon: usize::from(output - 1),
+            ..ConductorOptions::default()
+        })
+    }
+
+    fn button_map(&self) -> ButtonMap {
+        if self.no_buttons {
+            return ButtonMap::empty();
+        }
+        let mut map = ButtonMap::default();
+        for &(button, action) in &self.buttons {
+            match action {
+                Some(action) => map.bind(button, action),
+                None => map.unbind(button),
+            }
+        }
+        map
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BatteryWarning {
+    Off,
+    Critical,
+    Low,
+    Medium,
+}
+
+impl BatteryWarning {
+    fn threshold(self) -> Option<BatteryLevel> {
+        match self {
+            BatteryWarning::Off => None,
+            BatteryWarning::Critical => Some(BatteryLevel::Critical),
+            BatteryWarning::Low => Some(BatteryLevel::Low),
+            BatteryWarning::Medium => Some(BatteryLevel::Medium),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Repeat {
+    Off,
+    One,
+    All,
+}
+
+impl From<Repeat> for RepeatMode {
+    fn from(repeat: Repeat) -> Self {
+        match repeat {
+            Repeat::Off => RepeatMode::Off,
+            Repeat::One => RepeatMode::One,
+            Repeat::All => RepeatMode::All,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Args)]
+struct OutputArgs {
+    /// Only print warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print every note sent to a JoyCon
+    #[arg(short, long, global = true)]
+    verbose: bool,
+}
+
+impl OutputArgs {
+    fn verbosity(self) -> Verbosity {
+        if self.quiet {
+            Verbosity::Quiet
+        } else if self.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+}
+
+impl SongArgs {
+    fn conversion(&self) -> ConversionOptions {
+        let mut options = ConversionOptions {
+            transpose: self.transpose,
+            tempo_scale: self.tempo_scale,
+            primary_part: self.primary,
+            secondary_part: self.secondary,
+            two_voice: self.two_voice,
+            auto_voice: self.auto_voice,
+            ..ConversionOptions::default()
+        };
+        let step = Duration::from_millis(self.arpeggio_ms);
+        for &(part, voice) in &self.voices {
+            match part {
+                Some(part) => {
+                    options.part_voice_policies.insert(part, voice.policy(step));
+                }
+                None => options.voice_policy = Some(voice.policy(step)),
+            }
+        }
+        options
+    }
+}
+
+fn parse_tempo_scale(s: &str) -> Result<f32, String> {
+    let scale: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if scale.is_finite() && scale > 0.0 {
+        Ok(scale)
+    } else {
+        Err("must be a positive number".to_string())
+    }
+}
+
+fn parse_bend_range(s: &str) -> Result<f32, String> {
+    let semitones: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=12.0).contains(&semitones) {
+        Ok(semitones)
+    } else {
+        Err("must be between 0 and 12 semitones".to_string())
+    }
+}
+
+/// Parses a `--button` value: `BUTTON=ACTION`, where `none` unbinds.
+fn parse_button_binding(s: &str) -> Result<(Button, Option<PlaybackAction>), String> {
+    let (button, action) = s
+        .split_once('=')
+        .ok_or_else(|| "expected BUTTON=ACTION, e.g. home=next-song".to_string())?;
+    let button = button.parse().map_err(|e| format!("{e}"))?;
+    let action = match action.trim() {
+        "none" => None,
+        action => Some(action.parse().map_err(|e| format!("{e}"))?),
+    };
+    Ok((button, action))
+}
+
+/// Parses a `--voice` value: a policy, optionally prefixed by `PART:`.
+fn parse_voice(s: &str) -> Result<(Option<usize>, Voice), String> {
+    let (part, policy) = match s.split_once(':') {
+        Some((part, policy)) => {
+            let part = part.trim().parse().map_err(|e| format!("part: {e}"))?;
+            (Some(part), policy)
+        }
+        None => (None, s),
+    };
+    let voice = Voice::from_str(policy.trim(), true)?;
+    Ok((part, voice))
+}
+
+/// Parses a `CHANNEL:JOYCON` route with 1-based numbers into 0-based ones.
+#[cfg(feature = "live")]
+fn parse_route(s: &str) -> Result<(u8, usize), String> {
+    let (channel, joycon) = s
+        .split_once(':')
+        .ok_or_else(|| "expected CHANNEL:JOYCON, e.g. 10:2".to_string())?;
+    let channel: u8 = channel
+        .trim()
+        .parse()
+        .map_err(|e| format!("channel: {e}"))?;
+    let joycon: usize = joycon.trim().parse().map_err(|e| format!("joycon: {e}"))?;
+    if !(1..=16).contains(&channel) {
+        return Err("channel must be between 1 and 16".to_string());
+    }
+    if joycon == 0 {
+        return Err("joycons are counted from 1".to_string());
+    }
+    Ok((channel - 1, joycon - 1))
+}
+
+fn main() -> Result<(), BoxError> {
+    let cli = Cli::parse();
+    let verbosity = cli.output.verbosity();
+    init_logging(verbosity);
+
+    match cli.command {
+        Some(Command::Play { files, play }) => play_files(&files, &play, verbosity),
+        Some(Command::Analyze { files, json, song }) if json => analyze_files_json(&files, &song),
+        Some(Command::Analyze { files, song, .. }) => analyze_files(&files, &song, verbosity),
+        Some(Command::Render { file, output, song }) => {
+            let output = output.unwrap_or_else(|| file.with_extension("wav"));
+            render_midi_file(&file, &output, &song.conversion())?;
+            Ok(())
+        }
+        #[cfg(feature = "live")]
+        Some(Command::Live(live)) => run_live(&live, verbosity),
+        Some(Command::Instrument(instrument)) => run_instrument(&instrument, verbosity),
+        Some(Command::Game {
+            file,
+            difficulty,
+            joycons,
+            song,
+        }) => with_event_printer(verbosity, |events| {
+            let options = RhythmOptions {
+                difficulty: difficulty.into(),
+                conversion: song.conversion(),
+                max_joycons: joycons.map(usize::from),
+                events,
+                ..RhythmOptions::default()
+            };
+            play_rhythm_game(&file, &options).map(|_| ())
+        }),
+        Some(Command::ListDevices) => list_devices(),
+        None if cli.files.is_empty() => prompt_and_play(verbosity),
+        None => play_files(&cli.files, &cli.play, verbosity),
+    }
+}
+
+/// Prints log records as plain lines: info and debug to stdout, warnings
+/// and errors to stderr. Only this crate's records are shown.
+struct TerminalLogger;
+
+impl log::Log for TerminalLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level() && metadata.target().starts_with("musical_joycons")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // The player puts the terminal in raw mode, where `\n` alone does
+        // not return to the first column.
+        let line = record.args().to_string().replace('\n', "\r\n");
+        // A closed pipe must not take the player down with it.
+        let _ = if record.level() <= log::Level::Warn {
+            writeln!(io::stderr(), "{line}\r")
+        } else {
+            writeln!(io::stdout(), "{line}\r")
+        };
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: TerminalLogger = TerminalLogger;
+
+fn init_logging(verbosity: Verbosity) {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(verbosity.level_filter());
+}
+
+/// Runs `f` with an [`EventSender`] whose events are printed according to
+/// `verbosity`, and waits for every event to be printed.
+fn with_event_printer<T>(verbosity: Verbosity, f: impl FnOnce(EventSender) -> T) -> T {
+    let (events, rx) = EventSender::channel();
+    let printer = thread::spawn(move || {
+        for event in rx {
+            print_event(&event, verbosity);
+        }
+    });
+    let result = f(events);
+    let _ = printer.join();
+    result
+}
+
+fn print_event(event: &PlaybackEvent, verbosity: Verbosity) {
+    let line = match event {
+        PlaybackEvent::DeviceError { joycon, message } => {
+            eprintln!("⚠️  JoyCon {} failed: {}\r", joycon + 1, message);
+            return;
+        }
+        PlaybackEvent::BatteryLow { joycon, level } => {
+            eprintln!("🪫 JoyCon {} battery {}\r", joycon + 1, level);
+            return;
+        }
+        _ if verbosity == Verbosity::Quiet => return,
+        PlaybackEvent::NoteStarted {
+            joycon,
+            frequency,
+            amplitude,
+            time,
+        } if verbosity == Verbosity::Verbose => format!(
+            "🎮 JoyCon {} playing: freq={:.1}, amp={:.2}, t={:.2}s",
+            joycon + 1,
+            frequency,
+            amplitude,
+            time.as_secs_f32()
+        ),
+        PlaybackEvent::TempoChanged { tempo } if verbosity == Verbosity::Verbose => {
+            format!("🪄 Tempo {:.0}%", tempo * 100.0)
+        }
+        PlaybackEvent::NoteStarted { .. }
+        | PlaybackEvent::SongStarted { .. }
+        | PlaybackEvent::TempoChanged { .. } => return,
+        PlaybackEvent::SectionChanged {
+            joycon,
+            section,
+            track,
+        } => format!(
+            "🎼 JoyCon {} → part {} (section {})",
+            joycon + 1,
+            track,
+            section + 1
+        ),
+        PlaybackEvent::Reconnected { joycon } => format!("🔌 JoyCon {} reconnected", joycon + 1),
+        PlaybackEvent::BindingSwapped { primary_on_right } => format!(
+            "🔄 Swapped — primary now on {} Joy-Con",
+            if *primary_on_right { "Right" } else { "Left" }
+        ),
+        PlaybackEvent::PartCycled {
+            role,
+            part,
+            candidate,
+            candidates,
+        } => format!(
+            "🔁 {} → part {} (candidate {}/{})",
+            match role {
+                PartRole::Primary => "Primary",
+                PartRole::Secondary => "Secondary",
+            },
+            part,
+            candidate,
+            candidates
+        ),
+        PlaybackEvent::Paused { .. } => "⏸  Paused".to_string(),
+        PlaybackEvent::Resumed { .. } => "▶️  Resumed".to_string(),
+        PlaybackEvent::Seeked { position } => format!("⏩ {:.1}s", position.as_secs_f32()),
+        PlaybackEvent::VolumeChanged { volume } => format!("🔊 Volume {:.0}%", volume * 100.0),
+        PlaybackEvent::TargetJudged {
+            joycon,
+            judgement,
+            offset,
+            ..
+        } => {
+            let (icon, word) = match judgement {
+                Judgement::Perfect => ("🌟", "perfect"),
+                Judgement::Good => ("✅", "good"),
+                Judgement::Miss => ("❌", "miss"),
+            };
+            let offset = offset
+                .map(|offset| format!(" ({:+.0} ms)", offset * 1000.0))
+                .unwrap_or_default();
+            format!("{icon} JoyCon {} {word}{offset}", joycon + 1)
+        }
+    };
+    println!("{line}\r");
+}
+
+fn play_files(files: &[PathBuf], play: &PlayArgs, verbosity: Verbosity) -> Result<(), BoxError> {
+    let playlist = Playlist::from_paths(files)?;
+    let manager = JoyConManager::new()?;
+    with_event_printer(verbosity, |events| {
+        let options = PlaybackOptions {
+            conversion: play.song.conversion(),
+            max_joycons: play.joycons.map(usize::from),
+            shuffle: play.shuffle,
+            repeat: play.repeat.into(),
+            events,
+            buttons: play.button_map(),
+            lights: !play.no_lights,
+            low_battery: play.low_battery.threshold(),
+            reconnect: !play.no_reconnect,
+            expression: play.expression(),
+            conductor: play.conductor(),
+        };
+        play_playlist_with(&playlist, &manager, &options)
+    })
+}
+
+fn analyze_files(files: &[PathBuf], song: &SongArgs, verbosity: Verbosity) -> Result<(), BoxError> {
+    // Analysis output is the point of this command, so never silence it.
+    log::set_max_level(verbosity.max(Verbosity::Normal).level_filter());
+    for path in Playlist::from_paths(files)?.entries() {
+        println!("📊 Analyzing {:?}", path);
+        let midi_data = std::fs::read(path)?;
+        let (tracks, plan, selection) = parse_midi_to_rumble_with(&midi_data, &song.conversion())?;
+
+        println!("\nRumble tracks: {}", tracks.len());
+        for (idx, track) in tracks.iter().enumerate() {
+            let role = if idx == selection.primary {
+                " [primary]"
+            } else if idx == selection.secondary {
+                " [secondary]"
+            } else {
+                ""
+            };
+            println!(
+                "  Track {} : {} notes, {} cmds, dur={:.1}s, type={:?}, name={:?}{}",
+                idx,
+                track.metrics.note_count,
+                track.commands.len(),
+                track.total_duration.as_secs_f32(),
+                track.metrics.track_type,
+                track.metrics.track_name.as_deref().unwrap_or("-"),
+                role
+            );
+        }
+        println!("  {} section(s) in playback plan\n", plan.sections.len());
+    }
+    Ok(())
+}
+
+/// One entry of `analyze --json` output.
+#[derive(Serialize)]
+struct FileReport {
+    file: PathBuf,
+    #[serde(flatten)]
+    report: AnalysisReport,
+}
+
+fn analyze_files_json(files: &[PathBuf], song: &SongArgs) -> Result<(), BoxError> {
+    let options = song.conversion();
+    let reports = Playlist::from_paths(files)?
+        .entries()
+        .iter()
+        .map(|path| {
+            let midi_data = std::fs::read(path)?;
+            Ok(FileReport {
+                file: path.clone(),
+                report: analyze_midi(&midi_data, &options)?,
+            })
+        })
+        .collect::<Result<Vec<_>, BoxError>>()?;
+    // Write through a locked handle so a closed pipe (`| head`) is an error, not a panic.
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &reports)?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+#[cfg(feature = "live")]
+fn run_live(live: &LiveArgs, verbosity: Verbosity) -> Result<(), BoxError> {
+    if live.list_ports {
+        let ports = list_midi_ports()?;
+        if ports.is_empty() {
+            println!("No MIDI input ports found.");
+        }
+        for (idx, name) in ports.iter().enumerate() {
+            println!("{idx}\t{name}");
+        }
+        return Ok(());
+    }
+
+    let source = match (&live.virtual_port, &live.raw) {
+        (Some(name), _) => LiveSource::Virtual(name.clone()),
+        (None, Some(path)) => LiveSource::Raw(path.clone()),
+        (None, None) => LiveSource::Port(live.port.clone().unwrap_or_default()),
+    };
+    let routing = (!live.routes.is_empty()).then(|| {
+        live.routes
+            .iter()
+            .fold(ChannelRouting::none(), |routing, &(channel, joycon)| {
+                routing.route(channel, joycon)
+            })
+    });
+    with_event_printer(verbosity, |events| {
+        let options = LiveOptions {
+            routing,
+            policy: live.priority.into(),
+            transpose: live.transpose,
+            max_joycons: live.joycons.map(usize::from),
+            events,
+        };
+        play_live(&source, &options)
+    })
+}
+
+fn run_instrument(instrument: &InstrumentArgs, verbosity: Verbosity) -> Result<(), BoxError> {
+    with_event_printer(verbosity, |events| {
+        let options = InstrumentOptions {
+            scale: instrument.scale.into(),
+            root: instrument.root,
+            stick: instrument.stick.into(),
+            bend_range: instrument.bend_range,
+            policy: instrument.priority.into(),
+            partner: instrument.partner,
+            record: instrument.record.clone(),
+            max_joycons: instrument.joycons.map(usize::from),
+            events,
+            ..InstrumentOptions::default()
+        };
+        play_instrument(&options)
+    })
+}
+
+fn list_devices() -> Result<(), BoxError> {
+    let manager = JoyConManager::new()?;
+    let mut joycons = manager.scan_for_devices()?;
+    if joycons.is_empty() {
+        println!("No JoyCons found.");
+    }
+    for joycon in &mut joycons {
+        let battery = match joycon.query_input() {
+            Ok(Some(state)) if state.charging => format!("{} (charging)", state.battery),
+            Ok(Some(state)) => state.battery.to_string(),
+            Ok(None) | Err(_) => "unknown".to_string(),
+        };
+        let color = match joycon.read_identity() {
+            Ok(identity) => identity.colors.body.to_string(),
+            Err(_) => "unknown".to_string(),
+        };
+        println!(
+            "{:?}\tvia={}\tserial={}\tcolor={}\tbattery={}\tpath={}",
+            joycon.get_type(),
+            if joycon.is_usb() { "usb" } else { "bluetooth" },
+            joycon.serial(),
+            color,
+            battery,
+            joycon.device_info().path
+        );
+    }
+    Ok(())
+}
+
+fn prompt_and_play(verbosity: Verbosity) -> Result<(), BoxError> {
     println!("Musical JoyCons - MIDI Player");
     println!("=============================");
     println!("Drag and drop your MIDI file into this terminal and press Enter:");
@@ -16,7 +887,190 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         return Ok(());
     }
 
-    play_midi_file(path)?;
+    let manager = JoyConManager::new()?;
+    with_event_printer(verbosity, |events| {
+        let options = PlaybackOptions {
+            events,
+            buttons: ButtonMap::default(),
+            lights: true,
+            low_battery: Some(BatteryLevel::Low),
+            reconnect: true,
+            ..PlaybackOptions::default()
+        };
+        play_midi_file_with(path, &manager, &options)
+    })
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn bare_files_play_with_flags() {
+        let cli = Cli::try_parse_from([
+            "musical_joycons",
+            "a.mid",
+            "b.mid",
+            "--transpose",
+            "-12",
+            "--primary",
+            "3",
+            "-q",
+        ])
+        .unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.files.len(), 2);
+        assert_eq!(cli.play.song.transpose, -12);
+        assert_eq!(cli.play.song.primary, Some(3));
+        assert_eq!(cli.output.verbosity(), Verbosity::Quiet);
+    }
+
+    #[cfg(feature = "live")]
+    #[test]
+    fn live_routes_are_one_based() {
+        assert_eq!(parse_route("10:2"), Ok((9, 1)));
+        assert!(parse_route("0:1").is_err());
+        assert!(parse_route("17:1").is_err());
+        assert!(parse_route("1:0").is_err());
+        assert!(parse_route("1").is_err());
+    }
+
+    #[test]
+    fn voice_flags_set_global_and_part_policies() {
+        assert_eq!(parse_voice("highest"), Ok((None, Voice::Highest)));
+        assert_eq!(parse_voice("2:Lowest"), Ok((Some(2), Voice::Lowest)));
+        assert!(parse_voice("x:lowest").is_err());
+        assert!(parse_voice("loudest").is_err());
+
+        let cli = Cli::try_parse_from([
+            "musical_joycons",
+            "a.mid",
+            "--voice",
+            "lowest",
+            "--voice",
+            "1:arpeggiate",
+            "--arpeggio-ms",
+            "80",
+        ])
+        .unwrap();
+        let options = cli.play.song.conversion();
+        assert_eq!(options.voice_policy, Some(VoicePolicy::Lowest));
+        assert_eq!(
+            options.part_voice_policies.get(&1),
+            Some(&VoicePolicy::Arpeggiate {
+                step: Duration::from_millis(80)
+            })
+        );
+    }
+
+    #[test]
+    fn button_flags_edit_the_default_map() {
+        assert_eq!(
+            parse_button_binding("Home=next-song"),
+            Ok((Button::Home, Some(PlaybackAction::NextSong)))
+        );
+        assert_eq!(parse_button_binding("a=none"), Ok((Button::A, None)));
+        assert!(parse_button_binding("a").is_err());
+        assert!(parse_button_binding("turbo=quit").is_err());
+
+        let cli = Cli::try_parse_from([
+            "musical_joycons",
+            "a.mid",
+            "--button",
+            "home=next-song",
+            "--button",
+            "capture=none",
+        ])
+        .unwrap();
+        let map = cli.play.button_map();
+        assert_eq!(map.action(Button::Home), Some(PlaybackAction::NextSong));
+        assert_eq!(map.action(Button::Capture), None);
+        assert_eq!(map.action(Button::A), Some(PlaybackAction::TogglePause));
+
+        let cli = Cli::try_parse_from(["musical_joycons", "a.mid", "--no-buttons"]).unwrap();
+        assert!(cli.play.button_map().is_empty());
+    }
+
+    #[test]
+    fn conduct_takes_an_optional_controller() {
+        let conductor = |args: &[&str]| {
+            Cli::try_parse_from([&["musical_joycons", "a.mid"], args].concat())
+                .map(|cli| cli.play.conductor().map(|c| c.joycon))
+        };
+        assert_eq!(conductor(&[]).unwrap(), None);
+        assert_eq!(conductor(&["--conduct"]).unwrap(), Some(0));
+        assert_eq!(conductor(&["--conduct", "2"]).unwrap(), Some(1));
+        assert!(conductor(&["--conduct", "0"]).is_err());
+    }
+
+    #[test]
+    fn instrument_takes_scale_root_and_stick() {
+        let cli = Cli::try_parse_from([
+            "musical_joycons",
+            "instrument",
+            "--scale",
+            "minor-pentatonic",
+            "--root",
+            "57",
+            "--stick",
+            "bend",
+            "--partner",
+            "--record",
+            "take.mid",
+        ])
+        .unwrap();
+        let Some(Command::Instrument(instrument)) = cli.command else {
+            panic!("expected the instrument command");
+        };
+        assert_eq!(instrument.scale, ScaleName::MinorPentatonic);
+        assert_eq!(instrument.root, 57);
+        assert_eq!(instrument.stick, Stick::Bend);
+        assert!(instrument.partner);
+        assert_eq!(instrument.record, Some(PathBuf::from("take.mid")));
+
+        assert!(Cli::try_parse_from(["musical_joycons", "instrument", "--root", "128"]).is_err());
+    }
+
+    #[test]
+    fn game_takes_a_difficulty_and_song_flags() {
+        let cli = Cli::try_parse_from([
+            "musical_joycons",
+            "game",
+            "a.mid",
+            "--difficulty",
+            "hard",
+            "--primary",
+            "2",
+        ])
+        .unwrap();
+        let Some(Command::Game {
+            file,
+            difficulty,
+            song,
+            ..
+        }) = cli.command
+        else {
+            panic!("expected the game command");
+        };
+        assert_eq!(file, PathBuf::from("a.mid"));
+        assert_eq!(difficulty, Level::Hard);
+        assert_eq!(song.conversion().primary_part, Some(2));
+
+        assert!(Cli::try_parse_from(["musical_joycons", "game"]).is_err());
+    }
+
+    #[test]
+    fn rejects_non_positive_tempo_scale() {
+        assert!(
+            Cli::try_parse_from(["musical_joycons", "play", "a.mid", "--tempo-scale", "0"])
+                .is_err()
+        );
+        assert!(Cli::try_parse_from(["musical_joycons", "-q", "-v", "a.mid"]).is_err());
+    }
 }
@@ -0,0 +1,333 @@
+//! Shared song position for pause, resume and seek.
+//!
+//! Every playback thread reads the song position from one
+//! [`PlaybackClock`] instead of its own start `Instant`, so pausing or
+//! seeking moves all Joy-Cons together. Each pause, resume or seek bumps a
+//! generation counter; a thread that sees a new generation re-positions its
+//! command index for the new time.
+//!
+//! The clock normally follows the wall clock, but its rate can change
+//! while it runs (see [`PlaybackClock::set_rate`]). A rate change keeps
+//! the generation, so notes already sounding carry on.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Longest a waiting thread sleeps before re-checking its quit flag.
+const QUIT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy)]
+struct ClockState {
+    /// Song position at `running_since`, or the frozen position when paused.
+    base: Duration,
+    /// When the clock last started running; `None` while paused.
+    running_since: Option<Instant>,
+    /// Incremented on every pause, resume and seek.
+    generation: u64,
+    /// Song time that passes per second of wall-clock time.
+    rate: f64,
+}
+
+impl ClockState {
+    fn position(&self) -> Duration {
+        match self.running_since {
+            Some(since) => self.base + since.elapsed().mul_f64(self.rate),
+            None => self.base,
+        }
+    }
+}
+
+/// A pausable, seekable song clock shared by all playback threads.
+///
+/// A new clock is paused at zero; [`play_rumble_tracks`](super::play_rumble_tracks)
+/// starts it once every Joy-Con thread is ready.
+///
+/// # Example
+///
+/// ```
+/// use musical_joycons::midi::PlaybackClock;
+/// use std::time::Duration;
+///
+/// let clock = PlaybackClock::new();
+/// clock.seek_to(Duration::from_secs(30));
+/// assert_eq!(clock.position(), Duration::from_secs(30));
+///
+/// clock.seek_by(-45.0); // clamps at the start of the song
+/// assert_eq!(clock.position(), Duration::ZERO);
+/// ```
+#[derive(Debug)]
+pub struct PlaybackClock {
+    state: Mutex<ClockState>,
+    changed: Condvar,
+}
+
+impl Default for PlaybackClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlaybackClock {
+    /// Creates a paused clock at position zero.
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(ClockState {
+                base: Duration::ZERO,
+                running_since: None,
+                generation: 0,
+                rate: 1.0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ClockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ClockState)) {
+        let mut state = self.lock();
+        f(&mut state);
+        state.generation += 1;
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    /// Current song position.
+    pub fn position(&self) -> Duration {
+        self.lock().position()
+    }
+
+    /// Returns `true` while the clock is paused.
+    pub fn is_paused(&self) -> bool {
+        self.lock().running_since.is_none()
+    }
+
+    /// Counter that changes on every pause, resume and seek.
+    pub fn generation(&self) -> u64 {
+        self.lock().generation
+    }
+
+    /// Song seconds that pass per wall-clock second, `1.0` unless
+    /// [`set_rate`](Self::set_rate) changed it.
+    pub fn rate(&self) -> f32 {
+        self.lock().rate as f32
+    }
+
+    /// Speeds the clock up (`rate` above `1.0`) or slows it down from the
+    /// current position on. Non-positive or non-finite rates are ignored.
+    ///
+    /// Unlike a pause or seek, this keeps the generation: waiting threads
+    /// re-check their deadline but carry on where they are.
+    pub fn set_rate(&self, rate: f32) {
+        if !rate.is_finite() || rate <= 0.0 {
+            return;
+        }
+        let mut state = self.lock();
+        state.base = state.position();
+        if state.running_since.is_some() {
+            state.running_since = Some(Instant::now());
+        }
+        state.rate = f64::from(rate);
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    /// Stops the clock at its current position.
+    pub fn pause(&self) {
+        if self.is_paused() {
+            return;
+        }
+        self.update(|state| {
+            state.base = state.position();
+            state.running_since = None;
+        });
+    }
+
+    /// Restarts the clock from its current position.
+    pub fn resume(&self) {
+        if !self.is_paused() {
+            return;
+        }
+        self.update(|state| state.running_since = Some(Instant::now()));
+    }
+
+    /// Pauses a running clock or resumes a paused one. Returns `true` if the
+    /// clock is now paused.
+    pub fn toggle_pause(&self) -> bool {
+        if self.is_paused() {
+            self.resume();
+            false
+        } else {
+            self.pause();
+            true
+        }
+    }
+
+    /// Moves to `position`, keeping the paused/running state.
+    pub fn seek_to(&self, position: Duration) {
+        self.update(|state| {
+            state.base = position;
+            if state.running_since.is_some() {
+                state.running_since = Some(Instant::now());
+            }
+        });
+    }
+
+    /// Moves forward (positive) or backward (negative) by `seconds`,
+    /// clamping at the start of the song.
+    pub fn seek_by(&self, seconds: f32) {
+        let position = self.position().as_secs_f32() + seconds;
+        self.seek_to(Duration::from_secs_f32(position.max(0.0)));
+    }
+
+    /// Pauses the clock, rewinds it to zero and restores the normal rate.
+    pub fn reset(&self) {
+        self.update(|state| {
+            state.base = Duration::ZERO;
+            state.running_since = None;
+            state.rate = 1.0;
+        });
+    }
+
+    /// Blocks until the song position reaches `target`.
+    ///
+    /// Returns `false` without waiting the full time if the clock is paused,
+    /// the generation moves past `generation`, or `quit` is set.
+    pub fn wait_until(&self, target: Duration, generation: u64, quit: &AtomicBool) -> bool {
+        let mut state = self.lock();
+        loop {
+            if state.generation != generation
+                || state.running_since.is_none()
+                || quit.load(Ordering::Relaxed)
+            {
+                return false;
+            }
+            let position = state.position();
+            if position >= target {
+                return true;
+            }
+            let timeout = (target - position)
+                .div_f64(state.rate)
+                .min(QUIT_POLL_INTERVAL);
+            state = self
+                .changed
+                .wait_timeout(state, timeout)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Blocks while the clock is paused, for at most `timeout`. Returns
+    /// `true` if the clock is running.
+    pub fn wait_running(&self, timeout: Duration) -> bool {
+        let state = self.lock();
+        if state.running_since.is_some() {
+            return true;
+        }
+        self.changed
+            .wait_timeout_while(state, timeout, |state| state.running_since.is_none())
+            .unwrap_or_else(|e| e.into_inner())
+            .0
+            .running_since
+            .is_some()
+    }
+
+    /// Blocks while the clock is paused. Returns `false` if `quit` was set.
+    pub fn wait_while_paused(&self, quit: &AtomicBool) -> bool {
+        let mut state = self.lock();
+        while state.running_since.is_none() {
+            if quit.load(Ordering::Relaxed) {
+                return false;
+            }
+            state = self
+                .changed
+                .wait_timeout(state, QUIT_POLL_INTERVAL)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        !quit.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pause_freezes_position() {
+        let clock = PlaybackClock::new();
+        clock.seek_to(Duration::from_secs(1));
+        clock.resume();
+        std::thread::sleep(Duration::from_millis(20));
+        clock.pause();
+
+        let paused_at = clock.position();
+        assert!(paused_at >= Duration::from_millis(1020));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(clock.position(), paused_at);
+        assert!(clock.is_paused());
+    }
+
+    #[test]
+    fn wait_is_interrupted_by_seek() {
+        let clock = std::sync::Arc::new(PlaybackClock::new());
+        clock.resume();
+        let generation = clock.generation();
+        let quit = AtomicBool::new(false);
+
+        let seeker = std::sync::Arc::clone(&clock);
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            seeker.seek_by(5.0);
+        });
+
+        let started = Instant::now();
+        assert!(!clock.wait_until(Duration::from_secs(10), generation, &quit));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(clock.position() >= Duration::from_secs(5));
+        handle.join().unwrap();
+
+        let generation = clock.generation();
+        let target = clock.position() + Duration::from_millis(10);
+        assert!(clock.wait_until(target, generation, &quit));
+    }
+
+    #[test]
+    fn rate_scales_position_without_resync() {
+        let clock = PlaybackClock::new();
+        clock.set_rate(2.0);
+        let started = Instant::now();
+        clock.resume();
+        let generation = clock.generation();
+        let quit = AtomicBool::new(false);
+
+        // However long the machine takes, the clock runs twice as fast.
+        std::thread::sleep(Duration::from_millis(20));
+        let position = clock.position();
+        assert!(position >= Duration::from_millis(40));
+        assert!(position <= started.elapsed() * 2);
+        assert!(clock.wait_until(position + Duration::from_millis(20), generation, &quit));
+
+        clock.set_rate(0.0);
+        assert_eq!(clock.rate(), 2.0);
+        clock.set_rate(0.5);
+        assert_eq!(clock.generation(), generation);
+        clock.pause();
+        let paused_at = clock.position();
+        clock.set_rate(1.0);
+        assert_eq!(clock.position(), paused_at);
+
+        clock.reset();
+        assert_eq!(clock.rate(), 1.0);
+    }
+
+    #[test]
+    fn wait_running_times_out_while_paused() {
+        let clock = PlaybackClock::new();
+        assert!(!clock.wait_running(Duration::from_millis(5)));
+        clock.resume();
+        assert!(clock.wait_running(Duration::from_millis(5)));
+    }
+}
@@ -0,0 +1,260 @@
+//! Conducting playback with a controller.
+//!
+//! In conductor mode one controller works as a baton: every swing is a
+//! beat, and the song follows the tempo the swings give. A swing counts
+//! when the controller's angular rate rises past
+//! [`ConductorOptions::swing_threshold`] after having settled to half of
+//! it; the moment it does is the beat.
+//!
+//! [`Conductor`] compares the beats with the song's beat grid (see
+//! [`PlaybackPlan::beats`](super::PlaybackPlan::beats)), which comes from
+//! the MIDI tempo map and so follows its tempo changes. On each beat it
+//! sets the speed that brings the song to its next written beat just as
+//! the next swing is due, so the song catches up with a conductor who is
+//! ahead and waits for one who is behind as well as matching their tempo.
+//! Playback applies the speed with
+//! [`PlaybackClock::set_rate`](super::PlaybackClock::set_rate), which
+//! stretches or compresses the schedule of every controller at once.
+//!
+//! When the swings stop, the song carries on at the last speed.
+//!
+//! # Example
+//!
+//! ```
+//! use musical_joycons::joycon::ImuSample;
+//! use musical_joycons::midi::conductor::{Conductor, ConductorOptions};
+//! use std::time::Duration;
+//!
+//! // A song at 120 BPM: a beat every 500 ms.
+//! let beats = (0..8).map(|beat| Duration::from_millis(beat * 500)).collect();
+//! let mut conductor = Conductor::new(ConductorOptions::default(), beats);
+//!
+//! // One 50 ms swing every 400 ms, i.e. at 150 BPM.
+//! let swing = ImuSample {
+//!     acceleration: [0.0, 0.0, 1.0],
+//!     angular_rate: [0.0, 400.0, 0.0],
+//! };
+//! let mut beat = vec![swing; 10];
+//! beat.resize(80, ImuSample::default());
+//!
+//! assert_eq!(conductor.update(&beat, Duration::ZERO), None);
+//! // The song is right on a beat: follow the conducted 150 BPM.
+//! let tempo = conductor.update(&beat, Duration::from_millis(1000)).unwrap();
+//! assert!((tempo - 1.25).abs() < 1e-3);
+//! ```
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::track_types::beat_at;
+use crate::joycon::{magnitude, ImuSample, IMU_SAMPLE_INTERVAL};
+
+/// Intervals between swings averaged into the conducted tempo.
+const TEMPO_WINDOW: usize = 4;
+
+/// Longest gap between two swings that still belong to one run of beats.
+/// After a longer pause the next swing starts counting afresh.
+const MAX_BEAT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Settings of conductor mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConductorOptions {
+    /// The output whose controller conducts, 0-based and numbered like
+    /// [`count_outputs`](super::playback::count_outputs), the same as the
+    /// `joycon` of every [`PlaybackEvent`](super::events::PlaybackEvent).
+    pub joycon: usize,
+    /// Angular rate, in degrees per second, a swing must reach to count
+    /// as a beat.
+    pub swing_threshold: f32,
+    /// Shortest time between two beats; swings closer together are
+    /// ignored.
+    pub min_beat_interval: Duration,
+    /// Slowest playback, relative to the written tempo.
+    pub min_tempo: f32,
+    /// Fastest playback, relative to the written tempo.
+    pub max_tempo: f32,
+}
+
+impl Default for ConductorOptions {
+    fn default() -> Self {
+        Self {
+            joycon: 0,
+            swing_threshold: 200.0,
+            min_beat_interval: Duration::from_millis(200),
+            min_tempo: 0.5,
+            max_tempo: 2.0,
+        }
+    }
+}
+
+/// Follows one controller's swings and turns them into a playback speed.
+#[derive(Debug, Clone)]
+pub struct Conductor {
+    options: ConductorOptions,
+    /// The song's beat grid.
+    beats: Vec<Duration>,
+    /// Time of the latest sample, counted in samples since the start.
+    now: Duration,
+    /// `true` once the controller has settled since the last swing.
+    armed: bool,
+    /// Times of the latest swings, oldest first.
+    swings: VecDeque<Duration>,
+    /// The latest playback speed.
+    tempo: f32,
+}
+
+impl Conductor {
+    /// Creates a conductor for a song with the given beat times, playing
+    /// at the written tempo until the second swing.
+    pub fn new(options: ConductorOptions, beats: Vec<Duration>) -> Self {
+        Self {
+            options,
+            beats,
+            now: Duration::ZERO,
+            armed: true,
+            swings: VecDeque::with_capacity(TEMPO_WINDOW + 1),
+            tempo: 1.0,
+        }
+    }
+
+    /// The settings this conductor follows.
+    pub fn options(&self) -> &ConductorOptions {
+        &self.options
+    }
+
+    /// The latest playback speed, `1.0` at the written tempo.
+    pub fn tempo(&self) -> f32 {
+        self.tempo
+    }
+
+    /// Follows a run of consecutive samples, oldest first, with the song
+    /// at `position`. Returns the new playback speed if the samples hold a
+    /// beat that sets one.
+    ///
+    /// The first beat, and the first after a long pause, only starts the
+    /// count; so does a beat past the end of the beat grid.
+    pub fn update(&mut self, samples: &[ImuSample], position: Duration) -> Option<f32> {
+        let mut swung = false;
+        for sample in samples {
+            self.now += IMU_SAMPLE_INTERVAL;
+            let rate = magnitude(sample.angular_rate);
+            if rate < self.options.swing_threshold / 2.0 {
+                self.armed = true;
+            } else if self.armed && rate >= self.options.swing_threshold {
+                self.armed = false;
+                let too_soon = self
+                    .swings
+                    .back()
+                    .is_some_and(|&last| self.now - last < self.options.min_beat_interval);
+                if !too_soon {
+                    self.swing();
+                    swung = true;
+                }
+            }
+        }
+        if !swung {
+            return None;
+        }
+
+        let intervals = self.swings.len().checked_sub(1).filter(|&n| n > 0)?;
+        let interval = (self.swings[intervals] - self.swings[0]) / intervals as u32;
+        let next_beat = beat_at(&self.beats, position)?.round() as usize + 1;
+        let until_next_beat = self.beats.get(next_beat)?.checked_sub(position)?;
+        let tempo = (until_next_beat.as_secs_f32() / interval.as_secs_f32())
+            .clamp(self.options.min_tempo, self.options.max_tempo);
+        self.tempo = tempo;
+        Some(tempo)
+    }
+
+    /// Records a beat at the current sample.
+    fn swing(&mut self) {
+        if self
+            .swings
+            .back()
+            .is_some_and(|&last| self.now - last > MAX_BEAT_INTERVAL)
+        {
+            self.swings.clear();
+        }
+        if self.swings.len() > TEMPO_WINDOW {
+            self.swings.pop_front();
+        }
+        self.swings.push_back(self.now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A song at 120 BPM.
+    fn beats() -> Vec<Duration> {
+        (0..=20)
+            .map(|beat| Duration::from_millis(beat * 500))
+            .collect()
+    }
+
+    /// One beat of a conductor swinging with `peak` °/s, lasting
+    /// `interval_ms`.
+    fn swing(peak: f32, interval_ms: usize) -> Vec<ImuSample> {
+        let mut samples = vec![
+            ImuSample {
+                acceleration: [0.0, 0.0, 1.0],
+                angular_rate: [peak, 0.0, 0.0],
+            };
+            10
+        ];
+        samples.resize(interval_ms / 5, ImuSample::default());
+        samples
+    }
+
+    fn millis(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn swings_set_tempo_and_phase() {
+        let mut conductor = Conductor::new(ConductorOptions::default(), beats());
+        assert_eq!(conductor.update(&swing(400.0, 400), millis(0)), None);
+        assert_eq!(conductor.tempo(), 1.0);
+
+        // 400 ms swings with the song 100 ms behind: catch up to beat 2.
+        let tempo = conductor.update(&swing(400.0, 400), millis(400)).unwrap();
+        assert!((tempo - 1.5).abs() < 1e-3, "tempo was {tempo}");
+        // Back on the beat: follow the conducted 150 BPM.
+        let tempo = conductor.update(&swing(400.0, 400), millis(1000)).unwrap();
+        assert!((tempo - 1.25).abs() < 1e-3, "tempo was {tempo}");
+
+        // A swing too weak to count, then none at all, keep the tempo.
+        assert_eq!(conductor.update(&swing(150.0, 400), millis(1400)), None);
+        assert_eq!(conductor.update(&swing(0.0, 400), millis(1800)), None);
+        assert_eq!(conductor.tempo(), 1.25);
+
+        // Past the end of the grid there is no next beat to aim for.
+        assert_eq!(conductor.update(&swing(400.0, 400), millis(10_000)), None);
+    }
+
+    #[test]
+    fn tempo_stays_in_range() {
+        let mut conductor = Conductor::new(ConductorOptions::default(), beats());
+        conductor.update(&swing(400.0, 1500), millis(0));
+        let tempo = conductor.update(&swing(400.0, 1500), millis(500)).unwrap();
+        assert_eq!(tempo, 0.5);
+
+        // A bounce right after a beat is not another beat.
+        let mut conductor = Conductor::new(ConductorOptions::default(), beats());
+        conductor.update(&swing(400.0, 100), millis(0));
+        assert_eq!(conductor.update(&swing(400.0, 100), millis(100)), None);
+        let tempo = conductor.update(&swing(400.0, 250), millis(50)).unwrap();
+        assert_eq!(tempo, 2.0);
+    }
+
+    #[test]
+    fn long_pause_restarts_the_count() {
+        let mut conductor = Conductor::new(ConductorOptions::default(), beats());
+        conductor.update(&swing(400.0, 500), millis(0));
+        conductor.update(&swing(400.0, 3000), millis(500));
+        assert_eq!(conductor.update(&swing(400.0, 500), millis(3500)), None);
+        let tempo = conductor.update(&swing(400.0, 500), millis(4000)).unwrap();
+        assert!((tempo - 1.0).abs() < 1e-3, "tempo was {tempo}");
+    }
+}
@@ -0,0 +1,218 @@
+//! Typed playback events.
+//!
+//! Playback and live input report what happens while they run as
+//! [`PlaybackEvent`]s sent on an [`EventSender`]. The library itself prints
+//! nothing: diagnostics go through the [`log`] crate and events go to
+//! whoever holds the receiving end, so a front end decides what to show and
+//! embedders can react to notes, section changes and device errors.
+//!
+//! Sending never blocks, so events are safe to emit from the timing-critical
+//! playback threads. An [`EventSender`] without a receiver (the default)
+//! drops everything.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use musical_joycons::midi::events::{EventSender, PlaybackEvent};
+//! use musical_joycons::midi::{play_midi_file_with, PlaybackOptions};
+//! use musical_joycons::joycon::JoyConManager;
+//!
+//! let (events, rx) = EventSender::channel();
+//! std::thread::spawn(move || {
+//!     for event in rx {
+//!         if let PlaybackEvent::NoteStarted { joycon, frequency, .. } = event {
+//!             println!("JoyCon {joycon}: {frequency:.1} Hz");
+//!         }
+//!     }
+//! });
+//!
+//! let options = PlaybackOptions {
+//!     events,
+//!     ..PlaybackOptions::default()
+//! };
+//! play_midi_file_with("song.mid".into(), &JoyConManager::new()?, &options)?;
+//! # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+//! ```
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use super::rhythm::Judgement;
+use crate::joycon::BatteryLevel;
+
+/// The part role changed by [`PlaybackEvent::PartCycled`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartRole {
+    /// The melody part.
+    Primary,
+    /// The accompaniment part.
+    Secondary,
+}
+
+/// Something that happened during playback.
+///
+/// Joy-Con indices are 0-based positions in the connected controller list,
+/// where playback counts each motor of a Pro Controller as a Joy-Con of its
+/// own (see [`count_outputs`](super::count_outputs)); `time` and `position`
+/// are song positions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaybackEvent {
+    /// A playlist entry was loaded and is about to play.
+    SongStarted {
+        /// The MIDI file.
+        path: PathBuf,
+        /// 0-based position in the play order.
+        position: usize,
+        /// Number of songs in the play order.
+        count: usize,
+    },
+    /// A Joy-Con started sounding a note.
+    NoteStarted {
+        /// Output index, numbered like
+        /// [`count_outputs`](super::playback::count_outputs).
+        joycon: usize,
+        /// Frequency in Hz.
+        frequency: f32,
+        /// Amplitude in `0.0..=1.0`.
+        amplitude: f32,
+        /// Song position when the note was sent.
+        time: Duration,
+    },
+    /// A Joy-Con moved to another part at a playback plan section boundary.
+    SectionChanged {
+        /// Output index, numbered like
+        /// [`count_outputs`](super::playback::count_outputs).
+        joycon: usize,
+        /// 0-based section index in the plan.
+        section: usize,
+        /// Rumble track the Joy-Con now plays.
+        track: usize,
+    },
+    /// The primary part moved to the other Joy-Con side.
+    BindingSwapped {
+        /// `true` if the Right Joy-Con now plays the primary part.
+        primary_on_right: bool,
+    },
+    /// The primary or secondary part changed to the next candidate.
+    PartCycled {
+        /// Which part changed.
+        role: PartRole,
+        /// The rumble track now playing that role.
+        part: usize,
+        /// 1-based position in the candidate list.
+        candidate: usize,
+        /// Number of candidates.
+        candidates: usize,
+    },
+    /// Playback was paused.
+    Paused {
+        /// Song position at the pause.
+        position: Duration,
+    },
+    /// Playback was resumed.
+    Resumed {
+        /// Song position at the resume.
+        position: Duration,
+    },
+    /// The master volume changed.
+    VolumeChanged {
+        /// The new volume in `0.0..=1.0`.
+        volume: f32,
+    },
+    /// The song position jumped (seek or section jump).
+    Seeked {
+        /// The new song position.
+        position: Duration,
+    },
+    /// A Joy-Con's battery dropped to or below the warning threshold.
+    BatteryLow {
+        /// Joy-Con index.
+        joycon: usize,
+        /// The new battery level.
+        level: BatteryLevel,
+    },
+    /// Sending to a Joy-Con failed; its playback thread stops until the
+    /// Joy-Con reconnects, if reconnection is enabled.
+    DeviceError {
+        /// Joy-Con index.
+        joycon: usize,
+        /// The error message.
+        message: String,
+    },
+    /// A Joy-Con that failed was re-opened and resumed playback.
+    Reconnected {
+        /// Joy-Con index.
+        joycon: usize,
+    },
+    /// The conductor's beat changed the playback speed.
+    TempoChanged {
+        /// Speed relative to the written tempo, `1.0` as written.
+        tempo: f32,
+    },
+    /// A rhythm game player hit or missed a target.
+    TargetJudged {
+        /// Output index, numbered like
+        /// [`count_outputs`](super::playback::count_outputs).
+        joycon: usize,
+        /// 0-based target index in the chart.
+        target: usize,
+        /// How well the target was hit.
+        judgement: Judgement,
+        /// Time from the target to the press, in seconds: negative when
+        /// early. `None` for a miss.
+        offset: Option<f32>,
+        /// Song position of the target.
+        time: Duration,
+    },
+}
+
+/// Sending half of a playback event channel.
+///
+/// Cloning is cheap; every clone feeds the same receiver.
+#[derive(Debug, Clone, Default)]
+pub struct EventSender {
+    tx: Option<Sender<PlaybackEvent>>,
+}
+
+impl EventSender {
+    /// Creates a connected sender and its receiver.
+    pub fn channel() -> (Self, Receiver<PlaybackEvent>) {
+        let (tx, rx) = mpsc::channel();
+        (Self { tx: Some(tx) }, rx)
+    }
+
+    /// Sends `event`. Does nothing if there is no receiver or it was dropped.
+    pub fn emit(&self, event: PlaybackEvent) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emit_reaches_receiver_and_survives_drop() {
+        let (events, rx) = EventSender::channel();
+        events.clone().emit(PlaybackEvent::BindingSwapped {
+            primary_on_right: false,
+        });
+        assert_eq!(
+            rx.try_recv(),
+            Ok(PlaybackEvent::BindingSwapped {
+                primary_on_right: false
+            })
+        );
+
+        drop(rx);
+        events.emit(PlaybackEvent::Paused {
+            position: Duration::ZERO,
+        });
+        EventSender::default().emit(PlaybackEvent::Paused {
+            position: Duration::ZERO,
+        });
+    }
+}
@@ -0,0 +1,251 @@
+//! Motion-controlled expression during playback.
+//!
+//! With expression on, the controllers' motion shapes the notes they play
+//! without changing the [`RumbleTrack`](super::RumbleTrack):
+//!
+//! - **Tilt** bends the pitch. Raising the end of the controller along
+//!   its X axis bends up, lowering it bends down, up to
+//!   [`ExpressionOptions::bend_range`] semitones at
+//!   [`ExpressionOptions::max_tilt`] degrees. A controller held level plays
+//!   in tune.
+//! - **Shaking** accents the notes: a jolt of more than
+//!   [`ExpressionOptions::shake_threshold`] g pushes the amplitude toward
+//!   full, fading back over [`ExpressionOptions::accent_decay`].
+//!
+//! [`Expression`] follows one controller's [`ImuSample`]s and applies
+//! the result to each [`RumbleCommand`] just before it is sent; playback
+//! re-sends a sustained note whenever the expression audibly changes it.
+//!
+//! # Example
+//!
+//! ```
+//! use musical_joycons::joycon::ImuSample;
+//! use musical_joycons::midi::expression::{Expression, ExpressionOptions};
+//! use musical_joycons::midi::RumbleCommand;
+//! use std::f32::consts::FRAC_1_SQRT_2;
+//! use std::time::Duration;
+//!
+//! let mut expression = Expression::new(ExpressionOptions::default());
+//! // Tilted 45° with the default 2-semitone range: a whole tone up.
+//! let tilted = ImuSample {
+//!     acceleration: [FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2],
+//!     angular_rate: [0.0; 3],
+//! };
+//! expression.update(&[tilted]);
+//!
+//! let a4 = RumbleCommand {
+//!     frequency: 440.0,
+//!     amplitude: 0.5,
+//!     wait_before: Duration::ZERO,
+//!     low_band: None,
+//! };
+//! let bent = expression.apply(&a4);
+//! assert!((bent.frequency - 493.9).abs() < 0.5);
+//! ```
+
+use std::time::Duration;
+
+use super::rumble::RumbleCommand;
+use crate::joycon::{magnitude, ImuSample, IMU_SAMPLE_INTERVAL};
+
+/// How much of each new sample goes into the gravity estimate. Shakes
+/// average out; a tilt settles within about 100 ms.
+const GRAVITY_SMOOTHING: f32 = 0.05;
+
+/// Settings of motion-controlled expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExpressionOptions {
+    /// Largest pitch bend, in semitones either way.
+    pub bend_range: f32,
+    /// Tilt, in degrees, that bends by the full
+    /// [`bend_range`](Self::bend_range).
+    pub max_tilt: f32,
+    /// Acceleration beyond gravity, in g, that counts as a shake.
+    pub shake_threshold: f32,
+    /// How far a shake pushes notes toward full amplitude, from `0.0`
+    /// (not at all) to `1.0` (all the way).
+    pub accent: f32,
+    /// How long an accent takes to fade.
+    pub accent_decay: Duration,
+}
+
+impl Default for ExpressionOptions {
+    fn default() -> Self {
+        Self {
+            bend_range: 2.0,
+            max_tilt: 45.0,
+            shake_threshold: 1.0,
+            accent: 0.6,
+            accent_decay: Duration::from_millis(200),
+        }
+    }
+}
+
+/// The expression one controller's motion currently applies.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    options: ExpressionOptions,
+    /// Smoothed acceleration, i.e. which way gravity pulls; `None` before
+    /// the first sample.
+    gravity: Option<[f32; 3]>,
+    /// Strength of the current accent, `1.0` right after a shake.
+    accent: f32,
+}
+
+impl Expression {
+    /// Creates an expression with no bend and no accent.
+    pub fn new(options: ExpressionOptions) -> Self {
+        Self {
+            options,
+            gravity: None,
+            accent: 0.0,
+        }
+    }
+
+    /// The settings this expression follows.
+    pub fn options(&self) -> &ExpressionOptions {
+        &self.options
+    }
+
+    /// Follows a run of consecutive samples, oldest first.
+    pub fn update(&mut self, samples: &[ImuSample]) {
+        let decay = if self.options.accent_decay.is_zero() {
+            1.0
+        } else {
+            IMU_SAMPLE_INTERVAL.as_secs_f32() / self.options.accent_decay.as_secs_f32()
+        };
+        for sample in samples {
+            let acceleration = sample.acceleration;
+            if (magnitude(acceleration) - 1.0).abs() >= self.options.shake_threshold {
+                self.accent = 1.0;
+            } else {
+                self.accent = (self.accent - decay).max(0.0);
+            }
+            self.gravity = Some(match self.gravity {
+                Some(gravity) => std::array::from_fn(|i| {
+                    gravity[i] + (acceleration[i] - gravity[i]) * GRAVITY_SMOOTHING
+                }),
+                None => acceleration,
+            });
+        }
+    }
+
+    /// The current pitch bend, in semitones.
+    pub fn bend(&self) -> f32 {
+        let Some(gravity) = self.gravity else {
+            return 0.0;
+        };
+        let total = magnitude(gravity);
+        if total == 0.0 || self.options.max_tilt <= 0.0 {
+            return 0.0;
+        }
+        let tilt = (gravity[0] / total).clamp(-1.0, 1.0).asin().to_degrees();
+        (tilt / self.options.max_tilt).clamp(-1.0, 1.0) * self.options.bend_range
+    }
+
+    /// The current accent, from `0.0` (none) to `1.0` (just shaken).
+    pub fn accent(&self) -> f32 {
+        self.accent
+    }
+
+    /// A copy of `command` with the current bend and accent applied to
+    /// both bands. Silent commands are returned unchanged.
+    pub fn apply(&self, command: &RumbleCommand) -> RumbleCommand {
+        if command.amplitude <= 0.0 {
+            return command.clone();
+        }
+        let ratio = (self.bend() / 12.0).exp2();
+        let boost = self.accent * self.options.accent.clamp(0.0, 1.0);
+        let accented = |amplitude: f32| {
+            if amplitude > 0.0 {
+                (amplitude + (1.0 - amplitude) * boost).min(1.0)
+            } else {
+                amplitude
+            }
+        };
+        RumbleCommand {
+            frequency: command.frequency * ratio,
+            amplitude: accented(command.amplitude),
+            wait_before: command.wait_before,
+            low_band: command
+                .low_band
+                .map(|(frequency, amplitude)| (frequency * ratio, accented(amplitude))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    fn at_rest(x: f32, z: f32) -> ImuSample {
+        ImuSample {
+            acceleration: [x, 0.0, z],
+            angular_rate: [0.0; 3],
+        }
+    }
+
+    fn note(frequency: f32, amplitude: f32) -> RumbleCommand {
+        RumbleCommand {
+            frequency,
+            amplitude,
+            wait_before: Duration::ZERO,
+            low_band: Some((frequency / 2.0, amplitude)),
+        }
+    }
+
+    #[test]
+    fn tilt_bends_both_bands() {
+        let mut expression = Expression::new(ExpressionOptions::default());
+        assert_eq!(expression.bend(), 0.0);
+
+        expression.update(&[at_rest(0.0, 1.0)]);
+        assert_eq!(expression.apply(&note(440.0, 0.5)), note(440.0, 0.5));
+
+        // Tipped past the maximum tilt the other way: a full bend down.
+        let mut expression = Expression::new(ExpressionOptions::default());
+        expression.update(&[at_rest(-0.9, 0.44)]);
+        assert_eq!(expression.bend(), -2.0);
+        let bent = expression.apply(&note(440.0, 0.5));
+        assert!((bent.frequency - 392.0).abs() < 0.1);
+        assert!((bent.low_band.unwrap().0 - 196.0).abs() < 0.1);
+        assert_eq!(bent.amplitude, 0.5);
+
+        // Silence stays silent.
+        assert_eq!(expression.apply(&note(0.0, 0.0)), note(0.0, 0.0));
+    }
+
+    #[test]
+    fn gravity_settles_smoothly() {
+        let mut expression = Expression::new(ExpressionOptions::default());
+        expression.update(&[at_rest(0.0, 1.0)]);
+        expression.update(&[at_rest(FRAC_1_SQRT_2, FRAC_1_SQRT_2)]);
+        let first = expression.bend();
+        assert!(first > 0.0 && first < 0.2);
+
+        expression.update(&[at_rest(FRAC_1_SQRT_2, FRAC_1_SQRT_2); 100]);
+        assert!((expression.bend() - 2.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn shakes_accent_then_fade() {
+        let mut expression = Expression::new(ExpressionOptions::default());
+        expression.update(&[at_rest(0.0, 1.0)]);
+        assert_eq!(expression.accent(), 0.0);
+
+        // A 2.5 g jolt along Z.
+        expression.update(&[at_rest(0.0, 2.5)]);
+        assert_eq!(expression.accent(), 1.0);
+        let accented = expression.apply(&note(440.0, 0.5));
+        assert!((accented.amplitude - 0.8).abs() < 1e-6);
+        assert!((accented.low_band.unwrap().1 - 0.8).abs() < 1e-6);
+
+        // 20 samples = 100 ms: half faded.
+        expression.update(&[at_rest(0.0, 1.0); 20]);
+        assert!((expression.accent() - 0.5).abs() < 1e-3);
+        expression.update(&[at_rest(0.0, 1.0); 21]);
+        assert_eq!(expression.accent(), 0.0);
+        assert_eq!(expression.apply(&note(440.0, 0.5)).amplitude, 0.5);
+    }
+}
@@ -0,0 +1,803 @@
+//! Joy-Cons as a playable instrument.
+//!
+//! Instrument mode needs no file: the buttons play notes of a [`Scale`]
+//! and the rumble motor sounds them. Each output is one monophonic player
+//! with its own [`MonoVoice`], so holding several buttons plays the one the
+//! [`VoicePolicy`] picks. Outputs are numbered like
+//! [`count_outputs`](super::playback::count_outputs): a Joy-Con is one
+//! player, and each half of a Pro Controller is a player of its own, with
+//! that half's buttons and stick and motor.
+//!
+//! # Layout
+//!
+//! [`InstrumentOptions::buttons`] maps buttons to scale degrees, `0` being
+//! the root. The default layout ([`DEFAULT_NOTE_BUTTONS`]) gives each side
+//! an octave of a seven-note scale: the four direction or face buttons
+//! counter-clockwise from the left, then the shoulder button, the trigger
+//! and the two rail buttons. Scales with fewer notes carry on into the next
+//! octave.
+//!
+//! | Degree | Left side | Right side |
+//! |--------|-----------|------------|
+//! | 0 | ← | Y |
+//! | 1 | ↓ | B |
+//! | 2 | → | A |
+//! | 3 | ↑ | X |
+//! | 4 | L | R |
+//! | 5 | ZL | ZR |
+//! | 6 | SL | SL |
+//! | 7 | SR | SR |
+//!
+//! The player's stick ([`StickMode`]) shifts the note an octave up or
+//! down or bends it. **Home** or **Capture** on any controller, or **Q**
+//! on the keyboard, stops playing.
+//!
+//! # Partners
+//!
+//! With [`InstrumentOptions::partner`] set, players pair up: the first
+//! one's notes sound on the second one's motor and the other way round, so
+//! one player can feel the other's notes.
+//!
+//! # Recording
+//!
+//! With [`InstrumentOptions::record`] set, the performance is saved as a
+//! MIDI file with one track per player when playing stops. It plays
+//! back like any other file, through
+//! [`parse_midi_to_rumble`](super::parse_midi_to_rumble). Bends are not
+//! recorded, nor are players past the [`MAX_RECORDED_PLAYERS`] that fit
+//! on their own channels, and a performance without a note is not saved.
+//!
+//! # Example
+//!
+//! ```
+//! use musical_joycons::joycon::{Button, Buttons};
+//! use musical_joycons::midi::instrument::{Instrument, InstrumentOptions, Scale};
+//!
+//! let options = InstrumentOptions {
+//!     scale: Scale::MinorPentatonic,
+//!     root: 57, // A3
+//!     ..InstrumentOptions::default()
+//! };
+//! let mut instrument = Instrument::new(&options, 1);
+//!
+//! // X is the fourth degree of the scale: E4.
+//! let held: Buttons = [Button::X].into_iter().collect();
+//! let output = instrument.update(0, held, (0.0, 0.0)).unwrap();
+//! assert_eq!(output.note, Some(64));
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use midly::num::{u15, u24, u28, u4, u7};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+
+use crate::joycon::{
+    Button, Buttons, DeviceBackend, InputState, JoyCon, JoyConManager, JoyConType, RumbleSink,
+};
+
+use super::clock::PlaybackClock;
+use super::events::{EventSender, PlaybackEvent};
+use super::interactive::{run_interactive, Step};
+use super::playback::OutputTarget;
+use super::rumble::note_to_frequency;
+use super::voice::{MonoVoice, VoicePolicy};
+use super::JoyConSide;
+
+/// Stick travel, from `0.0` to `1.0`, ignored around the centre.
+const STICK_DEAD_ZONE: f32 = 0.15;
+
+/// Stick travel that shifts the note by an octave.
+const OCTAVE_THRESHOLD: f32 = 0.5;
+
+/// Smallest bend change, in semitones, worth re-sending a note for.
+const AUDIBLE_BEND: f32 = 0.05;
+
+/// Tick resolution of recordings.
+const TICKS_PER_BEAT: u16 = 480;
+
+/// Tempo of recordings, in microseconds per beat (120 BPM).
+const RECORDING_TEMPO: u32 = 500_000;
+
+/// The MIDI drum channel, which recordings skip.
+const DRUM_CHANNEL: usize = 9;
+
+/// Players a recording holds: one per MIDI channel but the drum channel.
+pub const MAX_RECORDED_PLAYERS: usize = 15;
+
+/// Buttons of the default layout and the scale degree each one plays.
+pub const DEFAULT_NOTE_BUTTONS: [(Button, i32); 16] = [
+    (Button::Left, 0),
+    (Button::Down, 1),
+    (Button::Right, 2),
+    (Button::Up, 3),
+    (Button::L, 4),
+    (Button::Zl, 5),
+    (Button::LeftSl, 6),
+    (Button::LeftSr, 7),
+    (Button::Y, 0),
+    (Button::B, 1),
+    (Button::A, 2),
+    (Button::X, 3),
+    (Button::R, 4),
+    (Button::Zr, 5),
+    (Button::RightSl, 6),
+    (Button::RightSr, 7),
+];
+
+/// The notes the buttons play, as steps above the root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scale {
+    /// Major (Ionian).
+    #[default]
+    Major,
+    /// Natural minor (Aeolian).
+    Minor,
+    /// Major pentatonic.
+    MajorPentatonic,
+    /// Minor pentatonic.
+    MinorPentatonic,
+    /// Blues: minor pentatonic with the flat fifth.
+    Blues,
+    /// All twelve semitones.
+    Chromatic,
+}
+
+impl Scale {
+    /// Semitones above the root of each note in one octave.
+    pub fn intervals(self) -> &'static [i32] {
+        match self {
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Scale::Blues => &[0, 3, 5, 6, 7, 10],
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        }
+    }
+
+    /// The MIDI note of `degree` steps above `root`, wrapping into higher
+    /// (or, for negative degrees, lower) octaves.
+    pub fn note(self, root: u8, degree: i32) -> i32 {
+        let intervals = self.intervals();
+        let steps = intervals.len() as i32;
+        i32::from(root)
+            + degree.div_euclid(steps) * 12
+            + intervals[degree.rem_euclid(steps) as usize]
+    }
+}
+
+/// What the stick does to the note being played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StickMode {
+    /// Up or down past half way plays an octave higher or lower.
+    #[default]
+    Octave,
+    /// Up or down bends the pitch, by up to
+    /// [`InstrumentOptions::bend_range`] semitones.
+    Bend,
+}
+
+/// Options for [`play_instrument`].
+#[derive(Debug, Clone)]
+pub struct InstrumentOptions {
+    /// The scale the buttons play.
+    pub scale: Scale,
+    /// MIDI note of degree 0. Defaults to middle C (60).
+    pub root: u8,
+    /// Buttons that play notes and their scale degrees; defaults to
+    /// [`DEFAULT_NOTE_BUTTONS`].
+    pub buttons: Vec<(Button, i32)>,
+    /// What the stick does.
+    pub stick: StickMode,
+    /// Largest bend in [`StickMode::Bend`], in semitones either way.
+    pub bend_range: f32,
+    /// Amplitude of every note, in `0.0..=1.0`.
+    pub amplitude: f32,
+    /// Which held note each player plays.
+    pub policy: VoicePolicy,
+    /// Sound each player's notes on its partner: the first and second
+    /// output swap motors, as do the third and fourth, and so on.
+    pub partner: bool,
+    /// Save the performance to this MIDI file when playing stops.
+    pub record: Option<PathBuf>,
+    /// Play on at most this many controllers; all connected ones when `None`.
+    pub max_joycons: Option<usize>,
+    /// Receives a [`PlaybackEvent::NoteStarted`] for every note played,
+    /// timed from when playing started.
+    pub events: EventSender,
+}
+
+impl Default for InstrumentOptions {
+    fn default() -> Self {
+        Self {
+            scale: Scale::default(),
+            root: 60,
+            buttons: DEFAULT_NOTE_BUTTONS.to_vec(),
+            stick: StickMode::default(),
+            bend_range: 2.0,
+            amplitude: 0.8,
+            policy: VoicePolicy::Last,
+            partner: false,
+            record: None,
+            max_joycons: None,
+            events: EventSender::default(),
+        }
+    }
+}
+
+/// A rumble state change produced by [`Instrument::update`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstrumentOutput {
+    /// Index of the output that sounds the note.
+    pub joycon: usize,
+    /// The MIDI note, before any bend, or `None` for silence.
+    pub note: Option<u8>,
+    /// Frequency in Hz, `0.0` for silence.
+    pub frequency: f32,
+    /// Amplitude in `0.0..=1.0`, `0.0` for silence.
+    pub amplitude: f32,
+}
+
+/// One output's part of the instrument.
+#[derive(Debug, Clone)]
+struct Player {
+    voice: MonoVoice,
+    /// Buttons held at the last update.
+    buttons: Buttons,
+    /// The note and bend sounding, if any.
+    sounding: Option<(u8, f32)>,
+}
+
+/// Turns controller input into notes.
+///
+/// This is the device-independent core of instrument mode: it owns one
+/// [`Player`] per output and reports an [`InstrumentOutput`] whenever the
+/// note a player plays changes.
+#[derive(Debug, Clone)]
+pub struct Instrument {
+    scale: Scale,
+    root: u8,
+    buttons: Vec<(Button, i32)>,
+    stick: StickMode,
+    bend_range: f32,
+    amplitude: f32,
+    partner: bool,
+    players: Vec<Player>,
+}
+
+impl Instrument {
+    /// Creates an instrument for `num_players` outputs with nothing held.
+    pub fn new(options: &InstrumentOptions, num_players: usize) -> Self {
+        let player = Player {
+            voice: MonoVoice::new(options.policy),
+            buttons: Buttons::default(),
+            sounding: None,
+        };
+        Self {
+            scale: options.scale,
+            root: options.root,
+            buttons: options.buttons.clone(),
+            stick: options.stick,
+            bend_range: options.bend_range,
+            amplitude: options.amplitude.clamp(0.0, 1.0),
+            partner: options.partner,
+            players: vec![player; num_players],
+        }
+    }
+
+    /// The output that sounds `player`'s notes.
+    pub fn output_for(&self, player: usize) -> usize {
+        let partner = player ^ 1;
+        if self.partner && partner < self.players.len() {
+            partner
+        } else {
+            player
+        }
+    }
+
+    /// Applies one player's held buttons and stick position, the stick
+    /// mapped to `-1.0..=1.0` on each axis. Returns the new state of the
+    /// output sounding its notes if it changed.
+    pub fn update(
+        &mut self,
+        player: usize,
+        buttons: Buttons,
+        stick: (f32, f32),
+    ) -> Option<InstrumentOutput> {
+        let pitch = |degree: i32| self.scale.note(self.root, degree).clamp(0, 127) as u8;
+        let state = self.players.get_mut(player)?;
+        let released = state.buttons.pressed_since(buttons);
+        let pressed = buttons.pressed_since(state.buttons);
+        state.buttons = buttons;
+        for &(button, degree) in &self.buttons {
+            if released.contains(button) {
+                state.voice.note_off(pitch(degree));
+            }
+        }
+        for &(button, degree) in &self.buttons {
+            if pressed.contains(button) {
+                state.voice.note_on(pitch(degree), self.amplitude);
+            }
+        }
+
+        let lean = stick.1.clamp(-1.0, 1.0);
+        let (octaves, bend) = match self.stick {
+            StickMode::Octave if lean >= OCTAVE_THRESHOLD => (1, 0.0),
+            StickMode::Octave if lean <= -OCTAVE_THRESHOLD => (-1, 0.0),
+            StickMode::Octave => (0, 0.0),
+            StickMode::Bend if lean.abs() < STICK_DEAD_ZONE => (0, 0.0),
+            StickMode::Bend => (0, lean * self.bend_range),
+        };
+        let current = state.voice.current().map(|(pitch, velocity)| {
+            let note = (i32::from(pitch) + octaves * 12).clamp(0, 127) as u8;
+            (note, bend, velocity)
+        });
+
+        let unchanged = match (state.sounding, current) {
+            (None, None) => true,
+            (Some((note, sent_bend)), Some((new_note, bend, _))) => {
+                note == new_note && (bend - sent_bend).abs() < AUDIBLE_BEND
+            }
+            _ => false,
+        };
+        if unchanged {
+            return None;
+        }
+        state.sounding = current.map(|(note, bend, _)| (note, bend));
+
+        let (note, frequency, amplitude) = match current {
+            Some((note, bend, velocity)) => (
+                Some(note),
+                note_to_frequency(i32::from(note)) * (bend / 12.0).exp2(),
+                velocity,
+            ),
+            None => (None, 0.0, 0.0),
+        };
+        Some(InstrumentOutput {
+            joycon: self.output_for(player),
+            note,
+            frequency,
+            amplitude,
+        })
+    }
+}
+
+/// A performance recorded as the notes each player played.
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    /// Per player, every change of note with its time, oldest first.
+    changes: Vec<Vec<(Duration, Option<u8>)>>,
+}
+
+impl Recording {
+    /// Creates an empty recording for `num_players` players, at most
+    /// [`MAX_RECORDED_PLAYERS`].
+    pub fn new(num_players: usize) -> Self {
+        Self {
+            changes: vec![Vec::new(); num_players.min(MAX_RECORDED_PLAYERS)],
+        }
+    }
+
+    /// Records that `player` started playing `note`, or stopped for
+    /// `None`, at `time`. A note still playing at the end of the recording
+    /// ends with the player's last change. Players the recording has no
+    /// room for are ignored.
+    pub fn record(&mut self, player: usize, note: Option<u8>, time: Duration) {
+        if let Some(changes) = self.changes.get_mut(player) {
+            changes.push((time, note));
+        }
+    }
+
+    /// Returns `true` if no player played anything.
+    pub fn is_empty(&self) -> bool {
+        self.changes
+            .iter()
+            .flatten()
+            .all(|(_, note)| note.is_none())
+    }
+
+    /// The recording as a MIDI file: a tempo track at 120 BPM, then one
+    /// track per player on its own channel, skipping the drum channel.
+    pub fn to_smf(&self) -> Smf<'static> {
+        let end_of_track = TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        };
+        let mut tracks = vec![vec![
+            TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(RECORDING_TEMPO))),
+            },
+            end_of_track,
+        ]];
+        for (player, changes) in self.changes.iter().enumerate() {
+            let channel = u4::new((player + usize::from(player >= DRUM_CHANNEL)) as u8);
+            let message = |message| TrackEventKind::Midi { channel, message };
+            let note_off = |key| {
+                message(MidiMessage::NoteOff {
+                    key: u7::new(key),
+                    vel: u7::new(0),
+                })
+            };
+            let mut timed = Vec::new();
+            let mut sounding: Option<u8> = None;
+            for &(time, note) in changes {
+                if let Some(key) = sounding.take() {
+                    timed.push((ticks(time), note_off(key)));
+                }
+                if let Some(key) = note {
+                    let vel = u7::new(100);
+                    let on = message(MidiMessage::NoteOn {
+                        key: u7::new(key),
+                        vel,
+                    });
+                    timed.push((ticks(time), on));
+                    sounding = Some(key);
+                }
+            }
+            if let (Some(key), Some(&(tick, _))) = (sounding, timed.last()) {
+                timed.push((tick, note_off(key)));
+            }
+            let mut last_tick = 0;
+            let mut events: Vec<TrackEvent> = timed
+                .into_iter()
+                .map(|(tick, kind)| {
+                    let tick = tick.max(last_tick);
+                    let delta = u28::new(tick - last_tick);
+                    last_tick = tick;
+                    TrackEvent { delta, kind }
+                })
+                .collect();
+            events.push(end_of_track);
+            tracks.push(events);
+        }
+        Smf {
+            header: Header::new(Format::Parallel, Timing::Metrical(u15::new(TICKS_PER_BEAT))),
+            tracks,
+        }
+    }
+
+    /// Writes the recording to a MIDI file.
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error if the file cannot be written.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        self.to_smf().save(path)
+    }
+}
+
+/// The tick of `time` in a recording.
+fn ticks(time: Duration) -> u32 {
+    let ticks_per_second = f64::from(TICKS_PER_BEAT) * 1_000_000.0 / f64::from(RECORDING_TEMPO);
+    (time.as_secs_f64() * ticks_per_second).round() as u32
+}
+
+/// The stick `output`'s player plays with, calibrated if the controller's
+/// flash holds a calibration: the stick on the motor's side of a Pro
+/// Controller, otherwise the Joy-Con's own stick.
+fn stick_of<S: RumbleSink>(
+    joycon: &JoyCon<S>,
+    output: OutputTarget,
+    state: &InputState,
+) -> (f32, f32) {
+    let identity = joycon.identity();
+    let right = match output.motor {
+        Some(side) => side == JoyConSide::Right,
+        None => joycon.get_type() == JoyConType::Right,
+    };
+    let (position, calibration) = if right {
+        (
+            state.right_stick,
+            identity.and_then(|identity| identity.right_stick),
+        )
+    } else {
+        (
+            state.left_stick,
+            identity.and_then(|identity| identity.left_stick),
+        )
+    };
+    match calibration {
+        Some(calibration) => calibration.normalize(position),
+        None => position.normalized(),
+    }
+}
+
+/// Plays connected JoyCons as an instrument until **Q** is pressed on the
+/// keyboard or **Home** or **Capture** on a controller.
+///
+/// # Errors
+///
+/// Fails if no JoyCons can be connected, a controller stops responding or
+/// the recording cannot be saved.
+pub fn play_instrument(
+    options: &InstrumentOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let manager = JoyConManager::new()?;
+    play_instrument_with(&manager, options)
+}
+
+/// Plays the JoyCons discovered by `manager` as an instrument.
+///
+/// See [`play_instrument`].
+pub fn play_instrument_with<B: DeviceBackend>(
+    manager: &JoyConManager<B>,
+    options: &InstrumentOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut joycons = manager.connect_and_initialize_at_most(options.max_joycons)?;
+    let outputs = OutputTarget::all(&joycons);
+    let mut instrument = Instrument::new(options, outputs.len());
+    let mut recording = options
+        .record
+        .as_ref()
+        .map(|_| Recording::new(outputs.len()));
+    if recording.is_some() && outputs.len() > MAX_RECORDED_PLAYERS {
+        log::warn!(
+            "⚠️  Only the first {MAX_RECORDED_PLAYERS} of {} players will be recorded",
+            outputs.len()
+        );
+    }
+
+    log::info!(
+        "🎹 Playing {:?} from note {} for {} player(s)",
+        options.scale,
+        options.root,
+        outputs.len()
+    );
+    log::info!("    Home/Capture or Q = stop");
+
+    let clock = PlaybackClock::new();
+    let result = run_interactive(&mut joycons, &clock, |joycons, step| {
+        let Step::Input {
+            joycon,
+            state,
+            time,
+        } = step
+        else {
+            return Ok(false);
+        };
+        for (player, &target) in outputs.iter().enumerate() {
+            if target.joycon != joycon {
+                continue;
+            }
+            let stick = stick_of(&joycons[joycon], target, state);
+            let buttons = target.buttons(state.buttons);
+            let Some(output) = instrument.update(player, buttons, stick) else {
+                continue;
+            };
+            if output.amplitude > 0.0 {
+                options.events.emit(PlaybackEvent::NoteStarted {
+                    joycon: output.joycon,
+                    frequency: output.frequency,
+                    amplitude: output.amplitude,
+                    time,
+                });
+            }
+            if let Some(recording) = &mut recording {
+                recording.record(player, output.note, time);
+            }
+            outputs[output.joycon].rumble(joycons, output.frequency, output.amplitude)?;
+        }
+        Ok(false)
+    });
+
+    // Keep the performance even if a controller is already gone.
+    let saved = match (&options.record, &mut recording) {
+        (Some(path), Some(recording)) if !recording.is_empty() => {
+            let end = clock.position();
+            for player in 0..outputs.len() {
+                recording.record(player, None, end);
+            }
+            recording.save(path).map(|()| {
+                log::info!("⏺️  Recorded performance to {}", path.display());
+            })
+        }
+        (Some(_), Some(_)) => {
+            log::info!("⏺️  Nothing was played; no recording saved");
+            Ok(())
+        }
+        _ => Ok(()),
+    };
+    result?;
+    saved?;
+    log::info!("⏹  Instrument mode stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    use crate::joycon::{SimulatedDevice, StickPosition};
+    use crate::midi::parse_midi_to_rumble;
+    use crate::midi::test_support::{after_initialization, simulated_manager};
+
+    fn held(buttons: &[Button]) -> Buttons {
+        buttons.iter().copied().collect()
+    }
+
+    #[test]
+    fn scales_wrap_into_octaves() {
+        assert_eq!(Scale::Major.note(60, 7), 72);
+        assert_eq!(Scale::Major.note(60, -1), 59);
+        assert_eq!(Scale::MinorPentatonic.note(57, 5), 69);
+        assert_eq!(Scale::Blues.note(60, 3), 66);
+        assert_eq!(Scale::Chromatic.note(60, 13), 73);
+    }
+
+    #[test]
+    fn buttons_and_stick_pick_the_note() {
+        let mut instrument = Instrument::new(&InstrumentOptions::default(), 1);
+        let note = |output: Option<InstrumentOutput>| output.unwrap().note;
+
+        assert_eq!(
+            note(instrument.update(0, held(&[Button::A]), (0.0, 0.0))),
+            Some(64)
+        );
+        // Holding the same buttons changes nothing.
+        assert_eq!(instrument.update(0, held(&[Button::A]), (0.0, 0.1)), None);
+        // The newest press wins, and releasing it falls back.
+        let both = held(&[Button::A, Button::Zr]);
+        assert_eq!(note(instrument.update(0, both, (0.0, 0.0))), Some(69));
+        assert_eq!(
+            note(instrument.update(0, held(&[Button::A]), (0.0, 0.0))),
+            Some(64)
+        );
+        // Stick up: an octave higher.
+        let up = instrument
+            .update(0, held(&[Button::A]), (0.0, 0.9))
+            .unwrap();
+        assert_eq!(up.note, Some(76));
+        assert_eq!(up.frequency, note_to_frequency(76));
+        assert_eq!(
+            note(instrument.update(0, Buttons::default(), (0.0, 0.0))),
+            None
+        );
+    }
+
+    #[test]
+    fn stick_bends_and_partners_swap_motors() {
+        let options = InstrumentOptions {
+            stick: StickMode::Bend,
+            partner: true,
+            ..InstrumentOptions::default()
+        };
+        let mut instrument = Instrument::new(&options, 3);
+        assert_eq!(instrument.output_for(0), 1);
+        assert_eq!(instrument.output_for(1), 0);
+        assert_eq!(instrument.output_for(2), 2);
+
+        let a4 = held(&[Button::Right]);
+        let plain = instrument.update(1, a4, (0.0, 0.0)).unwrap();
+        assert_eq!(plain.joycon, 0);
+        assert_eq!(plain.note, Some(64));
+        // Full stick up: a whole tone higher, still recorded as E4.
+        let bent = instrument.update(1, a4, (0.0, 1.0)).unwrap();
+        assert_eq!(bent.note, Some(64));
+        assert!((bent.frequency / plain.frequency - 2f32.powf(2.0 / 12.0)).abs() < 1e-4);
+        // Within the dead zone the note plays in tune.
+        let back = instrument.update(1, a4, (0.0, 0.1)).unwrap();
+        assert_eq!(back.frequency, plain.frequency);
+    }
+
+    #[test]
+    fn recording_plays_back_as_midi() {
+        let mut recording = Recording::new(2);
+        assert!(recording.is_empty());
+        recording.record(0, Some(60), Duration::from_millis(0));
+        recording.record(0, Some(64), Duration::from_millis(500));
+        recording.record(0, None, Duration::from_millis(1000));
+        recording.record(1, Some(48), Duration::from_millis(250));
+        recording.record(1, None, Duration::from_millis(750));
+        assert!(!recording.is_empty());
+
+        let mut midi = Vec::new();
+        recording.to_smf().write(&mut midi).unwrap();
+        let (tracks, _, _) = parse_midi_to_rumble(&midi, 2).unwrap();
+        let frequencies: Vec<f32> = tracks
+            .iter()
+            .flat_map(|track| &track.commands)
+            .filter(|command| command.amplitude > 0.0)
+            .map(|command| command.frequency)
+            .collect();
+        for note in [60, 64, 48] {
+            assert!(
+                frequencies.contains(&note_to_frequency(note)),
+                "note {note}"
+            );
+        }
+        let longest = tracks.iter().map(|t| t.total_duration).max().unwrap();
+        assert!((longest.as_secs_f32() - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn every_recorded_player_has_its_own_channel() {
+        let mut recording = Recording::new(MAX_RECORDED_PLAYERS + 1);
+        for player in 0..=MAX_RECORDED_PLAYERS {
+            recording.record(player, Some(60), Duration::ZERO);
+        }
+        let smf = recording.to_smf();
+        assert_eq!(smf.tracks.len(), MAX_RECORDED_PLAYERS + 1);
+
+        let channels: Vec<u8> = smf.tracks[1..]
+            .iter()
+            .filter_map(|track| {
+                track.iter().find_map(|event| match event.kind {
+                    TrackEventKind::Midi { channel, .. } => Some(channel.as_int()),
+                    _ => None,
+                })
+            })
+            .collect();
+        assert_eq!(
+            channels,
+            [0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 11, 12, 13, 14, 15]
+        );
+    }
+
+    #[test]
+    fn simulated_controller_plays_and_records() {
+        let path = std::env::temp_dir().join(format!("mj-instrument-{}.mid", std::process::id()));
+        let device = SimulatedDevice::new();
+        let manager = simulated_manager(JoyConType::Right, &device);
+        // Once the controller is set up: hold X for a moment, let go, then
+        // stop with Home.
+        let performer = after_initialization(&device, JoyConType::Right, |device| {
+            for buttons in [&[Button::X][..], &[], &[Button::Home]] {
+                let state = InputState {
+                    buttons: held(buttons),
+                    right_stick: StickPosition::default(),
+                    ..InputState::default()
+                };
+                device.push_input(state.to_report());
+                thread::sleep(Duration::from_millis(100));
+            }
+        });
+        let (events, rx) = EventSender::channel();
+        let options = InstrumentOptions {
+            record: Some(path.clone()),
+            events,
+            ..InstrumentOptions::default()
+        };
+        let result = play_instrument_with(&manager, &options);
+        performer.join().unwrap();
+        let midi = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+
+        let notes: Vec<PlaybackEvent> = rx.try_iter().collect();
+        assert!(matches!(
+            notes.as_slice(),
+            [PlaybackEvent::NoteStarted { joycon: 0, frequency, .. }]
+                if *frequency == note_to_frequency(65)
+        ));
+        let (tracks, _, _) = parse_midi_to_rumble(&midi.unwrap(), 1).unwrap();
+        assert!(tracks
+            .iter()
+            .flat_map(|track| &track.commands)
+            .any(|command| command.frequency == note_to_frequency(65)));
+    }
+
+    #[test]
+    fn silent_performance_is_not_saved() {
+        let path = std::env::temp_dir().join(format!("mj-silent-{}.mid", std::process::id()));
+        let device = SimulatedDevice::new();
+        let manager = simulated_manager(JoyConType::Left, &device);
+        let performer = after_initialization(&device, JoyConType::Left, |device| {
+            let state = InputState {
+                buttons: held(&[Button::Capture]),
+                ..InputState::default()
+            };
+            device.push_input(state.to_report());
+        });
+        let options = InstrumentOptions {
+            record: Some(path.clone()),
+            ..InstrumentOptions::default()
+        };
+        let result = play_instrument_with(&manager, &options);
+        performer.join().unwrap();
+        result.unwrap();
+        assert!(!path.exists());
+    }
+}
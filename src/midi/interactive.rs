@@ -0,0 +1,104 @@
+//! The input loop shared by the modes played on the controllers' buttons.
+//!
+//! Instrument mode and the rhythm game both read every controller's input
+//! reports until the player stops, answer them with rumble and silence the
+//! controllers at the end. [`run_interactive`] does that for them: it puts
+//! the terminal in raw mode so **Q** or **Esc** can stop without **Enter**,
+//! stops on **Home** or **Capture** on any controller, and times each
+//! report on a [`PlaybackClock`] as soon as it is read.
+
+use std::thread;
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+
+use crate::joycon::{Button, InputState, JoyCon, JoyConError, RumbleSink};
+
+use super::clock::PlaybackClock;
+
+/// How often the controllers and keyboard are read. A report is timed when
+/// it is read, so at most this long after it arrived.
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Buttons that stop an interactive mode.
+const QUIT_BUTTONS: [Button; 2] = [Button::Home, Button::Capture];
+
+/// What [`run_interactive`] hands the mode.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Step<'s> {
+    /// Controller `joycon` sent `state`, read at `time` on the clock.
+    Input {
+        joycon: usize,
+        state: &'s InputState,
+        time: Duration,
+    },
+    /// Every poll, after the reports that arrived, at `time` on the clock.
+    Tick { time: Duration },
+}
+
+/// Starts `clock` and feeds every controller's input reports, and a tick
+/// per poll, to `step` until it returns `true`, a controller's **Home** or
+/// **Capture** is pressed or **Q** or **Esc** on the keyboard. Every
+/// controller is silenced at the end, even after an error.
+///
+/// # Errors
+///
+/// The first error of `step`, of reading a controller or of silencing one.
+pub(crate) fn run_interactive<S: RumbleSink>(
+    joycons: &mut [JoyCon<S>],
+    clock: &PlaybackClock,
+    mut step: impl FnMut(&mut [JoyCon<S>], Step) -> Result<bool, JoyConError>,
+) -> Result<(), JoyConError> {
+    let raw_ok = crossterm::terminal::enable_raw_mode().is_ok();
+    clock.resume();
+    let result = (|| -> Result<(), JoyConError> {
+        loop {
+            for joycon in 0..joycons.len() {
+                while let Some(state) = joycons[joycon].read_input(Duration::ZERO)? {
+                    let time = clock.position();
+                    if QUIT_BUTTONS.iter().any(|&b| state.buttons.contains(b)) {
+                        return Ok(());
+                    }
+                    let input = Step::Input {
+                        joycon,
+                        state: &state,
+                        time,
+                    };
+                    if step(joycons, input)? {
+                        return Ok(());
+                    }
+                }
+            }
+            let tick = Step::Tick {
+                time: clock.position(),
+            };
+            if step(joycons, tick)? || quit_pressed() {
+                return Ok(());
+            }
+            thread::sleep(INPUT_POLL_INTERVAL);
+        }
+    })();
+    clock.pause();
+    if raw_ok {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+
+    let silenced = joycons
+        .iter_mut()
+        .map(|joycon| joycon.rumble(0.0, 0.0))
+        .fold(Ok(()), Result::and);
+    result.and(silenced)
+}
+
+/// Returns `true` if **Q** or **Esc** was pressed on the keyboard.
+pub(crate) fn quit_pressed() -> bool {
+    event::poll(Duration::ZERO).unwrap_or(false)
+        && matches!(
+            event::read(),
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc,
+                kind: KeyEventKind::Press,
+                ..
+            }))
+        )
+}
@@ -0,0 +1,617 @@
+//! Live MIDI input driving Joy-Cons in real time.
+//!
+//! Instead of a file, notes come from a keyboard, a DAW or any other MIDI
+//! source as they are played. Each note-on/off is routed by channel to a
+//! Joy-Con, passed through that Joy-Con's [`MonoVoice`] and sent with the
+//! same note-to-frequency mapping as file playback.
+//!
+//! # Sources
+//!
+//! | [`LiveSource`] | Reads from |
+//! |----------------|------------|
+//! | `Port(name)` | A system MIDI input port (ALSA sequencer, CoreMIDI, WinMM) whose name contains `name` |
+//! | `Virtual(name)` | A new virtual port other programs can connect to (Linux and macOS) |
+//! | `Raw(path)` | A raw MIDI byte stream: a `/dev/snd/midiC*D*` device, a FIFO, or a file |
+//!
+//! # Routing
+//!
+//! [`ChannelRouting`] maps each of the 16 MIDI channels to at most one
+//! output. Outputs are numbered like
+//! [`count_outputs`](super::playback::count_outputs): one per Joy-Con and
+//! two per Pro Controller, right motor first. By default channels are
+//! spread round-robin over the outputs, so a single keyboard on channel 1
+//! plays the first one.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use musical_joycons::midi::live::{play_live, ChannelRouting, LiveOptions, LiveSource};
+//! use musical_joycons::midi::voice::VoicePolicy;
+//!
+//! let options = LiveOptions {
+//!     routing: Some(ChannelRouting::none().route(0, 0).route(1, 1)),
+//!     policy: VoicePolicy::Highest,
+//!     ..LiveOptions::default()
+//! };
+//! play_live(&LiveSource::Port("keystation".into()), &options)?;
+//! # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+//! ```
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use midir::{MidiInput, MidiInputConnection};
+use thiserror::Error;
+
+use crate::joycon::{DeviceBackend, JoyConManager};
+
+use super::events::{EventSender, PlaybackEvent};
+use super::interactive::quit_pressed;
+use super::playback::OutputTarget;
+use super::rumble::note_to_frequency;
+use super::voice::{MonoVoice, VoicePolicy};
+
+/// Client name shown to other MIDI software.
+const CLIENT_NAME: &str = "Musical JoyCons";
+
+/// How often the event loop checks the keyboard when no MIDI arrives.
+const KEY_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Number of MIDI channels.
+const MIDI_CHANNELS: usize = 16;
+
+/// Errors that can occur while opening a live MIDI source.
+#[derive(Debug, Error)]
+pub enum LiveError {
+    /// The platform MIDI API reported an error.
+    #[error("MIDI error: {0}")]
+    Midi(String),
+
+    /// No MIDI input port matched the requested name.
+    #[error("No MIDI input port matching {0:?}")]
+    PortNotFound(String),
+
+    /// No MIDI input ports exist at all.
+    #[error("No MIDI input ports available")]
+    NoPorts,
+
+    /// Virtual ports are not supported on this platform.
+    #[error("Virtual MIDI ports are not supported on this platform")]
+    VirtualUnsupported,
+
+    /// A raw MIDI device or file could not be opened.
+    #[error("Failed to open raw MIDI source: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Where live MIDI events come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiveSource {
+    /// The first system input port whose name contains this text
+    /// (case-insensitive), or the port with this index. An empty string
+    /// picks the first port.
+    Port(String),
+    /// A virtual input port with this name.
+    Virtual(String),
+    /// A raw MIDI byte stream.
+    Raw(PathBuf),
+}
+
+/// Maps MIDI channels to output indices.
+///
+/// Channels are 0-based here (`0` is what musicians call channel 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelRouting {
+    routes: [Option<usize>; MIDI_CHANNELS],
+}
+
+impl ChannelRouting {
+    /// A routing that ignores every channel.
+    pub fn none() -> Self {
+        Self {
+            routes: [None; MIDI_CHANNELS],
+        }
+    }
+
+    /// Spreads channels over `num_outputs` outputs: channel `c` plays on
+    /// output `c % num_outputs`.
+    pub fn round_robin(num_outputs: usize) -> Self {
+        let mut routing = Self::none();
+        if num_outputs > 0 {
+            for (channel, route) in routing.routes.iter_mut().enumerate() {
+                *route = Some(channel % num_outputs);
+            }
+        }
+        routing
+    }
+
+    /// Routes `channel` to `output`. Channels above 15 are ignored.
+    pub fn route(mut self, channel: u8, output: usize) -> Self {
+        if let Some(route) = self.routes.get_mut(channel as usize) {
+            *route = Some(output);
+        }
+        self
+    }
+
+    /// The output that plays `channel`, if any.
+    pub fn joycon_for(&self, channel: u8) -> Option<usize> {
+        self.routes.get(channel as usize).copied().flatten()
+    }
+
+    /// Routes that point past the last of `num_outputs` outputs, as
+    /// `(channel, output)` pairs.
+    fn out_of_range(&self, num_outputs: usize) -> impl Iterator<Item = (u8, usize)> + '_ {
+        (0..MIDI_CHANNELS as u8).filter_map(move |channel| {
+            self.joycon_for(channel)
+                .filter(|&output| output >= num_outputs)
+                .map(|output| (channel, output))
+        })
+    }
+}
+
+/// Options for [`play_live`].
+#[derive(Debug, Clone, Default)]
+pub struct LiveOptions {
+    /// Channel routing; round-robin over the outputs when `None`.
+    pub routing: Option<ChannelRouting>,
+    /// Which held note each output plays. Live input never advances an
+    /// arpeggio, so [`VoicePolicy::Arpeggiate`] holds the lowest note.
+    pub policy: VoicePolicy,
+    /// Semitones added to every incoming note.
+    pub transpose: i32,
+    /// Play on at most this many controllers; all connected ones when `None`.
+    pub max_joycons: Option<usize>,
+    /// Receives a [`PlaybackEvent::NoteStarted`] for every note played,
+    /// timed from when listening started.
+    pub events: EventSender,
+}
+
+/// A rumble state change produced by [`LiveRouter::handle_message`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiveOutput {
+    /// Index of the output to update.
+    pub joycon: usize,
+    /// Frequency in Hz, `0.0` for silence.
+    pub frequency: f32,
+    /// Amplitude in `0.0..=1.0`, `0.0` for silence.
+    pub amplitude: f32,
+}
+
+/// Turns MIDI messages into per-output rumble states.
+///
+/// This is the device-independent core of live mode: it owns one
+/// [`MonoVoice`] per output and reports a [`LiveOutput`] whenever the note
+/// an output should play changes.
+#[derive(Debug, Clone)]
+pub struct LiveRouter {
+    routing: ChannelRouting,
+    voices: Vec<MonoVoice>,
+    sounding: Vec<Option<(u8, f32)>>,
+    transpose: i32,
+}
+
+impl LiveRouter {
+    /// Creates a router for `num_outputs` outputs.
+    pub fn new(
+        num_outputs: usize,
+        routing: ChannelRouting,
+        policy: VoicePolicy,
+        transpose: i32,
+    ) -> Self {
+        Self {
+            routing,
+            voices: vec![MonoVoice::new(policy); num_outputs],
+            sounding: vec![None; num_outputs],
+            transpose,
+        }
+    }
+
+    /// Applies one complete MIDI message.
+    ///
+    /// Handles note-on, note-off (including note-on with velocity 0) and the
+    /// All Sound Off / All Notes Off controllers; everything else is
+    /// ignored. Returns the new state of the affected output if it changed.
+    pub fn handle_message(&mut self, message: &[u8]) -> Option<LiveOutput> {
+        let (&status, data) = message.split_first()?;
+        let channel = status & 0x0F;
+        let joycon = self.routing.joycon_for(channel)?;
+        let voice = self.voices.get_mut(joycon)?;
+
+        match (status & 0xF0, data) {
+            (0x90, &[pitch, velocity, ..]) if velocity > 0 => {
+                voice.note_on(pitch, velocity as f32 / 127.0)
+            }
+            (0x80, &[pitch, ..]) | (0x90, &[pitch, ..]) => voice.note_off(pitch),
+            (0xB0, &[120 | 123, ..]) => voice.clear(),
+            _ => return None,
+        }
+
+        let current = voice.current();
+        if current == self.sounding[joycon] {
+            return None;
+        }
+        self.sounding[joycon] = current;
+
+        let (frequency, amplitude) = match current {
+            Some((pitch, velocity)) => (note_to_frequency(pitch as i32 + self.transpose), velocity),
+            None => (0.0, 0.0),
+        };
+        Some(LiveOutput {
+            joycon,
+            frequency,
+            amplitude,
+        })
+    }
+}
+
+/// Splits a raw MIDI byte stream into complete channel messages.
+///
+/// Supports running status; system exclusive, system common and real-time
+/// bytes are skipped.
+#[derive(Debug, Default)]
+struct RawMidiParser {
+    status: Option<u8>,
+    data: Vec<u8>,
+}
+
+impl RawMidiParser {
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        match byte {
+            // Real-time bytes may appear anywhere without affecting state.
+            0xF8..=0xFF => None,
+            // SysEx and system common messages cancel running status; their
+            // data bytes are dropped below.
+            0xF0..=0xF7 => {
+                self.status = None;
+                self.data.clear();
+                None
+            }
+            0x80..=0xEF => {
+                self.status = Some(byte);
+                self.data.clear();
+                None
+            }
+            _ => {
+                let status = self.status?;
+                self.data.push(byte);
+                let needed = match status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    _ => 2,
+                };
+                if self.data.len() < needed {
+                    return None;
+                }
+                let mut message = vec![status];
+                message.append(&mut self.data);
+                Some(message)
+            }
+        }
+    }
+}
+
+/// Lists the names of the system MIDI input ports.
+///
+/// # Errors
+///
+/// Returns [`LiveError::Midi`] if the platform MIDI API is unavailable.
+pub fn list_midi_ports() -> Result<Vec<String>, LiveError> {
+    let input = MidiInput::new(CLIENT_NAME).map_err(|e| LiveError::Midi(e.to_string()))?;
+    Ok(input
+        .ports()
+        .iter()
+        .map(|port| input.port_name(port).unwrap_or_else(|_| "<unknown>".into()))
+        .collect())
+}
+
+/// An open live source; dropping it closes the port.
+struct OpenSource {
+    /// System port connection; `None` for raw sources, whose reader thread
+    /// ends with the stream.
+    _connection: Option<MidiInputConnection<()>>,
+}
+
+fn open_source(
+    source: &LiveSource,
+    tx: Sender<Vec<u8>>,
+) -> Result<(OpenSource, String), LiveError> {
+    let midi_error = |e: &dyn std::fmt::Display| LiveError::Midi(e.to_string());
+
+    match source {
+        LiveSource::Port(wanted) => {
+            let input = MidiInput::new(CLIENT_NAME).map_err(|e| midi_error(&e))?;
+            let ports = input.ports();
+            if ports.is_empty() {
+                return Err(LiveError::NoPorts);
+            }
+            let wanted_lower = wanted.to_lowercase();
+            let port = ports
+                .iter()
+                .enumerate()
+                .find(|(idx, port)| {
+                    wanted.is_empty()
+                        || wanted.parse::<usize>() == Ok(*idx)
+                        || input
+                            .port_name(port)
+                            .is_ok_and(|name| name.to_lowercase().contains(&wanted_lower))
+                })
+                .map(|(_, port)| port.clone())
+                .ok_or_else(|| LiveError::PortNotFound(wanted.clone()))?;
+            let name = input.port_name(&port).map_err(|e| midi_error(&e))?;
+            let connection = input
+                .connect(
+                    &port,
+                    "input",
+                    move |_, message, _| {
+                        let _ = tx.send(message.to_vec());
+                    },
+                    (),
+                )
+                .map_err(|e| midi_error(&e))?;
+            Ok((
+                OpenSource {
+                    _connection: Some(connection),
+                },
+                name,
+            ))
+        }
+        #[cfg(unix)]
+        LiveSource::Virtual(name) => {
+            use midir::os::unix::VirtualInput;
+
+            let input = MidiInput::new(CLIENT_NAME).map_err(|e| midi_error(&e))?;
+            let connection = input
+                .create_virtual(
+                    name,
+                    move |_, message, _| {
+                        let _ = tx.send(message.to_vec());
+                    },
+                    (),
+                )
+                .map_err(|e| midi_error(&e))?;
+            Ok((
+                OpenSource {
+                    _connection: Some(connection),
+                },
+                format!("virtual port {name:?}"),
+            ))
+        }
+        #[cfg(not(unix))]
+        LiveSource::Virtual(_) => Err(LiveError::VirtualUnsupported),
+        LiveSource::Raw(path) => {
+            let mut file = File::open(path)?;
+            thread::spawn(move || {
+                let mut parser = RawMidiParser::default();
+                let mut buf = [0u8; 256];
+                while let Ok(n) = file.read(&mut buf) {
+                    if n == 0 {
+                        break;
+                    }
+                    for message in buf[..n].iter().filter_map(|&b| parser.push(b)) {
+                        if tx.send(message).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+            Ok((
+                OpenSource { _connection: None },
+                format!("{}", path.display()),
+            ))
+        }
+    }
+}
+
+/// Plays live MIDI input through connected JoyCons until **Q** or **Esc**
+/// is pressed.
+///
+/// # Errors
+///
+/// Fails if no JoyCons can be connected or the source cannot be opened
+/// (see [`LiveError`]).
+pub fn play_live(
+    source: &LiveSource,
+    options: &LiveOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let manager = JoyConManager::new()?;
+    play_live_with(source, &manager, options)
+}
+
+/// Plays live MIDI input on the JoyCons discovered by `manager`.
+///
+/// See [`play_live`].
+pub fn play_live_with<B: DeviceBackend>(
+    source: &LiveSource,
+    manager: &JoyConManager<B>,
+    options: &LiveOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut joycons = manager.connect_and_initialize_at_most(options.max_joycons)?;
+    let outputs = OutputTarget::all(&joycons);
+    let routing = options
+        .routing
+        .clone()
+        .unwrap_or_else(|| ChannelRouting::round_robin(outputs.len()));
+    for (channel, output) in routing.out_of_range(outputs.len()) {
+        log::warn!(
+            "⚠️  Channel {} is routed to output {}, but only {} are connected; it will be silent",
+            channel + 1,
+            output + 1,
+            outputs.len()
+        );
+    }
+    let mut router = LiveRouter::new(outputs.len(), routing, options.policy, options.transpose);
+
+    let (tx, rx) = mpsc::channel();
+    let (_source, name) = open_source(source, tx)?;
+    log::info!("🎹 Listening on {name} with {} JoyCon(s)", joycons.len());
+    log::info!("    Q = quit");
+
+    let started = Instant::now();
+    let raw_ok = crossterm::terminal::enable_raw_mode().is_ok();
+    let result = run_event_loop(
+        &rx,
+        |output| {
+            if output.amplitude > 0.0 {
+                options.events.emit(PlaybackEvent::NoteStarted {
+                    joycon: output.joycon,
+                    frequency: output.frequency,
+                    amplitude: output.amplitude,
+                    time: started.elapsed(),
+                });
+            }
+            outputs[output.joycon].rumble(&mut joycons, output.frequency, output.amplitude)
+        },
+        &mut router,
+    );
+    if raw_ok {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+
+    for joycon in &mut joycons {
+        joycon.rumble(0.0, 0.0)?;
+    }
+    result?;
+    log::info!("⏹  Live mode stopped");
+    Ok(())
+}
+
+/// Feeds messages from `rx` through `router` until a quit key is pressed or
+/// the source closes.
+fn run_event_loop<E>(
+    rx: &Receiver<Vec<u8>>,
+    mut send: impl FnMut(LiveOutput) -> Result<(), E>,
+    router: &mut LiveRouter,
+) -> Result<(), E> {
+    loop {
+        match rx.recv_timeout(KEY_POLL_INTERVAL) {
+            Ok(message) => {
+                if let Some(output) = router.handle_message(&message) {
+                    send(output)?;
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        if quit_pressed() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joycon::{JoyConType, SimulatedDevice};
+    use crate::midi::test_support::{initialization_reports, simulated_manager};
+
+    #[test]
+    fn out_of_range_routes_are_reported() {
+        let routing = ChannelRouting::none().route(0, 0).route(3, 2).route(9, 1);
+        assert_eq!(routing.out_of_range(2).collect::<Vec<_>>(), vec![(3, 2)]);
+        assert_eq!(ChannelRouting::round_robin(3).out_of_range(3).count(), 0);
+    }
+
+    #[test]
+    fn router_follows_voice_policy_and_routing() {
+        let routing = ChannelRouting::none().route(0, 0).route(9, 1);
+        let mut router = LiveRouter::new(2, routing, VoicePolicy::Last, 0);
+
+        let on = router.handle_message(&[0x90, 69, 127]).unwrap();
+        assert_eq!(on.joycon, 0);
+        assert!((on.frequency - note_to_frequency(69)).abs() < 0.01);
+        assert!((on.amplitude - 1.0).abs() < f32::EPSILON);
+
+        // A second note takes over under last-note priority; releasing it
+        // falls back to the first.
+        let second = router.handle_message(&[0x90, 72, 64]).unwrap();
+        assert!((second.frequency - note_to_frequency(72)).abs() < 0.01);
+        let back = router.handle_message(&[0x80, 72, 0]).unwrap();
+        assert!((back.frequency - note_to_frequency(69)).abs() < 0.01);
+
+        // Channel 10 goes to the second Joy-Con; unrouted channels are ignored.
+        assert_eq!(router.handle_message(&[0x99, 40, 100]).unwrap().joycon, 1);
+        assert!(router.handle_message(&[0x93, 60, 100]).is_none());
+
+        // Note-on with velocity 0 is a note-off.
+        let off = router.handle_message(&[0x90, 69, 0]).unwrap();
+        assert_eq!(off.amplitude, 0.0);
+    }
+
+    #[test]
+    fn raw_parser_handles_running_status_and_realtime() {
+        let mut parser = RawMidiParser::default();
+        let stream = [
+            0x90, 60, 100, 0xF8, 62, 100, 0xF0, 1, 2, 0xF7, 5, 0x80, 60, 0,
+        ];
+        let messages: Vec<Vec<u8>> = stream.iter().filter_map(|&b| parser.push(b)).collect();
+        assert_eq!(
+            messages,
+            vec![vec![0x90, 60, 100], vec![0x90, 62, 100], vec![0x80, 60, 0]]
+        );
+    }
+
+    #[test]
+    fn raw_source_drives_simulated_joycon() {
+        let path = std::env::temp_dir().join(format!("mj-live-{}.mid", std::process::id()));
+        std::fs::write(&path, [0x90, 69, 127, 0x80, 69, 0]).unwrap();
+
+        let device = SimulatedDevice::new();
+        let manager = simulated_manager(JoyConType::Right, &device);
+        let (events, rx) = EventSender::channel();
+        let options = LiveOptions {
+            events,
+            ..LiveOptions::default()
+        };
+        let result = play_live_with(&LiveSource::Raw(path.clone()), &manager, &options);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        let notes: Vec<PlaybackEvent> = rx.try_iter().collect();
+        assert!(matches!(
+            notes.as_slice(),
+            [PlaybackEvent::NoteStarted { joycon: 0, frequency, .. }]
+                if *frequency == note_to_frequency(69)
+        ));
+
+        // Rumble enable + chime, then note on, note off and the final stop.
+        let before = initialization_reports(JoyConType::Right);
+        assert_eq!(device.reports().len(), before + 3);
+    }
+
+    #[test]
+    fn pro_controller_motors_are_separate_outputs() {
+        let path = std::env::temp_dir().join(format!("mj-live-pro-{}.mid", std::process::id()));
+        std::fs::write(&path, [0x90, 69, 127, 0x91, 60, 127]).unwrap();
+
+        let pro = SimulatedDevice::new();
+        let manager = simulated_manager(JoyConType::ProController, &pro);
+        let (events, rx) = EventSender::channel();
+        let options = LiveOptions {
+            events,
+            ..LiveOptions::default()
+        };
+        let result = play_live_with(&LiveSource::Raw(path.clone()), &manager, &options);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        // Round-robin over the two motors: channel 1 on the right, channel 2
+        // on the left, sounding together.
+        let notes: Vec<usize> = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                PlaybackEvent::NoteStarted { joycon, .. } => Some(joycon),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(notes, [0, 1]);
+        let reports = pro.reports();
+        let both = &reports[reports.len() - 2].data;
+        assert_ne!(both[2..6], both[6..10]);
+        assert_ne!(both[2..6], reports.last().unwrap().data[2..6]);
+    }
+}
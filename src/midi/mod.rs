@@ -61,6 +61,49 @@
 //! 3. Switches JoyCons to more active tracks during silent periods
 //! 4. Maintains synchronization across all devices
 //!
+//! # Events and Logging
+//!
+//! Nothing in this module prints to the terminal. Diagnostics and the
+//! analysis summary are emitted through the [`log`] crate, and playback
+//! reports notes, section changes, binding swaps and device errors as typed
+//! [`PlaybackEvent`]s on the [`EventSender`] in [`PlaybackOptions`] (see
+//! [`events`]).
+//!
+//! # Playlists
+//!
+//! [`play_playlist`] plays several files, a directory or an `.m3u` list (see
+//! [`Playlist`]) on a single connection, with shuffle, repeat and
+//! next/previous keys.
+//!
+//! # Live Input
+//!
+//! The [`live`] module plays a MIDI keyboard or DAW through the Joy-Cons in
+//! real time, using the same frequency mapping and [`voice`] allocation as
+//! file playback. It needs the default `live` feature.
+//!
+//! # Instrument Mode
+//!
+//! The [`instrument`] module turns the Joy-Cons themselves into an
+//! instrument: buttons play the notes of a scale, the stick shifts octaves
+//! or bends, and the performance can be recorded to a MIDI file.
+//!
+//! # Rhythm Game
+//!
+//! The [`rhythm`] module charts the primary part's notes as targets to hit
+//! with the Joy-Con buttons, thinned by difficulty, and scores each
+//! player's hits, misses and timing.
+//!
+//! # Machine-Readable Analysis
+//!
+//! [`analyze_midi`] returns the part analysis, selection and playback plan
+//! as a serializable [`AnalysisReport`] for tooling.
+//!
+//! # Offline Rendering
+//!
+//! The [`render`] module synthesizes what each Joy-Con would play into a
+//! stereo WAV file (left/right channel per Joy-Con side), so part selection
+//! can be auditioned without controllers.
+//!
 //! # Tempo and Timing
 //!
 //! The module correctly handles:
@@ -68,18 +111,53 @@
 //! - Different time signatures
 //! - Variable tick resolutions
 //! - Proper note-on/note-off pairing
+//!
+//! The [`PlaybackPlan`] keeps the beat times of the tempo map, which the
+//! [`conductor`] module uses to let a controller set the tempo live.
 
+mod clock;
+pub mod conductor;
+pub mod events;
+pub mod expression;
+pub mod instrument;
+mod interactive;
+#[cfg(feature = "live")]
+pub mod live;
 pub mod parts;
 mod playback;
+pub mod playlist;
+pub mod remote;
+pub mod render;
+pub mod report;
+pub mod rhythm;
 pub mod rumble;
 pub mod scoring;
+#[cfg(test)]
+mod test_support;
 pub mod track_analysis;
 pub mod track_types;
+pub mod voice;
 
 // Re-export public types
+pub use clock::PlaybackClock;
+pub use events::{EventSender, PlaybackEvent};
+pub use instrument::{play_instrument, play_instrument_with, InstrumentOptions};
+#[cfg(feature = "live")]
+pub use live::{play_live, play_live_with, ChannelRouting, LiveOptions, LiveSource};
 pub use parts::{NoteObject, Part, PartKey};
-pub use playback::{play_midi_file, JoyConBinding, JoyConSide};
-pub use rumble::{parse_midi_to_rumble, ParseError, RumbleCommand, RumbleTrack, TrackSwitchPoint};
+pub use playback::{
+    count_outputs, play_midi_file, play_midi_file_with, play_playlist, play_playlist_with,
+    play_rumble_tracks, JoyConBinding, JoyConSide, PlaybackControls, PlaybackOptions,
+};
+pub use playlist::{Playlist, PlaylistError, RepeatMode};
+pub use remote::{ButtonMap, PlaybackAction};
+pub use report::{analyze_midi, AnalysisReport};
+pub use rhythm::{play_rhythm_game, play_rhythm_game_with, RhythmOptions};
+pub use rumble::{
+    parse_midi_to_rumble, parse_midi_to_rumble_with, ConversionOptions, ParseError, RumbleCommand,
+    RumbleTrack, TrackSwitchPoint, Verbosity,
+};
 pub use scoring::PartSelection;
 pub use track_analysis::{analyze_part, analyze_track, PartFeatures};
 pub use track_types::{PlaybackPlan, SectionAssignment, TrackMetrics, TrackType};
+pub use voice::{MonoVoice, VoicePolicy};
@@ -7,9 +7,10 @@
 use std::collections::HashMap;
 
 use midly::{Smf, TrackEventKind};
+use serde::Serialize;
 
 /// Identifies a musical part by its MIDI channel and GM program number.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct PartKey {
     pub channel: u8,
     pub program: u8,
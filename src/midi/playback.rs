@@ -2,7 +2,8 @@
 //!
 //! The playback system uses a pre-computed [`PlaybackPlan`] combined with a
 //! [`JoyConBinding`] that maps the primary and secondary parts to Left/Right
-//! Joy-Cons. The binding can be changed at runtime via keyboard controls.
+//! Joy-Cons. The binding can be changed at runtime via keyboard controls or
+//! the Joy-Con buttons.
 //!
 //! # Runtime Controls
 //!
@@ -13,20 +14,63 @@
 //! | `S` | Swap L/R assignment |
 //! | `1` | Cycle to next primary candidate |
 //! | `2` | Cycle to next secondary candidate |
+//! | `Space` | Pause / resume |
+//! | `←` / `→` | Seek back / forward 5 seconds |
+//! | `[` / `]` | Jump to the previous / next section |
+//! | `+` / `-` | Volume up / down |
+//! | `N` | Skip to the next song |
+//! | `P` | Go back to the previous song |
 //! | `Q` | Quit playback |
+//!
+//! The Joy-Cons' own buttons trigger the same actions through the
+//! [`ButtonMap`] in [`PlaybackOptions::buttons`]; see the
+//! [`remote`](super::remote) module for the default layout.
+//!
+//! # Expression
+//!
+//! With [`PlaybackOptions::expression`] set, tilting a controller bends the
+//! pitch of its notes and shaking it accents them; see the
+//! [`expression`](super::expression) module.
+//!
+//! # Conducting
+//!
+//! With [`PlaybackOptions::conductor`] set, one controller's swings set the
+//! tempo: every Joy-Con follows the shared clock, which speeds up or slows
+//! down to keep the song's beats on the conductor's; see the
+//! [`conductor`](super::conductor) module.
+//!
+//! # Playlists
+//!
+//! [`play_playlist`] plays a [`Playlist`] on one connection: the JoyCons are
+//! initialized once and reused, so songs follow each other without a
+//! reconnect or initialization chime. Shuffle and [`RepeatMode`] are set
+//! through [`PlaybackOptions`].
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::thread;
 use std::time::{Duration, Instant};
 
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
 
-use crate::joycon::{JoyConManager, JoyConType};
-
-use super::rumble::{parse_midi_to_rumble, RumbleCommand};
+use crate::joycon::{
+    BatteryLevel, Buttons, DeviceBackend, HomeLight, InputState, JoyCon, JoyConError,
+    JoyConManager, JoyConType, Motor, PlayerLights, Reconnect, RumbleSink,
+};
+
+use super::clock::PlaybackClock;
+use super::conductor::{Conductor, ConductorOptions};
+use super::events::{EventSender, PartRole, PlaybackEvent};
+use super::expression::{Expression, ExpressionOptions};
+use super::playlist::{clock_seed, Playlist, RepeatMode};
+use super::remote::{ButtonMap, PlaybackAction, Volume, VOLUME_STEP};
+use super::rumble::{
+    analyze_song, log_song_analysis, ConversionOptions, RumbleCommand, RumbleTrack, SongAnalysis,
+};
 use super::scoring::PartSelection;
+use super::track_types::PlaybackPlan;
 
 /// Maps the primary and secondary parts to physical Joy-Con sides.
 ///
@@ -67,48 +111,377 @@ impl JoyConBinding {
         }
     }
 
-    pub fn swap(&mut self) {
+    /// 1-based position of the given side's part in its candidate list.
+    pub fn candidate_for_side(&self, side: JoyConSide) -> usize {
+        match (side, self.primary_on_right) {
+            (JoyConSide::Right, true) | (JoyConSide::Left, false) => self.primary_candidate_pos + 1,
+            (JoyConSide::Left, true) | (JoyConSide::Right, false) => {
+                self.secondary_candidate_pos + 1
+            }
+        }
+    }
+
+    /// Moves the primary part to the other Joy-Con side.
+    pub fn swap(&mut self) -> PlaybackEvent {
         self.primary_on_right = !self.primary_on_right;
-        let side_str = if self.primary_on_right {
-            "Right"
-        } else {
-            "Left"
-        };
-        println!("🔄 Swapped — primary now on {side_str} Joy-Con");
+        PlaybackEvent::BindingSwapped {
+            primary_on_right: self.primary_on_right,
+        }
     }
 
-    pub fn cycle_primary(&mut self) {
+    /// Moves the primary part to the next candidate. Returns `None` if there
+    /// is no other candidate.
+    pub fn cycle_primary(&mut self) -> Option<PlaybackEvent> {
         if self.primary_candidates.len() <= 1 {
-            return;
+            return None;
         }
         self.primary_candidate_pos =
             (self.primary_candidate_pos + 1) % self.primary_candidates.len();
         self.primary_part_idx = self.primary_candidates[self.primary_candidate_pos];
-        println!(
-            "🔁 Primary → part {} (candidate {}/{})",
-            self.primary_part_idx,
-            self.primary_candidate_pos + 1,
-            self.primary_candidates.len()
-        );
+        Some(PlaybackEvent::PartCycled {
+            role: PartRole::Primary,
+            part: self.primary_part_idx,
+            candidate: self.primary_candidate_pos + 1,
+            candidates: self.primary_candidates.len(),
+        })
     }
 
-    pub fn cycle_secondary(&mut self) {
+    /// Moves the secondary part to the next candidate. Returns `None` if
+    /// there is no other candidate.
+    pub fn cycle_secondary(&mut self) -> Option<PlaybackEvent> {
         if self.secondary_candidates.len() <= 1 {
-            return;
+            return None;
         }
         self.secondary_candidate_pos =
             (self.secondary_candidate_pos + 1) % self.secondary_candidates.len();
         self.secondary_part_idx = self.secondary_candidates[self.secondary_candidate_pos];
-        println!(
-            "🔁 Secondary → part {} (candidate {}/{})",
-            self.secondary_part_idx,
-            self.secondary_candidate_pos + 1,
-            self.secondary_candidates.len()
-        );
+        Some(PlaybackEvent::PartCycled {
+            role: PartRole::Secondary,
+            part: self.secondary_part_idx,
+            candidate: self.secondary_candidate_pos + 1,
+            candidates: self.secondary_candidates.len(),
+        })
+    }
+}
+
+/// Options for [`play_midi_file_with`].
+#[derive(Debug, Clone)]
+pub struct PlaybackOptions {
+    /// Conversion settings. `num_joycons` is replaced by the number of
+    /// controllers actually used.
+    pub conversion: ConversionOptions,
+    /// Play on at most this many controllers; all connected ones when `None`.
+    pub max_joycons: Option<usize>,
+    /// Play playlist entries in random order.
+    pub shuffle: bool,
+    /// What to do at the end of a song.
+    pub repeat: RepeatMode,
+    /// Receives [`PlaybackEvent`]s for the whole session.
+    pub events: EventSender,
+    /// Joy-Con buttons that control playback, e.g. [`ButtonMap::default`];
+    /// [`ButtonMap::empty`] (the default) leaves the buttons unread.
+    pub buttons: ButtonMap,
+    /// Show each controller's part candidate on its player lights and pulse
+    /// the Home LED at section changes. Off by default.
+    pub lights: bool,
+    /// Warn when a controller's battery is at or below this level; `None`
+    /// (the default) never checks.
+    pub low_battery: Option<BatteryLevel>,
+    /// Keep playing when a controller drops out and bring it back once it
+    /// reconnects, instead of failing the song. Off by default.
+    pub reconnect: bool,
+    /// Let tilting and shaking the controllers bend and accent their notes;
+    /// `None` (the default) plays the notes as written.
+    pub expression: Option<ExpressionOptions>,
+    /// Let one controller's swings set the tempo; `None` (the default)
+    /// plays at the written tempo.
+    pub conductor: Option<ConductorOptions>,
+}
+
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        Self {
+            conversion: ConversionOptions::default(),
+            max_joycons: None,
+            shuffle: false,
+            repeat: RepeatMode::default(),
+            events: EventSender::default(),
+            buttons: ButtonMap::empty(),
+            lights: false,
+            low_battery: None,
+            reconnect: false,
+            expression: None,
+            conductor: None,
+        }
+    }
+}
+
+/// Shared state that steers a running [`play_rumble_tracks`].
+///
+/// Every Joy-Con thread reads the binding, clock, quit flag and volume;
+/// changing them from another thread changes playback on all Joy-Cons.
+#[derive(Debug, Clone)]
+pub struct PlaybackControls {
+    /// Which part each Joy-Con side plays.
+    pub binding: Arc<Mutex<JoyConBinding>>,
+    /// Song position shared by every Joy-Con.
+    pub clock: Arc<PlaybackClock>,
+    /// Stops playback early when set.
+    pub quit: Arc<AtomicBool>,
+    /// Master volume applied to every note.
+    pub volume: Arc<Volume>,
+    /// Maps newly pressed Joy-Con buttons to actions.
+    pub buttons: ButtonMap,
+    /// Receives the actions triggered by Joy-Con buttons. The buttons are
+    /// not read when this is `None`.
+    pub actions: Option<Sender<PlaybackAction>>,
+    /// Show the candidate each Joy-Con plays on its player lights (see
+    /// [`JoyConBinding::candidate_for_side`]) and pulse its Home LED when
+    /// the plan changes its part. Both are turned off when playback ends.
+    pub lights: bool,
+    /// Emit [`PlaybackEvent::BatteryLow`] when a battery drops to or below
+    /// this level; `None` never checks.
+    pub low_battery: Option<BatteryLevel>,
+    /// Shape each note with the motion of the controller playing it. The
+    /// IMU of every controller is enabled when playback starts.
+    pub expression: Option<ExpressionOptions>,
+    /// Follow the swings of the conducting controller, whose IMU is
+    /// enabled when playback starts, by changing the clock's rate.
+    pub conductor: Option<ConductorOptions>,
+    /// Receives every note sent, plan-driven part change and device error.
+    pub events: EventSender,
+}
+
+impl Default for PlaybackControls {
+    fn default() -> Self {
+        Self::new(&PartSelection {
+            primary: 0,
+            secondary: 0,
+            primary_candidates: Vec::new(),
+            secondary_candidates: Vec::new(),
+        })
+    }
+}
+
+impl PlaybackControls {
+    /// Creates controls for a song with the given part selection: the clock
+    /// is paused at zero, the volume full and the buttons unread.
+    pub fn new(selection: &PartSelection) -> Self {
+        Self {
+            binding: Arc::new(Mutex::new(JoyConBinding::new(selection))),
+            clock: Arc::new(PlaybackClock::new()),
+            quit: Arc::new(AtomicBool::new(false)),
+            volume: Arc::new(Volume::default()),
+            buttons: ButtonMap::default(),
+            actions: None,
+            lights: false,
+            low_battery: None,
+            expression: None,
+            conductor: None,
+            events: EventSender::default(),
+        }
+    }
+
+    /// Returns `true` if playback needs the Joy-Cons' input reports.
+    fn reads_input(&self) -> bool {
+        self.actions.is_some()
+            || self.low_battery.is_some()
+            || self.expression.is_some()
+            || self.conductor.is_some()
+    }
+}
+
+/// A song change requested from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SongSkip {
+    Next,
+    Previous,
+}
+
+/// How often Joy-Con buttons and the keyboard are checked during playback.
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How often a lost Joy-Con is looked for during playback.
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Songs at least this long get a warning before they start on a low
+/// battery.
+const LONG_SONG: Duration = Duration::from_secs(180);
+
+/// How far the arrow keys seek, in seconds.
+const SEEK_STEP_SECS: f32 = 5.0;
+
+/// Pressing "previous section" within this long of a section start goes to
+/// the section before it instead of restarting the current one.
+const SECTION_RESTART_GRACE: Duration = Duration::from_secs(2);
+
+/// Flags shared between the keyboard thread and the playlist driver.
+#[derive(Debug, Default)]
+struct Transport {
+    /// Controls handed to [`play_rumble_tracks`] for every song. The clock
+    /// is reset and `quit` (which stops the current song) cleared between
+    /// songs.
+    controls: PlaybackControls,
+    /// Plan of the current song, for section jumps.
+    plan: Mutex<Option<PlaybackPlan>>,
+    /// Ends the whole session, including the keyboard thread.
+    quit: AtomicBool,
+    /// Where to go once the current song has stopped.
+    skip: Mutex<Option<SongSkip>>,
+}
+
+impl Transport {
+    fn request_skip(&self, skip: SongSkip) {
+        *self.skip.lock().unwrap_or_else(|e| e.into_inner()) = Some(skip);
+        self.controls.quit.store(true, Ordering::Relaxed);
+    }
+
+    fn take_skip(&self) -> Option<SongSkip> {
+        self.skip.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    /// Seeks to the start of the next section, if there is one.
+    fn next_section(&self) -> Option<Duration> {
+        let plan = self.plan.lock().unwrap_or_else(|e| e.into_inner());
+        let clock = &self.controls.clock;
+        let target = plan.as_ref()?.next_section_time(clock.position())?;
+        clock.seek_to(target);
+        Some(target)
+    }
+
+    /// Seeks to the start of the current section, or the previous one when
+    /// the current section has only just started.
+    fn previous_section(&self) -> Duration {
+        let clock = &self.controls.clock;
+        let position = clock.position();
+        let plan = self.plan.lock().unwrap_or_else(|e| e.into_inner());
+        let target = plan
+            .as_ref()
+            .map(|plan| previous_section_target(plan, position))
+            .unwrap_or(Duration::ZERO);
+        clock.seek_to(target);
+        target
+    }
+
+    /// Carries out a keyboard or Joy-Con button action.
+    fn apply(&self, action: PlaybackAction, events: &EventSender) {
+        let controls = &self.controls;
+        match action {
+            PlaybackAction::Swap => {
+                if let Ok(mut b) = controls.binding.lock() {
+                    events.emit(b.swap());
+                }
+            }
+            PlaybackAction::CyclePrimary => {
+                if let Some(event) = controls
+                    .binding
+                    .lock()
+                    .ok()
+                    .and_then(|mut b| b.cycle_primary())
+                {
+                    events.emit(event);
+                }
+            }
+            PlaybackAction::CycleSecondary => {
+                if let Some(event) = controls
+                    .binding
+                    .lock()
+                    .ok()
+                    .and_then(|mut b| b.cycle_secondary())
+                {
+                    events.emit(event);
+                }
+            }
+            PlaybackAction::TogglePause => {
+                let paused = controls.clock.toggle_pause();
+                let position = controls.clock.position();
+                events.emit(if paused {
+                    PlaybackEvent::Paused { position }
+                } else {
+                    PlaybackEvent::Resumed { position }
+                });
+            }
+            PlaybackAction::SeekBack | PlaybackAction::SeekForward => {
+                let step = if action == PlaybackAction::SeekBack {
+                    -SEEK_STEP_SECS
+                } else {
+                    SEEK_STEP_SECS
+                };
+                controls.clock.seek_by(step);
+                events.emit(PlaybackEvent::Seeked {
+                    position: controls.clock.position(),
+                });
+            }
+            PlaybackAction::PreviousSection => {
+                let position = self.previous_section();
+                events.emit(PlaybackEvent::Seeked { position });
+            }
+            PlaybackAction::NextSection => {
+                if let Some(position) = self.next_section() {
+                    events.emit(PlaybackEvent::Seeked { position });
+                }
+            }
+            PlaybackAction::VolumeUp | PlaybackAction::VolumeDown => {
+                let step = if action == PlaybackAction::VolumeUp {
+                    VOLUME_STEP
+                } else {
+                    -VOLUME_STEP
+                };
+                let volume = controls.volume.adjust(step);
+                events.emit(PlaybackEvent::VolumeChanged { volume });
+            }
+            PlaybackAction::NextSong => {
+                log::info!("⏭  Next song…");
+                self.request_skip(SongSkip::Next);
+            }
+            PlaybackAction::PreviousSong => {
+                log::info!("⏮  Previous song…");
+                self.request_skip(SongSkip::Previous);
+            }
+            PlaybackAction::Quit => {
+                log::info!("⏹  Quitting playback…");
+                self.quit.store(true, Ordering::Relaxed);
+                controls.quit.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// The action behind a keyboard shortcut.
+fn key_action(code: KeyCode) -> Option<PlaybackAction> {
+    Some(match code {
+        KeyCode::Char('s') | KeyCode::Char('S') => PlaybackAction::Swap,
+        KeyCode::Char('1') => PlaybackAction::CyclePrimary,
+        KeyCode::Char('2') => PlaybackAction::CycleSecondary,
+        KeyCode::Char(' ') => PlaybackAction::TogglePause,
+        KeyCode::Left => PlaybackAction::SeekBack,
+        KeyCode::Right => PlaybackAction::SeekForward,
+        KeyCode::Char('[') => PlaybackAction::PreviousSection,
+        KeyCode::Char(']') => PlaybackAction::NextSection,
+        KeyCode::Char('+') | KeyCode::Char('=') => PlaybackAction::VolumeUp,
+        KeyCode::Char('-') => PlaybackAction::VolumeDown,
+        KeyCode::Char('n') | KeyCode::Char('N') => PlaybackAction::NextSong,
+        KeyCode::Char('p') | KeyCode::Char('P') => PlaybackAction::PreviousSong,
+        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => PlaybackAction::Quit,
+        _ => return None,
+    })
+}
+
+/// Where "previous section" goes from `position`: the start of the current
+/// section, or of the one before it within [`SECTION_RESTART_GRACE`].
+fn previous_section_target(plan: &PlaybackPlan, position: Duration) -> Duration {
+    let current = plan.section_start_at(position);
+    if position - current < SECTION_RESTART_GRACE && !current.is_zero() {
+        plan.section_start_at(current - Duration::from_nanos(1))
+    } else {
+        current
     }
 }
 
 /// Logical side of a Joy-Con for binding purposes.
+///
+/// A Pro Controller plays as both sides at once: its right motor is the
+/// `Right` side and its left motor the `Left` side.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum JoyConSide {
     Left,
@@ -126,6 +499,250 @@ impl JoyConSide {
     }
 }
 
+impl From<JoyConSide> for Motor {
+    /// The motor that plays a side on a controller with two.
+    fn from(side: JoyConSide) -> Self {
+        match side {
+            JoyConSide::Left => Motor::Left,
+            JoyConSide::Right => Motor::Right,
+        }
+    }
+}
+
+/// Number of parts the JoyCons play at once: one per Joy-Con and two per
+/// Pro Controller, whose motors play separately.
+///
+/// [`play_rumble_tracks`] expects a [`PlaybackPlan`] built for this many
+/// Joy-Cons.
+pub fn count_outputs<S: RumbleSink>(joycons: &[JoyCon<S>]) -> usize {
+    joycons
+        .iter()
+        .map(|joycon| if joycon.has_two_motors() { 2 } else { 1 })
+        .sum()
+}
+
+/// One output of [`count_outputs`]: a whole Joy-Con, or one motor of a Pro
+/// Controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OutputTarget {
+    /// Index of the controller.
+    pub(crate) joycon: usize,
+    /// The motor played, for a controller with two.
+    pub(crate) motor: Option<JoyConSide>,
+}
+
+impl OutputTarget {
+    /// The outputs of `joycons` in [`count_outputs`] order: controller by
+    /// controller, a Pro Controller's right motor before its left.
+    pub(crate) fn all<S: RumbleSink>(joycons: &[JoyCon<S>]) -> Vec<OutputTarget> {
+        joycons
+            .iter()
+            .enumerate()
+            .flat_map(|(joycon, device)| {
+                Self::motors(device.has_two_motors())
+                    .into_iter()
+                    .map(move |motor| OutputTarget { joycon, motor })
+            })
+            .collect()
+    }
+
+    fn motors(two_motors: bool) -> Vec<Option<JoyConSide>> {
+        if two_motors {
+            vec![Some(JoyConSide::Right), Some(JoyConSide::Left)]
+        } else {
+            vec![None]
+        }
+    }
+
+    /// Plays `frequency` at `amplitude` on this output alone.
+    pub(crate) fn rumble<S: RumbleSink>(
+        self,
+        joycons: &mut [JoyCon<S>],
+        frequency: f32,
+        amplitude: f32,
+    ) -> Result<(), JoyConError> {
+        let joycon = &mut joycons[self.joycon];
+        match self.motor {
+            Some(side) => joycon.rumble_motor(
+                side.into(),
+                &RumbleCommand {
+                    frequency,
+                    amplitude,
+                    ..SILENCE
+                },
+            ),
+            None => joycon.rumble(frequency, amplitude),
+        }
+    }
+
+    /// The buttons of `buttons` that belong to this output's player: all of
+    /// them, or the half of a Pro Controller on the motor's side.
+    pub(crate) fn buttons(self, buttons: Buttons) -> Buttons {
+        match self.motor {
+            Some(side) => buttons.on_side(side.into()),
+            None => buttons,
+        }
+    }
+}
+
+/// Rumble that silences a motor.
+const SILENCE: RumbleCommand = RumbleCommand {
+    frequency: 0.0,
+    amplitude: 0.0,
+    wait_before: Duration::ZERO,
+    low_band: None,
+};
+
+/// Smallest pitch change, in semitones, worth re-sending a sustained note
+/// for.
+const AUDIBLE_BEND: f32 = 0.05;
+
+/// Smallest amplitude change worth re-sending a sustained note for.
+const AUDIBLE_GAIN: f32 = 0.01;
+
+/// A controller shared by the playback threads of its outputs.
+struct SharedJoyCon<'j, S: RumbleSink> {
+    joycon: &'j mut JoyCon<S>,
+    /// Bumped by every reconnection, so the second output of a lost Pro
+    /// Controller resumes instead of re-opening it again.
+    connection: u64,
+    /// What the controller's motion does to its notes, if expression is on.
+    expression: Option<Expression>,
+    /// Follows the controller's swings, if it conducts.
+    conductor: Option<Conductor>,
+    /// The lowest battery level reported since the battery was last above
+    /// the `low_battery` threshold.
+    battery_reported: Option<BatteryLevel>,
+}
+
+/// The note an output is sounding, as written and as sent.
+struct Sounding {
+    note: RumbleCommand,
+    sent: RumbleCommand,
+}
+
+/// What one playback thread plays on: a whole Joy-Con, or one motor of a
+/// Pro Controller.
+struct Output<'a, 'j, S: RumbleSink> {
+    shared: &'a Mutex<SharedJoyCon<'j, S>>,
+    /// Which controller `shared` is.
+    controller: usize,
+    /// The motor played on, for a controller with two.
+    motor: Option<JoyConSide>,
+    sounding: Mutex<Sounding>,
+}
+
+/// Returns `true` if `b` sounds noticeably different from `a`.
+fn audibly_different(a: &RumbleCommand, b: &RumbleCommand) -> bool {
+    let bend = |a: f32, b: f32| a > 0.0 && b > 0.0 && (b / a).log2().abs() * 12.0 >= AUDIBLE_BEND;
+    let gain = |a: f32, b: f32| (a - b).abs() >= AUDIBLE_GAIN;
+    let (a_low, b_low) = (
+        a.low_band.unwrap_or_default(),
+        b.low_band.unwrap_or_default(),
+    );
+    bend(a.frequency, b.frequency)
+        || gain(a.amplitude, b.amplitude)
+        || bend(a_low.0, b_low.0)
+        || gain(a_low.1, b_low.1)
+}
+
+impl<'j, S: RumbleSink> Output<'_, 'j, S> {
+    fn lock(&self) -> MutexGuard<'_, SharedJoyCon<'j, S>> {
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn side(&self) -> JoyConSide {
+        self.motor
+            .unwrap_or_else(|| JoyConSide::from_joycon_type(self.lock().joycon.get_type()))
+    }
+
+    /// Whether this output reads the buttons and shows the lights. A Pro
+    /// Controller's right motor does, for the whole controller.
+    fn owns_controller(&self) -> bool {
+        self.motor != Some(JoyConSide::Left)
+    }
+
+    fn connection(&self) -> u64 {
+        self.lock().connection
+    }
+
+    /// Sends `command`, shaped by the controller's expression.
+    fn play(&self, command: &RumbleCommand) -> Result<(), JoyConError> {
+        let mut shared = self.lock();
+        let sent = match &shared.expression {
+            Some(expression) => expression.apply(command),
+            None => command.clone(),
+        };
+        self.send(shared.joycon, &sent)?;
+        *self.sounding.lock().unwrap_or_else(|e| e.into_inner()) = Sounding {
+            note: command.clone(),
+            sent,
+        };
+        Ok(())
+    }
+
+    /// Re-sends the sounding note if the expression has audibly changed it
+    /// since it was sent. Best-effort, like input: a failing device shows
+    /// up on the next note.
+    fn refresh(&self) {
+        let mut sounding = self.sounding.lock().unwrap_or_else(|e| e.into_inner());
+        if sounding.note.amplitude <= 0.0 {
+            return;
+        }
+        let mut shared = self.lock();
+        let Some(expression) = &shared.expression else {
+            return;
+        };
+        let sent = expression.apply(&sounding.note);
+        if audibly_different(&sounding.sent, &sent) && self.send(shared.joycon, &sent).is_ok() {
+            sounding.sent = sent;
+        }
+    }
+
+    fn send(&self, joycon: &mut JoyCon<S>, command: &RumbleCommand) -> Result<(), JoyConError> {
+        match self.motor {
+            Some(side) => joycon.rumble_motor(side.into(), command),
+            None => joycon.rumble_command(command),
+        }
+    }
+
+    fn silence(&self) -> Result<(), JoyConError> {
+        self.play(&SILENCE)
+    }
+
+    /// [`poll_controller`] for the output that owns the controller, which
+    /// also feeds its motion to the expression and the conductor.
+    fn poll(&self, joycon_idx: usize, controls: &PlaybackControls, events: &EventSender) {
+        if self.owns_controller() {
+            let mut shared = self.lock();
+            let SharedJoyCon {
+                joycon,
+                expression,
+                conductor,
+                battery_reported,
+                ..
+            } = &mut *shared;
+            poll_controller(joycon, joycon_idx, battery_reported, controls, events);
+            if expression.is_none() && conductor.is_none() {
+                return;
+            }
+            let samples = joycon.take_imu_samples();
+            if let Some(expression) = expression {
+                expression.update(&samples);
+            }
+            let position = controls.clock.position();
+            if let Some(tempo) = conductor
+                .as_mut()
+                .and_then(|conductor| conductor.update(&samples, position))
+            {
+                log::debug!("🪄 Conducted tempo {:.0}%", tempo * 100.0);
+                controls.clock.set_rate(tempo);
+                events.emit(PlaybackEvent::TempoChanged { tempo });
+            }
+        }
+    }
+}
+
 fn find_commands_at_time(commands: &[RumbleCommand], target_time: Duration) -> usize {
     let mut accumulated_time = Duration::ZERO;
     for (idx, cmd) in commands.iter().enumerate() {
@@ -137,6 +754,27 @@ fn find_commands_at_time(commands: &[RumbleCommand], target_time: Duration) -> u
     commands.len()
 }
 
+/// Returns the index of the first command not yet fired at `target_time`
+/// and the track time at which the command before it fired.
+///
+/// Adding the returned command's `wait_before` to the returned time gives
+/// its fire time, which is what the playback loop's `scheduled_time` needs.
+fn position_in_track(commands: &[RumbleCommand], target_time: Duration) -> (usize, Duration) {
+    let index = find_commands_at_time(commands, target_time);
+    let elapsed = commands[..index].iter().map(|c| c.wait_before).sum();
+    (index, elapsed)
+}
+
+/// Returns the track a Joy-Con should play at `time`: the bound track in the
+/// opening section, the plan's assignment after that.
+fn track_at(plan: &PlaybackPlan, joycon_idx: usize, bound_track: usize, time: Duration) -> usize {
+    if plan.section_start_at(time).is_zero() {
+        bound_track
+    } else {
+        plan.track_for(joycon_idx, time)
+    }
+}
+
 fn is_note_off(cmd: &RumbleCommand, prev_cmd: Option<&RumbleCommand>) -> bool {
     match prev_cmd {
         Some(prev) => prev.amplitude > 0.0 && cmd.amplitude == 0.0,
@@ -144,44 +782,33 @@ fn is_note_off(cmd: &RumbleCommand, prev_cmd: Option<&RumbleCommand>) -> bool {
     }
 }
 
-/// Spawns a thread that reads keyboard events and mutates the binding.
+/// Spawns a thread that reads keyboard events and carries out the actions
+/// of both the keyboard and the Joy-Con buttons (received on `actions`).
+///
+/// The thread runs until `transport.quit` is set, across every song of a
+/// playlist.
 fn spawn_input_thread(
-    binding: Arc<Mutex<JoyConBinding>>,
-    quit: Arc<AtomicBool>,
+    transport: Arc<Transport>,
+    events: EventSender,
+    actions: Receiver<PlaybackAction>,
 ) -> thread::JoinHandle<()> {
     thread::spawn(move || {
         // Enable raw mode so key-presses arrive immediately.
         let raw_ok = crossterm::terminal::enable_raw_mode().is_ok();
 
-        while !quit.load(Ordering::Relaxed) {
-            if event::poll(Duration::from_millis(50)).unwrap_or(false) {
+        while !transport.quit.load(Ordering::Relaxed) {
+            for action in actions.try_iter() {
+                transport.apply(action, &events);
+            }
+            if event::poll(INPUT_POLL_INTERVAL).unwrap_or(false) {
                 if let Ok(Event::Key(KeyEvent {
                     code,
                     kind: KeyEventKind::Press,
                     ..
                 })) = event::read()
                 {
-                    match code {
-                        KeyCode::Char('s') | KeyCode::Char('S') => {
-                            if let Ok(mut b) = binding.lock() {
-                                b.swap();
-                            }
-                        }
-                        KeyCode::Char('1') => {
-                            if let Ok(mut b) = binding.lock() {
-                                b.cycle_primary();
-                            }
-                        }
-                        KeyCode::Char('2') => {
-                            if let Ok(mut b) = binding.lock() {
-                                b.cycle_secondary();
-                            }
-                        }
-                        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => {
-                            println!("\n⏹  Quitting playback…");
-                            quit.store(true, Ordering::Relaxed);
-                        }
-                        _ => {}
+                    if let Some(action) = key_action(code) {
+                        transport.apply(action, &events);
                     }
                 }
             }
@@ -193,6 +820,193 @@ fn spawn_input_thread(
     })
 }
 
+/// Reads a Joy-Con's latest input report: sends the actions of newly
+/// pressed buttons and reports a battery at or below the `low_battery`
+/// threshold, once per level it drops to. Does nothing unless `controls`
+/// needs input.
+fn poll_controller<S: RumbleSink>(
+    joycon: &mut JoyCon<S>,
+    joycon_idx: usize,
+    battery_reported: &mut Option<BatteryLevel>,
+    controls: &PlaybackControls,
+    events: &EventSender,
+) {
+    if !controls.reads_input() {
+        return;
+    }
+    let previous = joycon.input_state();
+    // Input is best-effort: a failing device shows up on the next rumble.
+    let Ok(Some(state)) = joycon.poll_input() else {
+        return;
+    };
+    send_pressed_actions(previous, &state, controls);
+    if let Some(threshold) = controls.low_battery {
+        if state.battery > threshold {
+            *battery_reported = None;
+        } else if battery_reported.is_none_or(|reported| state.battery < reported) {
+            log::warn!("🪫 JoyCon {} battery is {}", joycon_idx + 1, state.battery);
+            events.emit(PlaybackEvent::BatteryLow {
+                joycon: joycon_idx,
+                level: state.battery,
+            });
+            *battery_reported = Some(state.battery);
+        }
+    }
+}
+
+/// Sends the actions of the buttons pressed in `state` but not in
+/// `previous`, if `controls` has an action channel.
+fn send_pressed_actions(
+    previous: Option<InputState>,
+    state: &InputState,
+    controls: &PlaybackControls,
+) {
+    if let Some(actions) = &controls.actions {
+        let pressed = state
+            .buttons
+            .pressed_since(previous.map(|p| p.buttons).unwrap_or_default());
+        for action in controls.buttons.actions(pressed) {
+            let _ = actions.send(action);
+        }
+    }
+}
+
+/// [`PlaybackClock::wait_until`] that keeps polling the Joy-Con's input
+/// while it waits and, with expression on, keeps the sounding note
+/// following the controller's motion.
+fn wait_until_polling<S: RumbleSink>(
+    output: &Output<'_, '_, S>,
+    joycon_idx: usize,
+    controls: &PlaybackControls,
+    events: &EventSender,
+    target: Duration,
+    generation: u64,
+) -> bool {
+    let polls = controls.reads_input() && output.owns_controller();
+    if !polls && controls.expression.is_none() {
+        return controls
+            .clock
+            .wait_until(target, generation, &controls.quit);
+    }
+    loop {
+        let slice_end = (controls.clock.position() + INPUT_POLL_INTERVAL).min(target);
+        let reached = controls
+            .clock
+            .wait_until(slice_end, generation, &controls.quit);
+        output.poll(joycon_idx, controls, events);
+        output.refresh();
+        if !reached || slice_end >= target {
+            return reached;
+        }
+    }
+}
+
+/// Pulses the Home LED to mark a section change, if `controls` shows
+/// lights. Lights are best-effort, like input.
+fn pulse_home_light<S: RumbleSink>(output: &Output<'_, '_, S>, controls: &PlaybackControls) {
+    if controls.lights && output.owns_controller() {
+        let _ = output.lock().joycon.set_home_light(&HomeLight::pulse());
+    }
+}
+
+/// Tries to re-open a Joy-Con lost on `connection` every
+/// [`RECONNECT_INTERVAL`], unless another output of it already did. The
+/// other controllers' `paths` are left alone, and the new path is recorded.
+/// Returns `false` if playback stopped or the song ended first.
+fn await_reconnect<S: RumbleSink>(
+    output: &Output<'_, '_, S>,
+    connection: u64,
+    reconnect: &dyn Reconnect<S>,
+    paths: &Mutex<Vec<String>>,
+    controls: &PlaybackControls,
+    song_end: Duration,
+) -> bool {
+    loop {
+        if controls.quit.load(Ordering::Relaxed) || controls.clock.position() >= song_end {
+            return false;
+        }
+        {
+            let mut shared = output.lock();
+            if shared.connection != connection {
+                return true;
+            }
+            let claimed: Vec<String> = {
+                let paths = paths.lock().unwrap_or_else(|e| e.into_inner());
+                paths
+                    .iter()
+                    .enumerate()
+                    .filter(|&(controller, _)| controller != output.controller)
+                    .map(|(_, path)| path.clone())
+                    .collect()
+            };
+            match reconnect.reconnect(shared.joycon, &claimed) {
+                Ok(()) => {
+                    paths.lock().unwrap_or_else(|e| e.into_inner())[output.controller] =
+                        shared.joycon.device_info().path.clone();
+                    shared.connection += 1;
+                    return true;
+                }
+                Err(e) => log::debug!("Reconnect failed: {}", e),
+            }
+        }
+        thread::sleep(RECONNECT_INTERVAL);
+    }
+}
+
+/// Turns on the motion sensors of every controller for expression, or
+/// of the `conducting` one only. A controller whose IMU cannot be enabled
+/// plays its notes as written, at the written tempo.
+fn enable_motion<S: RumbleSink>(
+    joycons: &mut [JoyCon<S>],
+    controls: &PlaybackControls,
+    conducting: Option<usize>,
+) {
+    for (idx, joycon) in joycons.iter_mut().enumerate() {
+        if controls.expression.is_none() && conducting != Some(idx) {
+            continue;
+        }
+        if !joycon.is_imu_enabled() {
+            if let Err(e) = joycon.enable_imu() {
+                log::warn!(
+                    "🎛️  JoyCon {} motion sensors unavailable ({}); ignoring its motion",
+                    idx + 1,
+                    e
+                );
+            }
+        }
+        // Motion from before the song should not bend its first note.
+        joycon.take_imu_samples();
+    }
+}
+
+/// Warns about every Joy-Con whose battery is at or below `threshold` when
+/// the song is long enough to risk it running out. Buttons pressed in the
+/// reports read for it still send their actions.
+fn warn_low_battery<S: RumbleSink>(
+    joycons: &mut [JoyCon<S>],
+    threshold: BatteryLevel,
+    controls: &PlaybackControls,
+    song_length: Duration,
+) {
+    if song_length < LONG_SONG {
+        return;
+    }
+    for (idx, joycon) in joycons.iter_mut().enumerate() {
+        let previous = joycon.input_state();
+        if let Ok(Some(state)) = joycon.poll_input() {
+            send_pressed_actions(previous, &state, controls);
+        }
+        if let Some(level) = joycon.battery().filter(|&level| level <= threshold) {
+            log::warn!(
+                "🪫 JoyCon {} battery is {} and this song is {:.0}s long — consider charging it first",
+                idx + 1,
+                level,
+                song_length.as_secs_f32()
+            );
+        }
+    }
+}
+
 /// Plays a MIDI file through connected JoyCons with runtime L/R swap support.
 ///
 /// # Runtime Controls
@@ -201,20 +1015,222 @@ fn spawn_input_thread(
 /// - **S** to swap which Joy-Con plays the primary (melody) part
 /// - **1** to cycle to the next primary candidate
 /// - **2** to cycle to the next secondary candidate
+/// - **Space** to pause or resume
+/// - **←** / **→** to seek back or forward five seconds
+/// - **[** / **]** to jump to the previous or next section
+/// - **+** / **-** to change the volume
 /// - **Q** or **Esc** to stop playback
+///
+/// The Joy-Con buttons are not read; [`play_midi_file_with`] can make them
+/// a remote through [`PlaybackOptions::buttons`].
 pub fn play_midi_file(path: PathBuf) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let manager = JoyConManager::new()?;
-    let joycons = manager.connect_and_initialize_joycons()?;
-    let num_joycons = joycons.len();
+    play_midi_file_with(path, &manager, &PlaybackOptions::default())
+}
+
+/// Plays a MIDI file on the JoyCons discovered by `manager`.
+///
+/// This is [`play_midi_file`] with the device backend chosen by the caller,
+/// e.g. a [`SimulatedBackend`](crate::joycon::SimulatedBackend) to run the
+/// whole pipeline without controllers.
+pub fn play_midi_file_with<B: DeviceBackend>(
+    path: PathBuf,
+    manager: &JoyConManager<B>,
+    options: &PlaybackOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    play_playlist_with(&Playlist::new(vec![path]), manager, options)
+}
+
+/// Plays every song in a playlist through connected JoyCons.
+///
+/// The JoyCons are connected and initialized once for the whole playlist,
+/// and the next song is converted while the current one plays, so songs
+/// follow each other without a gap. A song that fails to load is reported
+/// and skipped; when every song of a pass fails, playback stops with the
+/// last error. In addition to the
+/// controls of [`play_midi_file`], **N** and **P** move to the next and
+/// previous song.
+pub fn play_playlist(
+    playlist: &Playlist,
+    options: &PlaybackOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let manager = JoyConManager::new()?;
+    play_playlist_with(playlist, &manager, options)
+}
+
+/// Plays a playlist on the JoyCons discovered by `manager`.
+///
+/// See [`play_playlist`].
+pub fn play_playlist_with<B: DeviceBackend>(
+    playlist: &Playlist,
+    manager: &JoyConManager<B>,
+    options: &PlaybackOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut joycons = manager.connect_and_initialize_at_most(options.max_joycons)?;
+    let conversion = ConversionOptions {
+        num_joycons: count_outputs(&joycons),
+        ..options.conversion.clone()
+    };
+
+    // The input thread outlives individual songs, so it gets a binding whose
+    // contents are replaced at the start of each song.
+    let (action_tx, action_rx) = mpsc::channel();
+    let transport = Arc::new(Transport {
+        controls: PlaybackControls {
+            buttons: options.buttons.clone(),
+            actions: (!options.buttons.is_empty()).then_some(action_tx),
+            lights: options.lights,
+            low_battery: options.low_battery,
+            expression: options.expression,
+            conductor: options.conductor,
+            events: options.events.clone(),
+            ..PlaybackControls::default()
+        },
+        ..Transport::default()
+    });
+    let controls = &transport.controls;
+    let input_handle =
+        spawn_input_thread(Arc::clone(&transport), options.events.clone(), action_rx);
+
+    let mut order = playlist.order(options.shuffle, clock_seed());
+    let mut position = 0;
+    let mut result = Ok(());
+    let mut preload: Option<Preload> = None;
+    // Songs that failed in a row; a whole pass of them ends the playlist.
+    let mut failures = 0;
+
+    while position < order.len() && !transport.quit.load(Ordering::Relaxed) {
+        let path = &playlist.entries()[order[position]];
+        log::info!(
+            "🎵 Loading MIDI file ({}/{}): {:?}",
+            position + 1,
+            order.len(),
+            path
+        );
+
+        let loaded = match preload.take() {
+            Some(preload) if preload.path == *path => preload.join(),
+            // A skip or a reshuffle went elsewhere; that load is discarded.
+            _ => load_song(path, &conversion),
+        };
+        // The next song converts while this one plays, so the two follow
+        // each other without a gap.
+        let next = match options.repeat {
+            RepeatMode::One => Some(order[position]),
+            _ if position + 1 < order.len() => Some(order[position + 1]),
+            RepeatMode::All if !options.shuffle => order.first().copied(),
+            _ => None,
+        };
+        preload = next.map(|next| Preload::start(&playlist.entries()[next], &conversion));
+
+        let played: Result<(), Box<dyn std::error::Error + Send + Sync>> =
+            loaded.and_then(|song| {
+                if log::log_enabled!(log::Level::Info) {
+                    log_song_analysis(&song);
+                    log_rumble_tracks(&song.tracks);
+                }
+                log::info!(
+                    "    S = swap L/R  |  1 = cycle primary  |  2 = cycle secondary  |  N/P = next/previous  |  Q = quit"
+                );
+                log::info!(
+                    "    Space = pause  |  ←/→ = seek 5s  |  [/] = previous/next section  |  +/- = volume"
+                );
+                options.events.emit(PlaybackEvent::SongStarted {
+                    path: path.clone(),
+                    position,
+                    count: order.len(),
+                });
+                if let Some(threshold) = options.low_battery {
+                    warn_low_battery(&mut joycons, threshold, controls, song.total_duration());
+                }
+                *controls.binding.lock().unwrap_or_else(|e| e.into_inner()) =
+                    JoyConBinding::new(&song.selection);
+                *transport.plan.lock().unwrap_or_else(|e| e.into_inner()) =
+                    Some(song.plan.clone());
+                controls.clock.reset();
+                controls.quit.store(false, Ordering::Relaxed);
+                let reconnect: Option<&dyn Reconnect<B::Sink>> =
+                    options.reconnect.then_some(manager);
+                play_tracks(
+                    &mut joycons,
+                    &song.tracks,
+                    &song.plan,
+                    controls,
+                    reconnect,
+                )
+            });
+
+        let failed = played.is_err();
+        if let Err(e) = played {
+            failures += 1;
+            if playlist.len() == 1 || failures >= order.len() {
+                if failures > 1 {
+                    log::error!("❌ No song in the playlist could be played");
+                }
+                result = Err(e);
+                break;
+            }
+            log::warn!("⚠️  Skipping {:?}: {}", path, e);
+        } else {
+            failures = 0;
+        }
+
+        match transport.take_skip() {
+            Some(SongSkip::Previous) => position = position.saturating_sub(1),
+            Some(SongSkip::Next) => position += 1,
+            None if options.repeat == RepeatMode::One && !failed => {}
+            None => position += 1,
+        }
+        if position == order.len() && options.repeat == RepeatMode::All {
+            order = playlist.order(options.shuffle, clock_seed());
+            position = 0;
+        }
+    }
+
+    // Signal the input thread to stop and wait for it.
+    transport.quit.store(true, Ordering::Relaxed);
+    let _ = input_handle.join();
+
+    result?;
+    log::info!("✨ Playback complete!");
+    Ok(())
+}
+
+type LoadedSong = Result<SongAnalysis, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Reads and converts a playlist entry.
+fn load_song(path: &Path, conversion: &ConversionOptions) -> LoadedSong {
+    let data = std::fs::read(path)?;
+    Ok(analyze_song(&data, conversion)?)
+}
+
+/// A playlist entry converting on a background thread.
+struct Preload {
+    path: PathBuf,
+    handle: thread::JoinHandle<LoadedSong>,
+}
 
-    println!("🎵 Loading MIDI file: {:?}", path);
-    let midi_data = std::fs::read(&path)?;
+impl Preload {
+    fn start(path: &Path, conversion: &ConversionOptions) -> Self {
+        let thread_path = path.to_path_buf();
+        let conversion = conversion.clone();
+        Self {
+            path: path.to_path_buf(),
+            handle: thread::spawn(move || load_song(&thread_path, &conversion)),
+        }
+    }
 
-    let (tracks, plan, selection) = parse_midi_to_rumble(&midi_data, num_joycons)?;
+    fn join(self) -> LoadedSong {
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err("loading the song panicked".into()))
+    }
+}
 
-    println!("\nAvailable parts (rumble tracks): {}", tracks.len());
+fn log_rumble_tracks(tracks: &[RumbleTrack]) {
+    log::info!("Available parts (rumble tracks): {}", tracks.len());
     for (idx, track) in tracks.iter().enumerate() {
-        println!(
+        log::info!(
             "  Part {} : {} notes, {} cmds, dur={:.1}s, type={:?}, name={:?}",
             idx,
             track.metrics.note_count,
@@ -224,203 +1240,465 @@ pub fn play_midi_file(path: PathBuf) -> Result<(), Box<dyn std::error::Error + S
             track.metrics.track_name.as_deref().unwrap_or("-")
         );
     }
+}
 
-    let binding = Arc::new(Mutex::new(JoyConBinding::new(&selection)));
-    let quit = Arc::new(AtomicBool::new(false));
-    let start_signal = Arc::new(Mutex::new(false));
+/// Plays pre-converted rumble tracks on a set of JoyCons.
+///
+/// This is the synchronized playback loop behind [`play_midi_file`]: one
+/// thread per JoyCon, each following `plan` and the shared binding of
+/// `controls`, all timed from its shared clock. The clock is resumed once
+/// every thread is ready; pausing it silences every motor and seeking it
+/// re-positions every Joy-Con. Playback stops early when the quit flag is
+/// set, and every note is scaled by the master volume. With an action
+/// channel, each thread also reads its Joy-Con's buttons and sends the
+/// mapped actions. Every note sent, plan-driven part change and device
+/// error is reported on the controls' event sender.
+///
+/// The JoyCons are only borrowed, so the caller can play another song on
+/// the same connection afterwards.
+///
+/// A Pro Controller plays two parts, one per motor: its right motor is the
+/// `Right` side of the binding and its left motor the `Left` side. Build
+/// `plan` for [`count_outputs`] Joy-Cons; each motor then has its own
+/// thread and Joy-Con index, right motor first.
+///
+/// The JoyCons may use any [`RumbleSink`], so the same loop drives real
+/// controllers and [`SimulatedDevice`](crate::joycon::SimulatedDevice)s.
+///
+/// A Joy-Con whose connection fails stops playing and fails the song;
+/// [`play_playlist_with`] brings it back instead when
+/// [`PlaybackOptions::reconnect`] is set.
+pub fn play_rumble_tracks<S: RumbleSink>(
+    joycons: &mut [JoyCon<S>],
+    tracks: &[RumbleTrack],
+    plan: &PlaybackPlan,
+    controls: &PlaybackControls,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    play_tracks(joycons, tracks, plan, controls, None)
+}
 
-    // Spawn the keyboard input thread.
-    let input_handle = spawn_input_thread(Arc::clone(&binding), Arc::clone(&quit));
+/// [`play_rumble_tracks`], surviving a Joy-Con dropping out if given a way
+/// to `reconnect` it.
+///
+/// When sending to a Joy-Con fails, its thread reports a
+/// [`PlaybackEvent::DeviceError`] and asks `reconnect` to re-open it every
+/// half second. Once it is back it re-synchronizes to the current song
+/// position and a [`PlaybackEvent::Reconnected`] follows. A Joy-Con that
+/// does not come back before the song ends stays silent without failing
+/// the song.
+fn play_tracks<S: RumbleSink>(
+    joycons: &mut [JoyCon<S>],
+    tracks: &[RumbleTrack],
+    plan: &PlaybackPlan,
+    controls: &PlaybackControls,
+    reconnect: Option<&dyn Reconnect<S>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let events = &controls.events;
+    let song_end = tracks
+        .iter()
+        .map(|t| t.total_duration)
+        .max()
+        .unwrap_or(Duration::ZERO);
+
+    // The conductor is numbered by output; its controller conducts.
+    let conducting = controls.conductor.and_then(|conductor| {
+        let target = OutputTarget::all(joycons).get(conductor.joycon).copied();
+        if target.is_none() {
+            log::warn!(
+                "🪄 No output {} to conduct with; playing at the written tempo",
+                conductor.joycon + 1
+            );
+        }
+        target.map(|target| target.joycon)
+    });
+    if controls.expression.is_some() || conducting.is_some() {
+        enable_motion(joycons, controls, conducting);
+    }
 
-    let mut handles: Vec<thread::JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>>> =
-        Vec::new();
+    // Where every controller is open, so reconnecting one never touches
+    // another.
+    let paths = Mutex::new(
+        joycons
+            .iter()
+            .map(|joycon| joycon.device_info().path.clone())
+            .collect::<Vec<_>>(),
+    );
+    // A Pro Controller's motors play separately: the right one takes the
+    // primary side, the left one the next part in the plan.
+    let shared: Vec<Mutex<SharedJoyCon<S>>> = joycons
+        .iter_mut()
+        .enumerate()
+        .map(|(idx, joycon)| {
+            let expression = controls
+                .expression
+                .filter(|_| joycon.is_imu_enabled())
+                .map(Expression::new);
+            let conductor = controls
+                .conductor
+                .filter(|_| conducting == Some(idx) && joycon.is_imu_enabled())
+                .map(|c| Conductor::new(c, plan.beats.clone()));
+            Mutex::new(SharedJoyCon {
+                joycon,
+                connection: 0,
+                expression,
+                conductor,
+                battery_reported: None,
+            })
+        })
+        .collect();
+    let outputs: Vec<Output<S>> = shared
+        .iter()
+        .enumerate()
+        .flat_map(|(controller, shared)| {
+            let two_motors = shared
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .joycon
+                .has_two_motors();
+            OutputTarget::motors(two_motors)
+                .into_iter()
+                .map(move |motor| Output {
+                    shared,
+                    controller,
+                    motor,
+                    sounding: Mutex::new(Sounding {
+                        note: SILENCE,
+                        sent: SILENCE,
+                    }),
+                })
+        })
+        .collect();
+
+    let paths = &paths;
+    let result = thread::scope(|scope| {
+        let mut handles: Vec<
+            thread::ScopedJoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+        > = Vec::new();
+
+        for (joycon_idx, output) in outputs.iter().enumerate() {
+            handles.push(scope.spawn(move || loop {
+                let connection = output.connection();
+                let error = match play_on_joycon(
+                    output, joycon_idx, tracks, plan, controls, events, song_end,
+                ) {
+                    Ok(()) => return Ok(()),
+                    Err(e) => e,
+                };
+                events.emit(PlaybackEvent::DeviceError {
+                    joycon: joycon_idx,
+                    message: error.to_string(),
+                });
+                let Some(reconnect) = reconnect else {
+                    return Err(error.into());
+                };
+                log::warn!(
+                    "🔌 JoyCon {} lost ({}); waiting for it to reconnect",
+                    joycon_idx + 1,
+                    error
+                );
+                if !await_reconnect(output, connection, reconnect, paths, controls, song_end) {
+                    return Ok(());
+                }
+                log::info!("🔌 JoyCon {} reconnected", joycon_idx + 1);
+                events.emit(PlaybackEvent::Reconnected { joycon: joycon_idx });
+            }));
+        }
 
-    for (joycon_idx, mut joycon) in joycons.into_iter().enumerate() {
-        let joycon_signal = Arc::clone(&start_signal);
-        let joycon_tracks = tracks.clone();
-        let joycon_plan = plan.clone();
-        let joycon_binding = Arc::clone(&binding);
-        let joycon_quit = Arc::clone(&quit);
-        let side = JoyConSide::from_joycon_type(joycon.get_type());
+        log::info!("▶️  Starting playback…");
+        controls.clock.resume();
 
-        handles.push(thread::spawn(move || {
-            while !*joycon_signal.lock().unwrap_or_else(|e| e.into_inner()) {
-                thread::sleep(Duration::from_millis(1));
+        for handle in handles {
+            match handle.join() {
+                Ok(result) => result?,
+                Err(_) => return Err("A playback thread panicked".into()),
             }
+        }
 
-            let playback_start = Instant::now();
-            let mut last_write = playback_start;
+        Ok(())
+    });
 
-            // Resolve which track this Joy-Con should start on.
-            let mut current_track_idx = joycon_binding
-                .lock()
-                .map(|b| b.track_for_side(side))
-                .unwrap_or(0);
-            let mut command_index = 0;
-            let mut scheduled_time = Duration::ZERO;
-            let mut pending_track_switch: Option<usize> = None;
-            let mut next_section_time = joycon_plan.next_section_time(Duration::ZERO);
-
-            println!(
-                "🎮 JoyCon {} ({:?}) starting on part {}",
-                joycon_idx + 1,
-                side,
-                current_track_idx
-            );
+    // Like the motors, the lights are left off. Best-effort, like input.
+    if controls.lights {
+        for shared in &shared {
+            let mut shared = shared.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = shared.joycon.set_player_lights(PlayerLights::OFF);
+            let _ = shared.joycon.set_home_light(&HomeLight::off());
+        }
+    }
+    result
+}
+
+/// One Joy-Con's playback thread: follows the clock and plan from the
+/// current song position until the song ends or `quit` is set.
+///
+/// Starts by re-positioning at the clock's position, so calling it again
+/// after a reconnection resumes where the song is.
+fn play_on_joycon<S: RumbleSink>(
+    output: &Output<'_, '_, S>,
+    joycon_idx: usize,
+    joycon_tracks: &[RumbleTrack],
+    joycon_plan: &PlaybackPlan,
+    controls: &PlaybackControls,
+    events: &EventSender,
+    song_end: Duration,
+) -> Result<(), JoyConError> {
+    let joycon_binding = &controls.binding;
+    let joycon_clock = &controls.clock;
+    let joycon_quit = &controls.quit;
+    let side = output.side();
+
+    if !joycon_clock.wait_while_paused(joycon_quit) {
+        return Ok(());
+    }
+
+    let mut last_write = Instant::now();
+
+    // Resolve which track this Joy-Con should start on.
+    let mut bound_track = joycon_binding
+        .lock()
+        .map(|b| b.track_for_side(side))
+        .unwrap_or(0);
+    let mut current_track_idx = bound_track;
+    let mut command_index = 0;
+    let mut scheduled_time = Duration::ZERO;
+    let mut pending_track_switch: Option<usize> = None;
+    let mut next_section_time = joycon_plan.next_section_time(Duration::ZERO);
+    let mut generation = joycon_clock.generation();
+    let mut first_sync = true;
+    let mut needs_sync = true;
+    let mut shown_candidate = None;
+
+    log::info!(
+        "🎮 JoyCon {} ({:?}) starting on part {}",
+        joycon_idx + 1,
+        side,
+        current_track_idx
+    );
+
+    loop {
+        if joycon_quit.load(Ordering::Relaxed) {
+            break;
+        }
 
-            loop {
+        // Pause: silence the motor and wait for resume or quit,
+        // still reading the buttons so they can resume.
+        if joycon_clock.is_paused() {
+            output.silence()?;
+            last_write = Instant::now();
+            while !joycon_clock.wait_running(INPUT_POLL_INTERVAL) {
                 if joycon_quit.load(Ordering::Relaxed) {
                     break;
                 }
+                output.poll(joycon_idx, controls, events);
+            }
+            if joycon_quit.load(Ordering::Relaxed) {
+                break;
+            }
+            needs_sync = true;
+            continue;
+        }
 
-                let current_time = playback_start.elapsed();
-
-                // Check if the binding changed (swap / cycle).
-                let desired_track = joycon_binding
-                    .lock()
-                    .map(|b| b.track_for_side(side))
-                    .unwrap_or(current_track_idx);
-
-                if desired_track != current_track_idx && desired_track < joycon_tracks.len() {
-                    current_track_idx = desired_track;
-                    command_index = find_commands_at_time(
-                        &joycon_tracks[current_track_idx].commands,
-                        current_time,
-                    );
-                    pending_track_switch = None;
-                }
+        if joycon_clock.generation() != generation {
+            needs_sync = true;
+        }
 
-                let track = &joycon_tracks[current_track_idx];
-
-                if command_index >= track.commands.len() {
-                    let mut found_next = false;
-                    let mut scan_time = next_section_time;
-                    while let Some(boundary) = scan_time {
-                        let candidate = joycon_plan.track_for(joycon_idx, boundary);
-                        if candidate != current_track_idx {
-                            let ci = find_commands_at_time(
-                                &joycon_tracks[candidate].commands,
-                                current_time,
-                            );
-                            if ci < joycon_tracks[candidate].commands.len() {
-                                current_track_idx = candidate;
-                                command_index = ci;
-                                next_section_time = joycon_plan.next_section_time(boundary);
-                                found_next = true;
-                                break;
-                            }
-                        }
-                        scan_time = joycon_plan.next_section_time(boundary);
+        let current_time = joycon_clock.position();
+
+        // After a seek or resume, re-position on the track the
+        // plan assigns at the new time and restore whatever note
+        // was sounding there.
+        if needs_sync {
+            generation = joycon_clock.generation();
+            current_track_idx = track_at(joycon_plan, joycon_idx, bound_track, current_time)
+                .min(joycon_tracks.len().saturating_sub(1));
+            let commands = &joycon_tracks[current_track_idx].commands;
+            (command_index, scheduled_time) = position_in_track(commands, current_time);
+            next_section_time = joycon_plan.next_section_time(current_time);
+            pending_track_switch = None;
+
+            let sounding = command_index.checked_sub(1).map(|i| &commands[i]);
+            match sounding {
+                Some(cmd) => {
+                    let cmd = &cmd.with_gain(controls.volume.get());
+                    if cmd.amplitude > 0.0 {
+                        events.emit(PlaybackEvent::NoteStarted {
+                            joycon: joycon_idx,
+                            frequency: cmd.frequency,
+                            amplitude: cmd.amplitude,
+                            time: current_time,
+                        });
                     }
-                    if !found_next {
-                        break;
-                    }
-                    continue;
+                    output.play(cmd)?;
+                    last_write = Instant::now();
                 }
-
-                // Section boundary crossing.
-                if let Some(boundary) = next_section_time {
-                    if current_time >= boundary {
-                        let new_track = joycon_plan.track_for(joycon_idx, current_time);
-                        if new_track != current_track_idx {
-                            pending_track_switch = Some(new_track);
-                        }
-                        next_section_time = joycon_plan.next_section_time(current_time);
-                    }
+                None if !first_sync => {
+                    output.silence()?;
+                    last_write = Instant::now();
                 }
+                None => {}
+            }
+            first_sync = false;
+            needs_sync = false;
+        }
 
-                let cmd = &track.commands[command_index];
-                let prev_cmd = if command_index > 0 {
-                    Some(&track.commands[command_index - 1])
-                } else {
-                    None
-                };
+        // Check if the binding changed (swap / cycle).
+        let desired_track = joycon_binding
+            .lock()
+            .map(|b| b.track_for_side(side))
+            .unwrap_or(bound_track);
 
-                // Execute pending switch at note-off boundary.
-                if let Some(new_track_idx) = pending_track_switch {
-                    if is_note_off(cmd, prev_cmd) {
-                        current_track_idx = new_track_idx;
-                        command_index = find_commands_at_time(
-                            &joycon_tracks[current_track_idx].commands,
-                            current_time,
-                        );
-                        pending_track_switch = None;
-                        continue;
-                    }
-                }
+        if controls.lights && output.owns_controller() {
+            let candidate = joycon_binding
+                .lock()
+                .map(|b| b.candidate_for_side(side))
+                .unwrap_or(1);
+            if shown_candidate != Some(candidate) {
+                let _ = output
+                    .lock()
+                    .joycon
+                    .set_player_lights(PlayerLights::count(candidate));
+                shown_candidate = Some(candidate);
+            }
+        }
 
-                // Sleep until the scheduled fire time, absorbing any prior
-                // oversleep or HID-I/O overhead automatically.
-                if !cmd.wait_before.is_zero() {
-                    scheduled_time += cmd.wait_before;
-                    let target = playback_start + scheduled_time;
-                    let now = Instant::now();
-                    if target > now {
-                        thread::sleep(target - now);
-                    }
-                }
+        if desired_track != bound_track {
+            bound_track = desired_track;
+            if desired_track != current_track_idx && desired_track < joycon_tracks.len() {
+                current_track_idx = desired_track;
+                (command_index, scheduled_time) =
+                    position_in_track(&joycon_tracks[current_track_idx].commands, current_time);
+                pending_track_switch = None;
+            }
+        }
 
-                // Coalesce consecutive zero-wait commands (same-tick events).
-                // The JoyCon plays one frequency at a time, so only the last
-                // state at each tick is audible.
-                while command_index + 1 < track.commands.len()
-                    && track.commands[command_index + 1].wait_before.is_zero()
-                {
-                    command_index += 1;
+        let track = &joycon_tracks[current_track_idx];
+
+        if command_index >= track.commands.len() {
+            let mut found_next = false;
+            let mut scan_time = next_section_time;
+            while let Some(boundary) = scan_time {
+                let candidate = joycon_plan.track_for(joycon_idx, boundary);
+                if candidate != current_track_idx {
+                    let (ci, elapsed) =
+                        position_in_track(&joycon_tracks[candidate].commands, current_time);
+                    if ci < joycon_tracks[candidate].commands.len() {
+                        current_track_idx = candidate;
+                        command_index = ci;
+                        scheduled_time = elapsed;
+                        next_section_time = joycon_plan.next_section_time(boundary);
+                        events.emit(PlaybackEvent::SectionChanged {
+                            joycon: joycon_idx,
+                            section: joycon_plan.section_index_at(boundary),
+                            track: candidate,
+                        });
+                        pulse_home_light(output, controls);
+                        found_next = true;
+                        break;
+                    }
                 }
-                let cmd = &track.commands[command_index];
-
-                if cmd.amplitude > 0.0 {
-                    println!(
-                        "🎮 JoyCon {} playing: freq={:.1}, amp={:.2}, t={:.2}s",
-                        joycon_idx + 1,
-                        cmd.frequency,
-                        cmd.amplitude,
-                        playback_start.elapsed().as_secs_f32()
-                    );
+                scan_time = joycon_plan.next_section_time(boundary);
+            }
+            if !found_next {
+                // Nothing left to play; stay available for a
+                // seek back until the song ends.
+                if wait_until_polling(output, joycon_idx, controls, events, song_end, generation) {
+                    break;
                 }
+            }
+            continue;
+        }
 
-                // Throttle HID writes to avoid overwhelming the USB pipe.
-                const MIN_HID_INTERVAL: Duration = Duration::from_millis(2);
-                let since_last = last_write.elapsed();
-                if since_last < MIN_HID_INTERVAL {
-                    thread::sleep(MIN_HID_INTERVAL - since_last);
+        // Section boundary crossing.
+        if let Some(boundary) = next_section_time {
+            if current_time >= boundary {
+                let new_track = joycon_plan.track_for(joycon_idx, current_time);
+                if new_track != current_track_idx {
+                    pending_track_switch = Some(new_track);
                 }
+                next_section_time = joycon_plan.next_section_time(current_time);
+            }
+        }
 
-                joycon.rumble(cmd.frequency, cmd.amplitude)?;
-                last_write = Instant::now();
-                command_index += 1;
+        let cmd = &track.commands[command_index];
+        let prev_cmd = if command_index > 0 {
+            Some(&track.commands[command_index - 1])
+        } else {
+            None
+        };
+
+        // Execute pending switch at note-off boundary.
+        if let Some(new_track_idx) = pending_track_switch {
+            if is_note_off(cmd, prev_cmd) {
+                current_track_idx = new_track_idx;
+                (command_index, scheduled_time) =
+                    position_in_track(&joycon_tracks[current_track_idx].commands, current_time);
+                pending_track_switch = None;
+                events.emit(PlaybackEvent::SectionChanged {
+                    joycon: joycon_idx,
+                    section: joycon_plan.section_index_at(current_time),
+                    track: current_track_idx,
+                });
+                pulse_home_light(output, controls);
+                continue;
             }
+        }
 
-            println!("🎮 JoyCon {} stopping", joycon_idx + 1);
-            joycon.rumble(0.0, 0.0)?;
-            Ok(())
-        }));
-    }
+        // Wait on the shared clock until the scheduled fire time,
+        // absorbing any prior oversleep or HID-I/O overhead. A
+        // pause, seek or quit interrupts the wait and is handled
+        // at the top of the loop.
+        if !cmd.wait_before.is_zero() {
+            let fire_time = scheduled_time + cmd.wait_before;
+            if !wait_until_polling(output, joycon_idx, controls, events, fire_time, generation) {
+                continue;
+            }
+            scheduled_time = fire_time;
+        }
 
-    println!("\n▶️  Starting playback…");
-    println!("    S = swap L/R  |  1 = cycle primary  |  2 = cycle secondary  |  Q = quit\n");
-    *start_signal.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        // Coalesce consecutive zero-wait commands (same-tick events).
+        // The JoyCon plays one frequency at a time, so only the last
+        // state at each tick is audible.
+        while command_index + 1 < track.commands.len()
+            && track.commands[command_index + 1].wait_before.is_zero()
+        {
+            command_index += 1;
+        }
+        let cmd = &track.commands[command_index].with_gain(controls.volume.get());
+
+        if cmd.amplitude > 0.0 {
+            events.emit(PlaybackEvent::NoteStarted {
+                joycon: joycon_idx,
+                frequency: cmd.frequency,
+                amplitude: cmd.amplitude,
+                time: joycon_clock.position(),
+            });
+        }
 
-    for handle in handles {
-        match handle.join() {
-            Ok(result) => result?,
-            Err(_) => return Err("A playback thread panicked".into()),
+        // Throttle HID writes to avoid overwhelming the USB pipe.
+        const MIN_HID_INTERVAL: Duration = Duration::from_millis(2);
+        let since_last = last_write.elapsed();
+        if since_last < MIN_HID_INTERVAL {
+            thread::sleep(MIN_HID_INTERVAL - since_last);
         }
-    }
 
-    // Signal the input thread to stop and wait for it.
-    quit.store(true, Ordering::Relaxed);
-    let _ = input_handle.join();
+        output.play(cmd)?;
+        last_write = Instant::now();
+        command_index += 1;
+    }
 
-    println!("✨ Playback complete!");
+    log::info!("🎮 JoyCon {} stopping", joycon_idx + 1);
+    output.silence()?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::joycon::{Button, SimulatedBackend, SimulatedDevice};
+    use crate::midi::rumble::parse_midi_to_rumble;
     use crate::midi::scoring::PartSelection;
+    use crate::midi::test_support::two_part_song;
+    use crate::midi::track_types::SectionAssignment;
 
     #[test]
     fn test_find_commands_at_time() {
@@ -429,16 +1707,19 @@ mod tests {
                 frequency: 100.0,
                 amplitude: 1.0,
                 wait_before: Duration::from_millis(100),
+                low_band: None,
             },
             RumbleCommand {
                 frequency: 200.0,
                 amplitude: 0.5,
                 wait_before: Duration::from_millis(200),
+                low_band: None,
             },
             RumbleCommand {
                 frequency: 300.0,
                 amplitude: 0.7,
                 wait_before: Duration::from_millis(300),
+                low_band: None,
             },
         ];
 
@@ -467,12 +1748,14 @@ mod tests {
             frequency: 100.0,
             amplitude: 1.0,
             wait_before: Duration::from_millis(100),
+            low_band: None,
         };
 
         let note_off = RumbleCommand {
             frequency: 100.0,
             amplitude: 0.0,
             wait_before: Duration::from_millis(100),
+            low_band: None,
         };
 
         assert!(!is_note_off(&note_on, None));
@@ -516,6 +1799,8 @@ mod tests {
 
         binding.cycle_primary();
         assert_eq!(binding.primary_part_idx, 2);
+        assert_eq!(binding.candidate_for_side(JoyConSide::Right), 2);
+        assert_eq!(binding.candidate_for_side(JoyConSide::Left), 1);
 
         binding.cycle_primary();
         assert_eq!(binding.primary_part_idx, 4);
@@ -540,4 +1825,576 @@ mod tests {
         binding.cycle_secondary();
         assert_eq!(binding.secondary_part_idx, 1); // wraps
     }
+
+    #[test]
+    fn test_end_to_end_simulated_playback() {
+        let left = SimulatedDevice::new();
+        let right = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new()
+                .with_device(JoyConType::Left, left.clone())
+                .with_device(JoyConType::Right, right.clone()),
+        );
+
+        let mut joycons = manager.connect_and_initialize_joycons().unwrap();
+        assert_eq!(joycons.len(), 2);
+        // Initialization enables rumble and plays the test scale.
+        assert!(left.reports().len() > 1);
+        left.clear();
+        right.clear();
+
+        let (tracks, plan, selection) =
+            parse_midi_to_rumble(&two_part_song(96), joycons.len()).unwrap();
+        let (events, rx) = EventSender::channel();
+        play_rumble_tracks(
+            &mut joycons,
+            &tracks,
+            &plan,
+            &PlaybackControls {
+                events,
+                ..PlaybackControls::new(&selection)
+            },
+        )
+        .unwrap();
+
+        // Every note of both parts is reported once.
+        let notes: Vec<usize> = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                PlaybackEvent::NoteStarted { joycon, .. } => Some(joycon),
+                _ => None,
+            })
+            .collect();
+        for joycon in 0..2 {
+            assert_eq!(notes.iter().filter(|&&j| j == joycon).count(), 4);
+        }
+
+        for device in [&left, &right] {
+            let reports = device.reports();
+            assert!(reports.iter().all(|r| r.is_rumble() && r.data.len() == 49));
+            // Four notes plus silences, and the final report stops the motor.
+            assert!(reports.len() >= 4, "only {} reports", reports.len());
+            let first = reports.first().unwrap().timestamp;
+            let last = reports.last().unwrap().timestamp;
+            assert!(last - first >= Duration::from_millis(600));
+        }
+
+        // The final report on each side must match an explicit stop command.
+        let reference = SimulatedDevice::new();
+        let mut stopper = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Right, reference.clone()),
+        )
+        .scan_for_devices()
+        .unwrap()
+        .remove(0);
+        stopper.rumble(0.0, 0.0).unwrap();
+        assert_eq!(
+            right.reports().last().unwrap().data[2..10],
+            reference.reports()[0].data[2..10]
+        );
+    }
+
+    #[test]
+    fn test_playlist_reuses_connection() {
+        let dir = std::env::temp_dir().join(format!("mj-playback-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let song = dir.join("song.mid");
+        std::fs::write(&song, two_part_song(96)).unwrap();
+
+        let options = PlaybackOptions::default();
+        let report_count = |playlist: Playlist| {
+            let device = SimulatedDevice::new();
+            let manager = JoyConManager::with_backend(
+                SimulatedBackend::new().with_device(JoyConType::Right, device.clone()),
+            );
+            play_playlist_with(&playlist, &manager, &options).unwrap();
+            device.reports().len()
+        };
+
+        let init = {
+            let device = SimulatedDevice::new();
+            JoyConManager::with_backend(
+                SimulatedBackend::new().with_device(JoyConType::Right, device.clone()),
+            )
+            .connect_and_initialize_joycons()
+            .unwrap();
+            device.reports().len()
+        };
+        let once = report_count(Playlist::new(vec![song.clone()]));
+        let twice = report_count(Playlist::new(vec![song.clone(), song]));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Initialization (rumble enable + chime) happens once per playlist.
+        assert!(once > init);
+        assert_eq!(twice - init, 2 * (once - init));
+    }
+
+    #[test]
+    fn test_repeating_playlist_of_bad_files_stops() {
+        let dir = std::env::temp_dir().join(format!("mj-bad-playlist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let broken = dir.join("broken.mid");
+        std::fs::write(&broken, b"not a midi file").unwrap();
+        let playlist = Playlist::new(vec![broken, dir.join("missing.mid")]);
+
+        let options = PlaybackOptions {
+            repeat: RepeatMode::All,
+            ..PlaybackOptions::default()
+        };
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Right, SimulatedDevice::new()),
+        );
+        let result = play_playlist_with(&playlist, &manager, &options);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_position_in_track() {
+        let commands: Vec<RumbleCommand> = [100, 200, 300]
+            .into_iter()
+            .map(|ms| RumbleCommand {
+                frequency: 440.0,
+                amplitude: 0.5,
+                wait_before: Duration::from_millis(ms),
+                low_band: None,
+            })
+            .collect();
+
+        assert_eq!(
+            position_in_track(&commands, Duration::from_millis(50)),
+            (0, Duration::ZERO)
+        );
+        assert_eq!(
+            position_in_track(&commands, Duration::from_millis(350)),
+            (2, Duration::from_millis(300))
+        );
+        assert_eq!(
+            position_in_track(&commands, Duration::from_secs(5)),
+            (3, Duration::from_millis(600))
+        );
+    }
+
+    #[test]
+    fn test_previous_section_target() {
+        let section = |secs| SectionAssignment {
+            start_time: Duration::from_secs(secs),
+            track_indices: vec![0, 1],
+        };
+        let plan = PlaybackPlan::new(vec![section(0), section(10), section(20)]);
+
+        // Well into a section: restart it.
+        assert_eq!(
+            previous_section_target(&plan, Duration::from_secs(15)),
+            Duration::from_secs(10)
+        );
+        // Just after a section start: go to the one before.
+        assert_eq!(
+            previous_section_target(&plan, Duration::from_millis(20_500)),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            previous_section_target(&plan, Duration::from_secs(1)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_playback_starts_from_seeked_clock() {
+        let right = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Right, right.clone()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+
+        let (tracks, plan, selection) = parse_midi_to_rumble(&two_part_song(96), 1).unwrap();
+        let controls = PlaybackControls::new(&selection);
+        controls.clock.seek_to(Duration::from_millis(500));
+
+        let started = Instant::now();
+        play_rumble_tracks(&mut joycons, &tracks, &plan, &controls).unwrap();
+
+        // Only the last 200 ms of the 700 ms song are played.
+        let elapsed = started.elapsed();
+        assert!(elapsed < Duration::from_millis(450), "took {elapsed:?}");
+        assert!(elapsed >= Duration::from_millis(150), "took {elapsed:?}");
+        assert!(!right.reports().is_empty());
+    }
+
+    #[test]
+    fn test_joycon_buttons_send_actions() {
+        let right = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Right, right.clone()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+        let (tracks, plan, selection) = parse_midi_to_rumble(&two_part_song(96), 1).unwrap();
+
+        let (action_tx, action_rx) = mpsc::channel();
+        let (events, rx) = EventSender::channel();
+        let controls = PlaybackControls {
+            actions: Some(action_tx),
+            events,
+            ..PlaybackControls::new(&selection)
+        };
+        controls.volume.set(0.5);
+        let mut state = InputState::default();
+        state.buttons.insert(Button::X);
+        right.push_input(state.to_report());
+
+        play_rumble_tracks(&mut joycons, &tracks, &plan, &controls).unwrap();
+
+        assert_eq!(
+            action_rx.try_iter().collect::<Vec<_>>(),
+            [PlaybackAction::Swap]
+        );
+        let amplitudes: Vec<f32> = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                PlaybackEvent::NoteStarted { amplitude, .. } => Some(amplitude),
+                _ => None,
+            })
+            .collect();
+        assert!(!amplitudes.is_empty());
+        assert!(amplitudes.iter().all(|&a| a <= 0.5));
+    }
+
+    #[test]
+    fn test_player_lights_show_candidate() {
+        let right = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Right, right.clone()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+        let (tracks, plan, _) = parse_midi_to_rumble(&two_part_song(96), 1).unwrap();
+
+        let controls = PlaybackControls {
+            lights: true,
+            ..PlaybackControls::new(&PartSelection {
+                primary: 0,
+                secondary: 0,
+                primary_candidates: vec![0, 0, 0],
+                secondary_candidates: vec![0],
+            })
+        };
+        controls.binding.lock().unwrap().cycle_primary();
+        controls.binding.lock().unwrap().cycle_primary();
+        play_rumble_tracks(&mut joycons, &tracks, &plan, &controls).unwrap();
+
+        let lights: Vec<u8> = right
+            .reports()
+            .iter()
+            .filter(|r| r.data[0] == 0x01 && r.data[10] == 0x30)
+            .map(|r| r.data[11])
+            .collect();
+        // The third candidate, then off once playback ends.
+        assert_eq!(lights, [0b0111, 0]);
+        assert_eq!(right.reports().last().unwrap().data[10], 0x38);
+    }
+
+    #[test]
+    fn test_battery_drops_are_reported_once() {
+        let device = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Left, device.clone()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+        let controls = PlaybackControls {
+            low_battery: Some(BatteryLevel::Low),
+            ..PlaybackControls::default()
+        };
+        let (events, rx) = EventSender::channel();
+
+        let mut reported = None;
+        for battery in [
+            BatteryLevel::Medium,
+            BatteryLevel::Low,
+            BatteryLevel::Low,
+            BatteryLevel::Medium,
+            BatteryLevel::Critical,
+        ] {
+            let state = InputState {
+                battery,
+                ..InputState::default()
+            };
+            device.push_input(state.to_report());
+            poll_controller(&mut joycons[0], 0, &mut reported, &controls, &events);
+        }
+
+        let levels: Vec<BatteryLevel> = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                PlaybackEvent::BatteryLow { joycon: 0, level } => Some(level),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(levels, [BatteryLevel::Low, BatteryLevel::Critical]);
+    }
+
+    #[test]
+    fn test_battery_low_from_the_start_is_reported() {
+        let device = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Left, device.clone()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+        let low = InputState {
+            battery: BatteryLevel::Low,
+            ..InputState::default()
+        };
+        // The controller was already low when it was initialized.
+        device.push_input(low.to_report());
+        joycons[0].poll_input().unwrap();
+
+        let controls = PlaybackControls {
+            low_battery: Some(BatteryLevel::Low),
+            ..PlaybackControls::default()
+        };
+        let (events, rx) = EventSender::channel();
+        let mut reported = None;
+        for _ in 0..2 {
+            device.push_input(low.to_report());
+            poll_controller(&mut joycons[0], 0, &mut reported, &controls, &events);
+        }
+        let count = rx
+            .try_iter()
+            .filter(|e| matches!(e, PlaybackEvent::BatteryLow { .. }))
+            .count();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_battery_check_keeps_button_presses() {
+        let device = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Right, device.clone()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+        let (action_tx, action_rx) = mpsc::channel();
+        let controls = PlaybackControls {
+            actions: Some(action_tx),
+            ..PlaybackControls::default()
+        };
+        let mut state = InputState::default();
+        state.buttons.insert(Button::A);
+        device.push_input(state.to_report());
+
+        warn_low_battery(&mut joycons, BatteryLevel::Low, &controls, LONG_SONG);
+        assert_eq!(
+            action_rx.try_iter().collect::<Vec<_>>(),
+            [PlaybackAction::TogglePause]
+        );
+    }
+
+    #[test]
+    fn test_dropped_joycon_reconnects_mid_song() {
+        let device = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Left, device.clone()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+        let (tracks, plan, selection) = parse_midi_to_rumble(&two_part_song(96), 1).unwrap();
+        let (events, rx) = EventSender::channel();
+        let controls = PlaybackControls {
+            events,
+            ..PlaybackControls::new(&selection)
+        };
+
+        device.disconnect();
+        device.clear();
+        let plug_in = {
+            let device = device.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                device.reconnect();
+            })
+        };
+        play_tracks(&mut joycons, &tracks, &plan, &controls, Some(&manager)).unwrap();
+        plug_in.join().unwrap();
+
+        let events: Vec<PlaybackEvent> = rx.try_iter().collect();
+        let failed = events
+            .iter()
+            .position(|e| matches!(e, PlaybackEvent::DeviceError { joycon: 0, .. }));
+        let reconnected = events
+            .iter()
+            .position(|e| matches!(e, PlaybackEvent::Reconnected { joycon: 0 }));
+        assert!(failed.is_some());
+        assert!(failed < reconnected);
+        assert!(device.reports().iter().any(|r| r.is_rumble()));
+
+        // Without a way to reconnect, the song fails.
+        device.disconnect();
+        controls.clock.reset();
+        assert!(play_rumble_tracks(&mut joycons, &tracks, &plan, &controls,).is_err());
+    }
+
+    #[test]
+    fn test_pro_controller_plays_two_parts() {
+        let pro = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::ProController, pro.clone()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+        assert_eq!(count_outputs(&joycons), 2);
+
+        let (tracks, plan, selection) =
+            parse_midi_to_rumble(&two_part_song(96), count_outputs(&joycons)).unwrap();
+        let (events, rx) = EventSender::channel();
+        play_rumble_tracks(
+            &mut joycons,
+            &tracks,
+            &plan,
+            &PlaybackControls {
+                events,
+                ..PlaybackControls::new(&selection)
+            },
+        )
+        .unwrap();
+
+        let notes: Vec<usize> = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                PlaybackEvent::NoteStarted { joycon, .. } => Some(joycon),
+                _ => None,
+            })
+            .collect();
+        for joycon in 0..2 {
+            assert_eq!(notes.iter().filter(|&&j| j == joycon).count(), 4);
+        }
+
+        // Both motors end silent, and melody and bass sounded together,
+        // one on each motor.
+        let reports = pro.reports();
+        let silent = &reports.last().unwrap().data[2..6];
+        assert_eq!(&reports.last().unwrap().data[6..10], silent);
+        assert!(reports.iter().any(|r| {
+            let (left, right) = (&r.data[2..6], &r.data[6..10]);
+            left != silent && right != silent && left != right
+        }));
+    }
+
+    #[test]
+    fn test_tilted_controller_bends_notes() {
+        use crate::joycon::{hd_rumble, RawImuSample};
+
+        let device = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new().with_device(JoyConType::Right, device.clone()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+        joycons[0].enable_imu().unwrap();
+        // Standing on end: a full bend up, here a tritone.
+        let upright = RawImuSample {
+            accel: [4096, 0, 0],
+            gyro: [0; 3],
+        };
+        for _ in 0..5 {
+            let mut report = InputState::default().to_report();
+            RawImuSample::write_report(&[upright; 3], &mut report);
+            device.push_input(report);
+        }
+        device.clear();
+
+        let (tracks, plan, selection) = parse_midi_to_rumble(&two_part_song(96), 1).unwrap();
+        let controls = PlaybackControls {
+            expression: Some(ExpressionOptions {
+                bend_range: 6.0,
+                ..ExpressionOptions::default()
+            }),
+            ..PlaybackControls::new(&selection)
+        };
+        play_rumble_tracks(&mut joycons, &tracks, &plan, &controls).unwrap();
+
+        // The last melody note, F5, sounds as B5.
+        let high_frequencies: Vec<u16> = device
+            .reports()
+            .iter()
+            .filter(|r| r.is_rumble())
+            .map(|r| u16::from(r.data[6]) | u16::from(r.data[7] & 0x01) << 8)
+            .collect();
+        assert!(high_frequencies.contains(&hd_rumble::encode_high_frequency(987.8)));
+        assert!(!high_frequencies.contains(&hd_rumble::encode_high_frequency(698.5)));
+    }
+
+    #[test]
+    fn test_conductor_sets_tempo_for_every_controller() {
+        use crate::joycon::RawImuSample;
+
+        let conducting = SimulatedDevice::new();
+        let following = SimulatedDevice::new();
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new()
+                .with_device(JoyConType::Left, conducting.clone())
+                .with_device(JoyConType::Right, following.clone()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+        joycons[0].enable_imu().unwrap();
+        // Two swings 250 ms apart: twice the song's 120 BPM.
+        let swing = RawImuSample {
+            accel: [0, 0, 4096],
+            gyro: [5714, 0, 0],
+        };
+        let still = RawImuSample {
+            accel: [0, 0, 4096],
+            gyro: [0; 3],
+        };
+        let samples: Vec<RawImuSample> = (0..102)
+            .map(|i| if i % 50 < 10 { swing } else { still })
+            .collect();
+        for chunk in samples.chunks(3) {
+            let mut report = InputState::default().to_report();
+            RawImuSample::write_report(chunk.try_into().unwrap(), &mut report);
+            conducting.push_input(report);
+        }
+
+        let (tracks, plan, selection) = parse_midi_to_rumble(&two_part_song(96), 2).unwrap();
+        let (events, rx) = EventSender::channel();
+        let controls = PlaybackControls {
+            conductor: Some(ConductorOptions::default()),
+            events,
+            ..PlaybackControls::new(&selection)
+        };
+        play_rumble_tracks(&mut joycons, &tracks, &plan, &controls).unwrap();
+
+        // Every tempo the conductor set is close to double, and the shared
+        // clock ends at the last one.
+        let tempos: Vec<f32> = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                PlaybackEvent::TempoChanged { tempo } => Some(tempo),
+                _ => None,
+            })
+            .collect();
+        assert!(!tempos.is_empty());
+        assert!(tempos.iter().all(|&tempo| tempo > 1.5), "{tempos:?}");
+        assert_eq!(tempos.last().copied(), Some(controls.clock.rate()));
+        assert!(joycons[0].is_imu_enabled());
+        assert!(!joycons[1].is_imu_enabled());
+        assert!(following.reports().iter().any(|r| r.is_rumble()));
+    }
+
+    #[test]
+    fn test_conductor_is_numbered_by_output() {
+        let manager = JoyConManager::with_backend(
+            SimulatedBackend::new()
+                .with_device(JoyConType::ProController, SimulatedDevice::new())
+                .with_device(JoyConType::Left, SimulatedDevice::new()),
+        );
+        let mut joycons = manager.scan_for_devices().unwrap();
+        let (tracks, plan, selection) =
+            parse_midi_to_rumble(&two_part_song(96), count_outputs(&joycons)).unwrap();
+        // Outputs 0 and 1 are the Pro Controller's motors; 2 is the Joy-Con.
+        let controls = PlaybackControls {
+            conductor: Some(ConductorOptions {
+                joycon: 2,
+                ..ConductorOptions::default()
+            }),
+            ..PlaybackControls::new(&selection)
+        };
+        play_rumble_tracks(&mut joycons, &tracks, &plan, &controls).unwrap();
+
+        assert!(!joycons[0].is_imu_enabled());
+        assert!(joycons[1].is_imu_enabled());
+    }
 }
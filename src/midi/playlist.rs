@@ -0,0 +1,231 @@
+//! Playlists built from files, directories and `.m3u` lists.
+//!
+//! A [`Playlist`] is an ordered list of MIDI file paths. It is consumed by
+//! [`play_playlist`](super::play_playlist), which keeps the Joy-Con
+//! connection open between songs so there is no reconnect or initialization
+//! chime between tracks.
+//!
+//! # Sources
+//!
+//! [`Playlist::from_paths`] expands each path it is given:
+//!
+//! - **Directories** contribute their `.mid`/`.midi` files, sorted by name
+//!   (not recursive)
+//! - **`.m3u` / `.m3u8` files** contribute one entry per non-comment line,
+//!   resolved relative to the list's own directory
+//! - **Anything else** is taken as a MIDI file as-is
+//!
+//! # Example
+//!
+//! ```no_run
+//! use musical_joycons::midi::playlist::Playlist;
+//! use std::path::PathBuf;
+//!
+//! let playlist = Playlist::from_paths(&[PathBuf::from("songs/"), PathBuf::from("extra.m3u")])?;
+//! for path in playlist.entries() {
+//!     println!("{}", path.display());
+//! }
+//! # Ok::<(), musical_joycons::midi::playlist::PlaylistError>(())
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+/// Errors that can occur while building a playlist.
+#[derive(Debug, Error)]
+pub enum PlaylistError {
+    /// A directory or `.m3u` list could not be read.
+    #[error("Failed to read {path:?}: {source}")]
+    Io {
+        /// The directory or list being read.
+        path: PathBuf,
+        /// The underlying I/O error.
+        source: std::io::Error,
+    },
+
+    /// Expansion produced no files to play.
+    #[error("No MIDI files found")]
+    Empty,
+}
+
+/// What happens when playback reaches the end of a song.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatMode {
+    /// Stop after the last song.
+    #[default]
+    Off,
+    /// Replay the current song until skipped.
+    One,
+    /// Start over from the first song after the last one.
+    All,
+}
+
+/// An ordered list of MIDI files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Playlist {
+    entries: Vec<PathBuf>,
+}
+
+impl Playlist {
+    /// Creates a playlist from paths that are already MIDI files.
+    pub fn new(entries: Vec<PathBuf>) -> Self {
+        Self { entries }
+    }
+
+    /// Builds a playlist by expanding files, directories and `.m3u` lists.
+    ///
+    /// # Errors
+    ///
+    /// - [`PlaylistError::Io`] - A directory or list could not be read
+    /// - [`PlaylistError::Empty`] - No files were found
+    pub fn from_paths(paths: &[PathBuf]) -> Result<Self, PlaylistError> {
+        let mut entries = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                entries.extend(read_directory(path)?);
+            } else if has_extension(path, &["m3u", "m3u8"]) {
+                entries.extend(read_m3u(path)?);
+            } else {
+                entries.push(path.clone());
+            }
+        }
+
+        if entries.is_empty() {
+            return Err(PlaylistError::Empty);
+        }
+        Ok(Self { entries })
+    }
+
+    /// The files in playlist order.
+    pub fn entries(&self) -> &[PathBuf] {
+        &self.entries
+    }
+
+    /// Number of files in the playlist.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the playlist has no files.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the play order as indices into [`entries`](Self::entries).
+    ///
+    /// When `shuffle` is set the order is a permutation derived from `seed`;
+    /// the same seed always gives the same order.
+    pub fn order(&self, shuffle: bool, seed: u64) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        if shuffle {
+            // Fisher–Yates with a xorshift generator; good enough for
+            // shuffling songs and avoids an extra dependency.
+            let mut state = seed | 1;
+            for i in (1..order.len()).rev() {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                order.swap(i, (state % (i as u64 + 1)) as usize);
+            }
+        }
+        order
+    }
+}
+
+/// A shuffle seed taken from the system clock.
+pub(crate) fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| extensions.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+fn read_directory(dir: &Path) -> Result<Vec<PathBuf>, PlaylistError> {
+    let io_error = |source| PlaylistError::Io {
+        path: dir.to_path_buf(),
+        source,
+    };
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        if path.is_file() && has_extension(&path, &["mid", "midi"]) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn read_m3u(list: &Path) -> Result<Vec<PathBuf>, PlaylistError> {
+    let contents = fs::read_to_string(list).map_err(|source| PlaylistError::Io {
+        path: list.to_path_buf(),
+        source,
+    })?;
+    let base = list.parent().unwrap_or(Path::new(""));
+    Ok(parse_m3u(&contents, base))
+}
+
+/// Parses `.m3u` contents; relative entries are joined onto `base`.
+fn parse_m3u(contents: &str, base: &Path) -> Vec<PathBuf> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| base.join(line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn m3u_skips_comments_and_resolves_relative_paths() {
+        let contents =
+            "#EXTM3U\n#EXTINF:123,Title\nsong.mid\n\n  sub/other.mid  \n/abs/third.mid\n";
+        let entries = parse_m3u(contents, Path::new("/music"));
+        assert_eq!(
+            entries,
+            vec![
+                PathBuf::from("/music/song.mid"),
+                PathBuf::from("/music/sub/other.mid"),
+                PathBuf::from("/abs/third.mid"),
+            ]
+        );
+    }
+
+    #[test]
+    fn directory_lists_sorted_midi_files() {
+        let dir = std::env::temp_dir().join(format!("mj-playlist-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["b.mid", "a.MIDI", "notes.txt"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let playlist = Playlist::from_paths(std::slice::from_ref(&dir)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(playlist.entries(), &[dir.join("a.MIDI"), dir.join("b.mid")]);
+    }
+
+    #[test]
+    fn shuffle_is_a_seeded_permutation() {
+        let playlist = Playlist::new((0..8).map(|i| PathBuf::from(format!("{i}.mid"))).collect());
+        assert_eq!(playlist.order(false, 42), (0..8).collect::<Vec<_>>());
+
+        let shuffled = playlist.order(true, 42);
+        assert_eq!(shuffled, playlist.order(true, 42));
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..8).collect::<Vec<_>>());
+    }
+}
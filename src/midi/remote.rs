@@ -0,0 +1,282 @@
+//! Playback control from the Joy-Cons themselves.
+//!
+//! Every playback control — the keyboard shortcuts and Joy-Con buttons
+//! alike — is a [`PlaybackAction`]. A [`ButtonMap`] decides which button
+//! triggers which action, so the controllers work as self-contained remotes
+//! during a performance. The default map uses the same layout on both
+//! Joy-Cons:
+//!
+//! | Right Joy-Con | Left Joy-Con | Action |
+//! |---------------|--------------|--------|
+//! | `A` | `→` | Pause / resume |
+//! | `X` | `↑` | Swap L/R assignment |
+//! | `Y` | `←` | Cycle primary candidate |
+//! | `B` | `↓` | Cycle secondary candidate |
+//! | `R` | `L` | Next section |
+//! | `ZR` | `ZL` | Previous section |
+//! | `+` | `−` | Volume up / down |
+//! | `Home` | `Capture` | Quit |
+//!
+//! # Example
+//!
+//! ```
+//! use musical_joycons::joycon::Button;
+//! use musical_joycons::midi::remote::{ButtonMap, PlaybackAction};
+//!
+//! let mut map = ButtonMap::default();
+//! map.bind(Button::Home, "next-song".parse()?);
+//! map.unbind(Button::Capture);
+//!
+//! assert_eq!(map.action(Button::Home), Some(PlaybackAction::NextSong));
+//! assert_eq!(map.action(Button::Capture), None);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::joycon::{Button, Buttons};
+
+/// How much one volume step changes the master volume.
+pub const VOLUME_STEP: f32 = 0.1;
+
+/// Something a keyboard shortcut or Joy-Con button does during playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackAction {
+    /// Moves the primary part to the other Joy-Con side.
+    Swap,
+    /// Moves the primary part to the next candidate.
+    CyclePrimary,
+    /// Moves the secondary part to the next candidate.
+    CycleSecondary,
+    /// Pauses or resumes.
+    TogglePause,
+    /// Seeks back five seconds.
+    SeekBack,
+    /// Seeks forward five seconds.
+    SeekForward,
+    /// Jumps to the start of the current or previous section.
+    PreviousSection,
+    /// Jumps to the next section.
+    NextSection,
+    /// Goes back to the previous song.
+    PreviousSong,
+    /// Skips to the next song.
+    NextSong,
+    /// Raises the master volume by [`VOLUME_STEP`].
+    VolumeUp,
+    /// Lowers the master volume by [`VOLUME_STEP`].
+    VolumeDown,
+    /// Stops playback.
+    Quit,
+}
+
+impl PlaybackAction {
+    /// Every action.
+    pub const ALL: [PlaybackAction; 13] = [
+        PlaybackAction::Swap,
+        PlaybackAction::CyclePrimary,
+        PlaybackAction::CycleSecondary,
+        PlaybackAction::TogglePause,
+        PlaybackAction::SeekBack,
+        PlaybackAction::SeekForward,
+        PlaybackAction::PreviousSection,
+        PlaybackAction::NextSection,
+        PlaybackAction::PreviousSong,
+        PlaybackAction::NextSong,
+        PlaybackAction::VolumeUp,
+        PlaybackAction::VolumeDown,
+        PlaybackAction::Quit,
+    ];
+
+    /// Lower-case name used in configuration, e.g. `"cycle-primary"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            PlaybackAction::Swap => "swap",
+            PlaybackAction::CyclePrimary => "cycle-primary",
+            PlaybackAction::CycleSecondary => "cycle-secondary",
+            PlaybackAction::TogglePause => "pause",
+            PlaybackAction::SeekBack => "seek-back",
+            PlaybackAction::SeekForward => "seek-forward",
+            PlaybackAction::PreviousSection => "previous-section",
+            PlaybackAction::NextSection => "next-section",
+            PlaybackAction::PreviousSong => "previous-song",
+            PlaybackAction::NextSong => "next-song",
+            PlaybackAction::VolumeUp => "volume-up",
+            PlaybackAction::VolumeDown => "volume-down",
+            PlaybackAction::Quit => "quit",
+        }
+    }
+}
+
+impl fmt::Display for PlaybackAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error returned when parsing an unknown [`PlaybackAction`] name.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown playback action {0:?}")]
+pub struct ParseActionError(String);
+
+impl FromStr for PlaybackAction {
+    type Err = ParseActionError;
+
+    /// Parses a [`name`](PlaybackAction::name), ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PlaybackAction::ALL
+            .into_iter()
+            .find(|action| action.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| ParseActionError(s.to_string()))
+    }
+}
+
+/// Which Joy-Con button triggers which [`PlaybackAction`].
+///
+/// The default is the layout in the [module documentation](self);
+/// [`ButtonMap::empty`] ignores every button.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ButtonMap {
+    bindings: Vec<(Button, PlaybackAction)>,
+}
+
+impl Default for ButtonMap {
+    fn default() -> Self {
+        use PlaybackAction::*;
+
+        // Each action sits on a right-side button and its left-side mirror.
+        let bindings = vec![
+            (Button::A, TogglePause),
+            (Button::Right, TogglePause),
+            (Button::X, Swap),
+            (Button::Up, Swap),
+            (Button::Y, CyclePrimary),
+            (Button::Left, CyclePrimary),
+            (Button::B, CycleSecondary),
+            (Button::Down, CycleSecondary),
+            (Button::R, NextSection),
+            (Button::L, NextSection),
+            (Button::Zr, PreviousSection),
+            (Button::Zl, PreviousSection),
+            (Button::Plus, VolumeUp),
+            (Button::Minus, VolumeDown),
+            (Button::Home, Quit),
+            (Button::Capture, Quit),
+        ];
+        Self { bindings }
+    }
+}
+
+impl ButtonMap {
+    /// A map with no buttons bound.
+    pub fn empty() -> Self {
+        Self {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Makes `button` trigger `action`, replacing any previous binding.
+    pub fn bind(&mut self, button: Button, action: PlaybackAction) {
+        self.unbind(button);
+        self.bindings.push((button, action));
+    }
+
+    /// Makes `button` do nothing.
+    pub fn unbind(&mut self, button: Button) {
+        self.bindings.retain(|&(b, _)| b != button);
+    }
+
+    /// The action bound to `button`, if any.
+    pub fn action(&self, button: Button) -> Option<PlaybackAction> {
+        self.bindings
+            .iter()
+            .find(|&&(b, _)| b == button)
+            .map(|&(_, action)| action)
+    }
+
+    /// Returns `true` if no button is bound.
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    /// The actions for a set of newly pressed buttons, in button order.
+    pub fn actions(&self, pressed: Buttons) -> impl Iterator<Item = PlaybackAction> + '_ {
+        pressed.iter().filter_map(|button| self.action(button))
+    }
+}
+
+/// A master volume in `0.0..=1.0`, shared between threads.
+#[derive(Debug)]
+pub struct Volume(AtomicU32);
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Volume {
+    /// Creates a volume at `level`, clamped to `0.0..=1.0`.
+    pub fn new(level: f32) -> Self {
+        Self(AtomicU32::new(level.clamp(0.0, 1.0).to_bits()))
+    }
+
+    /// The current level.
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Sets the level, clamped to `0.0..=1.0`.
+    pub fn set(&self, level: f32) {
+        self.0
+            .store(level.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Changes the level by `delta` and returns the new level.
+    pub fn adjust(&self, delta: f32) -> f32 {
+        let level = (self.get() + delta).clamp(0.0, 1.0);
+        self.set(level);
+        level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_map_covers_both_joycons() {
+        let map = ButtonMap::default();
+        assert_eq!(map.action(Button::A), Some(PlaybackAction::TogglePause));
+        assert_eq!(map.action(Button::Right), Some(PlaybackAction::TogglePause));
+        assert_eq!(map.action(Button::Plus), Some(PlaybackAction::VolumeUp));
+        assert_eq!(map.action(Button::Minus), Some(PlaybackAction::VolumeDown));
+        assert_eq!(map.action(Button::LeftStick), None);
+
+        let pressed: Buttons = [Button::Home, Button::X].into_iter().collect();
+        assert_eq!(
+            map.actions(pressed).collect::<Vec<_>>(),
+            [PlaybackAction::Swap, PlaybackAction::Quit]
+        );
+        assert!(ButtonMap::empty().is_empty());
+    }
+
+    #[test]
+    fn action_names_round_trip() {
+        for action in PlaybackAction::ALL {
+            assert_eq!(action.name().parse(), Ok(action));
+        }
+        assert!("louder".parse::<PlaybackAction>().is_err());
+    }
+
+    #[test]
+    fn volume_is_clamped() {
+        let volume = Volume::default();
+        assert_eq!(volume.adjust(0.5), 1.0);
+        assert!((volume.adjust(-0.25) - 0.75).abs() < 1e-6);
+        volume.set(-3.0);
+        assert_eq!(volume.get(), 0.0);
+    }
+}
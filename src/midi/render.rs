@@ -0,0 +1,408 @@
+//! Offline rendering of rumble playback to audio.
+//!
+//! Synthesizes what each Joy-Con would play as a sine wave at the commanded
+//! `frequency`, scaled by `amplitude` (plus a second sine for a two-voice
+//! command's [`low_band`](RumbleCommand::low_band)), and writes the result as a 16-bit
+//! stereo WAV file. The left channel is the Left Joy-Con and the right
+//! channel the Right Joy-Con, so part selection and section switching can
+//! be auditioned on a machine with no controllers.
+//!
+//! # Channel Assignment
+//!
+//! Each side starts on the part chosen by a fresh [`JoyConBinding`] (primary
+//! on the right) and follows the [`PlaybackPlan`] at every later section
+//! boundary: the primary side takes the plan's melody slot, the other side
+//! the complement slot.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use musical_joycons::midi::render::render_midi_file;
+//! use musical_joycons::midi::ConversionOptions;
+//! use std::path::Path;
+//!
+//! render_midi_file(
+//!     Path::new("song.mid"),
+//!     Path::new("song.wav"),
+//!     &ConversionOptions::default(),
+//! )?;
+//! # Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use super::playback::{JoyConBinding, JoyConSide};
+use super::rumble::{parse_midi_to_rumble_with, ConversionOptions, RumbleCommand, RumbleTrack};
+use super::scoring::PartSelection;
+use super::track_types::PlaybackPlan;
+
+/// Default output sample rate in Hz.
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+/// Peak output level, leaving headroom below full scale.
+const OUTPUT_GAIN: f32 = 0.8;
+
+/// Amplitude smoothing time constant, to avoid clicks on note boundaries.
+const SMOOTHING_SECS: f32 = 0.002;
+
+/// A constant frequency/amplitude span on one Joy-Con.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    start: Duration,
+    end: Duration,
+    frequency: f32,
+    amplitude: f32,
+    low_band: Option<(f32, f32)>,
+}
+
+/// Stereo audio rendered from rumble tracks.
+#[derive(Debug, Clone)]
+pub struct RenderedAudio {
+    /// Samples per second.
+    pub sample_rate: u32,
+    /// Interleaved frames as `[left, right]`, each in `-1.0..=1.0`.
+    pub frames: Vec<[f32; 2]>,
+}
+
+impl RenderedAudio {
+    /// Length of the rendered audio.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames.len() as f64 / self.sample_rate as f64)
+    }
+
+    /// Writes the audio as a 16-bit PCM stereo WAV stream.
+    pub fn write_wav<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        const CHANNELS: u16 = 2;
+        const BITS_PER_SAMPLE: u16 = 16;
+        let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+        let byte_rate = self.sample_rate * block_align as u32;
+        let data_len = (self.frames.len() * block_align as usize) as u32;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(36 + data_len).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?; // PCM
+        writer.write_all(&CHANNELS.to_le_bytes())?;
+        writer.write_all(&self.sample_rate.to_le_bytes())?;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&data_len.to_le_bytes())?;
+        for frame in &self.frames {
+            for sample in frame {
+                let pcm = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+                writer.write_all(&pcm.to_le_bytes())?;
+            }
+        }
+        writer.flush()
+    }
+}
+
+/// Converts a command list into absolute-time segments.
+///
+/// Each command holds from its fire time until the next command fires; the
+/// last command holds until `end`.
+fn command_segments(commands: &[RumbleCommand], end: Duration) -> Vec<Segment> {
+    let mut segments = Vec::with_capacity(commands.len());
+    let mut time = Duration::ZERO;
+    for (idx, cmd) in commands.iter().enumerate() {
+        time += cmd.wait_before;
+        let next = commands
+            .get(idx + 1)
+            .map(|c| time + c.wait_before)
+            .unwrap_or(end.max(time));
+        if next > time {
+            segments.push(Segment {
+                start: time,
+                end: next,
+                frequency: cmd.frequency,
+                amplitude: cmd.amplitude,
+                low_band: cmd.low_band,
+            });
+        }
+    }
+    segments
+}
+
+/// Builds the segment timeline one Joy-Con side would play.
+fn side_timeline(
+    tracks: &[RumbleTrack],
+    plan: &PlaybackPlan,
+    selection: &PartSelection,
+    side: JoyConSide,
+) -> Vec<Segment> {
+    let binding = JoyConBinding::new(selection);
+    let plan_slot = if binding.track_for_side(side) == binding.primary_part_idx {
+        0
+    } else {
+        1
+    };
+
+    // (window start, track index) — the first window follows the binding,
+    // later windows follow the plan.
+    let mut windows = vec![(Duration::ZERO, binding.track_for_side(side))];
+    for section in plan.sections.iter().filter(|s| !s.start_time.is_zero()) {
+        windows.push((
+            section.start_time,
+            plan.track_for(plan_slot, section.start_time),
+        ));
+    }
+
+    let song_end = tracks
+        .iter()
+        .map(|t| t.total_duration)
+        .max()
+        .unwrap_or(Duration::ZERO);
+
+    let mut timeline = Vec::new();
+    for (i, &(window_start, track_idx)) in windows.iter().enumerate() {
+        let window_end = windows.get(i + 1).map(|w| w.0).unwrap_or(song_end);
+        let Some(track) = tracks.get(track_idx) else {
+            continue;
+        };
+        for seg in command_segments(&track.commands, track.total_duration) {
+            let start = seg.start.max(window_start);
+            let end = seg.end.min(window_end);
+            if start < end {
+                timeline.push(Segment { start, end, ..seg });
+            }
+        }
+    }
+    timeline
+}
+
+/// One smoothed sine oscillator.
+#[derive(Debug, Default)]
+struct Oscillator {
+    phase: f32,
+    level: f32,
+}
+
+impl Oscillator {
+    fn next(&mut self, frequency: f32, amplitude: f32, dt: f32, smoothing: f32) -> f32 {
+        self.level += (amplitude.clamp(0.0, 1.0) - self.level) * smoothing;
+        self.phase = (self.phase + frequency.max(0.0) * dt).fract();
+        (self.phase * std::f32::consts::TAU).sin() * self.level
+    }
+}
+
+/// Synthesizes a mono sine signal from a segment timeline.
+fn synthesize(timeline: &[Segment], sample_rate: u32, frames: usize) -> Vec<f32> {
+    let mut samples = vec![0.0f32; frames];
+    let dt = 1.0 / sample_rate as f32;
+    let smoothing = 1.0 - (-dt / SMOOTHING_SECS).exp();
+
+    let mut high = Oscillator::default();
+    let mut low = Oscillator::default();
+    let mut seg_idx = 0;
+
+    for (n, sample) in samples.iter_mut().enumerate() {
+        let t = Duration::from_secs_f64(n as f64 / sample_rate as f64);
+        while seg_idx < timeline.len() && timeline[seg_idx].end <= t {
+            seg_idx += 1;
+        }
+        let (frequency, amplitude, low_band) = match timeline.get(seg_idx) {
+            Some(seg) if seg.start <= t => (seg.frequency, seg.amplitude, seg.low_band),
+            _ => (0.0, 0.0, None),
+        };
+        let (low_frequency, low_amplitude) = low_band.unwrap_or((0.0, 0.0));
+
+        let mix = high.next(frequency, amplitude, dt, smoothing)
+            + low.next(low_frequency, low_amplitude, dt, smoothing);
+        // Two voices share the headroom of one.
+        let headroom = (high.level + low.level).max(1.0);
+        *sample = mix / headroom * OUTPUT_GAIN;
+    }
+    samples
+}
+
+/// Renders rumble tracks to stereo audio following `plan` and `selection`.
+///
+/// The result covers the whole song: every track's `total_duration`.
+pub fn render_rumble(
+    tracks: &[RumbleTrack],
+    plan: &PlaybackPlan,
+    selection: &PartSelection,
+    sample_rate: u32,
+) -> RenderedAudio {
+    let left = side_timeline(tracks, plan, selection, JoyConSide::Left);
+    let right = side_timeline(tracks, plan, selection, JoyConSide::Right);
+
+    let end = left
+        .iter()
+        .chain(right.iter())
+        .map(|s| s.end)
+        .max()
+        .unwrap_or(Duration::ZERO);
+    let frames = (end.as_secs_f64() * sample_rate as f64).ceil() as usize;
+
+    let left = synthesize(&left, sample_rate, frames);
+    let right = synthesize(&right, sample_rate, frames);
+
+    RenderedAudio {
+        sample_rate,
+        frames: left.into_iter().zip(right).map(|(l, r)| [l, r]).collect(),
+    }
+}
+
+/// Parses a MIDI file and renders it to a stereo WAV file.
+///
+/// Uses the same conversion as live playback with two Joy-Cons;
+/// `options.num_joycons` is ignored.
+pub fn render_midi_file(
+    input: &Path,
+    output: &Path,
+    options: &ConversionOptions,
+) -> Result<RenderedAudio, Box<dyn std::error::Error + Send + Sync>> {
+    let midi_data = std::fs::read(input)?;
+    let conversion = ConversionOptions {
+        num_joycons: 2,
+        ..options.clone()
+    };
+    let (tracks, plan, selection) = parse_midi_to_rumble_with(&midi_data, &conversion)?;
+
+    let audio = render_rumble(&tracks, &plan, &selection, DEFAULT_SAMPLE_RATE);
+    audio.write_wav(BufWriter::new(File::create(output)?))?;
+
+    log::info!(
+        "💾 Rendered {:.1}s of audio to {:?}",
+        audio.duration().as_secs_f32(),
+        output
+    );
+    Ok(audio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::track_types::{SectionAssignment, TrackMetrics};
+
+    fn cmd(frequency: f32, amplitude: f32, wait_ms: u64) -> RumbleCommand {
+        RumbleCommand {
+            frequency,
+            amplitude,
+            wait_before: Duration::from_millis(wait_ms),
+            low_band: None,
+        }
+    }
+
+    fn track(commands: Vec<RumbleCommand>, total_ms: u64) -> RumbleTrack {
+        RumbleTrack {
+            commands,
+            total_duration: Duration::from_millis(total_ms),
+            switch_points: Vec::new(),
+            track_index: 0,
+            metrics: TrackMetrics::default(),
+        }
+    }
+
+    fn selection(primary: usize, secondary: usize) -> PartSelection {
+        PartSelection {
+            primary,
+            secondary,
+            primary_candidates: vec![primary],
+            secondary_candidates: vec![secondary],
+        }
+    }
+
+    fn single_section_plan() -> PlaybackPlan {
+        PlaybackPlan::new(vec![SectionAssignment {
+            start_time: Duration::ZERO,
+            track_indices: vec![0, 1],
+        }])
+    }
+
+    fn peak(samples: impl Iterator<Item = f32>) -> f32 {
+        samples.fold(0.0, |acc, s| acc.max(s.abs()))
+    }
+
+    #[test]
+    fn command_segments_honor_wait_before() {
+        let commands = vec![cmd(0.0, 0.0, 0), cmd(440.0, 1.0, 100), cmd(0.0, 0.0, 200)];
+        let segments = command_segments(&commands, Duration::from_millis(300));
+
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].start, Duration::ZERO);
+        assert_eq!(segments[0].end, Duration::from_millis(100));
+        assert_eq!(segments[1].start, Duration::from_millis(100));
+        assert_eq!(segments[1].end, Duration::from_millis(300));
+        assert_eq!(segments[1].frequency, 440.0);
+    }
+
+    #[test]
+    fn primary_renders_on_right_channel() {
+        let tracks = vec![
+            track(vec![cmd(440.0, 1.0, 0), cmd(0.0, 0.0, 100)], 100),
+            track(vec![cmd(0.0, 0.0, 0), cmd(0.0, 0.0, 100)], 100),
+        ];
+        let audio = render_rumble(&tracks, &single_section_plan(), &selection(0, 1), 8_000);
+
+        assert_eq!(audio.frames.len(), 800);
+        assert!(peak(audio.frames.iter().map(|f| f[1])) > 0.5);
+        assert_eq!(peak(audio.frames.iter().map(|f| f[0])), 0.0);
+    }
+
+    #[test]
+    fn rendered_frequency_matches_command() {
+        let tracks = vec![track(vec![cmd(500.0, 1.0, 0), cmd(0.0, 0.0, 1000)], 1000)];
+        let audio = render_rumble(&tracks, &single_section_plan(), &selection(0, 0), 8_000);
+
+        let crossings = audio
+            .frames
+            .windows(2)
+            .filter(|w| w[0][1] <= 0.0 && w[1][1] > 0.0)
+            .count();
+        assert!((495..=505).contains(&crossings), "{crossings} crossings");
+    }
+
+    #[test]
+    fn later_sections_follow_the_plan() {
+        let tracks = vec![
+            track(vec![cmd(440.0, 1.0, 0), cmd(0.0, 0.0, 500)], 500),
+            track(vec![cmd(0.0, 0.0, 0), cmd(600.0, 1.0, 500)], 1000),
+        ];
+        let plan = PlaybackPlan::new(vec![
+            SectionAssignment {
+                start_time: Duration::ZERO,
+                track_indices: vec![0, 1],
+            },
+            SectionAssignment {
+                start_time: Duration::from_millis(500),
+                track_indices: vec![1, 0],
+            },
+        ]);
+        let audio = render_rumble(&tracks, &plan, &selection(0, 1), 8_000);
+
+        // The right (primary) side moves onto part 1 for the second half.
+        let second_half = &audio.frames[4_100..];
+        assert!(peak(second_half.iter().map(|f| f[1])) > 0.5);
+        assert_eq!(peak(second_half.iter().map(|f| f[0])), 0.0);
+    }
+
+    #[test]
+    fn wav_header_is_well_formed() {
+        let audio = RenderedAudio {
+            sample_rate: 8_000,
+            frames: vec![[0.0, 0.5]; 10],
+        };
+        let mut buf = Vec::new();
+        audio.write_wav(&mut buf).unwrap();
+
+        assert_eq!(buf.len(), 44 + 10 * 4);
+        assert_eq!(&buf[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(buf[4..8].try_into().unwrap()), 36 + 40);
+        assert_eq!(&buf[8..12], b"WAVE");
+        assert_eq!(u16::from_le_bytes(buf[22..24].try_into().unwrap()), 2);
+        assert_eq!(u32::from_le_bytes(buf[24..28].try_into().unwrap()), 8_000);
+        assert_eq!(u32::from_le_bytes(buf[40..44].try_into().unwrap()), 40);
+        assert_eq!(i16::from_le_bytes(buf[46..48].try_into().unwrap()), 16_384);
+    }
+}
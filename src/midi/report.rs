@@ -0,0 +1,195 @@
+//! Machine-readable song analysis.
+//!
+//! [`analyze_midi`] runs the same pipeline as
+//! [`parse_midi_to_rumble_with`](super::parse_midi_to_rumble_with) but
+//! returns everything it decided as an [`AnalysisReport`] instead of
+//! printing it. The report derives `Serialize`, so tooling can consume it as
+//! JSON via [`AnalysisReport::to_json`].
+//!
+//! # Index Spaces
+//!
+//! All part indices in the report — `parts[].index`, [`PartSelection`]
+//! fields and `sections[].parts` — refer to the `parts` list, i.e. the same
+//! numbers accepted by `--primary`/`--secondary`. `rumble_tracks` lists the
+//! part index behind each converted rumble track.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use musical_joycons::midi::report::analyze_midi;
+//! use musical_joycons::midi::ConversionOptions;
+//!
+//! let midi_data = std::fs::read("song.mid")?;
+//! let report = analyze_midi(&midi_data, &ConversionOptions::default())?;
+//! println!("{}", report.to_json()?);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use serde::Serialize;
+
+use super::parts::PartKey;
+use super::rumble::{analyze_song, ConversionOptions, ParseError};
+use super::scoring::{primary_score, secondary_score, PartSelection};
+use super::track_analysis::PartFeatures;
+
+/// Analysis of one MIDI part.
+#[derive(Debug, Clone, Serialize)]
+pub struct PartReport {
+    /// Index of this part in [`AnalysisReport::parts`].
+    pub index: usize,
+    /// MIDI channel and program.
+    pub key: PartKey,
+    /// Track name, if the file has one.
+    pub name: Option<String>,
+    /// Number of notes in the part.
+    pub note_count: usize,
+    /// Full feature set used for scoring.
+    pub features: PartFeatures,
+    /// Melody-likeness score.
+    pub primary_score: f32,
+    /// Accompaniment score relative to the selected primary part.
+    pub secondary_score: f32,
+}
+
+/// One section of the playback plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct SectionReport {
+    /// Section start in seconds.
+    pub start_secs: f64,
+    /// Section end in seconds.
+    pub end_secs: f64,
+    /// Part index per Joy-Con slot (slot 0 plays the melody).
+    pub parts: Vec<usize>,
+    /// Part names per Joy-Con slot; `"unnamed"` when the file has none.
+    pub part_names: Vec<String>,
+}
+
+/// Everything the analysis decided about a song.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisReport {
+    /// Song length in seconds, after tempo scaling.
+    pub duration_secs: f64,
+    /// Every part in the file.
+    pub parts: Vec<PartReport>,
+    /// Primary/secondary choice and ranked candidates.
+    pub selection: PartSelection,
+    /// Part index of each converted rumble track.
+    pub rumble_tracks: Vec<usize>,
+    /// Playback plan sections, in time order.
+    pub sections: Vec<SectionReport>,
+}
+
+impl AnalysisReport {
+    /// Serializes the report as pretty-printed JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Analyzes MIDI data and returns a serializable report.
+///
+/// Unlike [`parse_midi_to_rumble_with`](super::parse_midi_to_rumble_with),
+/// nothing is logged.
+///
+/// # Errors
+///
+/// The same as [`parse_midi_to_rumble_with`](super::parse_midi_to_rumble_with).
+pub fn analyze_midi(
+    midi_data: &[u8],
+    options: &ConversionOptions,
+) -> Result<AnalysisReport, ParseError> {
+    let song = analyze_song(midi_data, options)?;
+    let primary_features = &song.features[song.part_selection.primary];
+
+    let parts = song
+        .parts
+        .iter()
+        .zip(&song.features)
+        .enumerate()
+        .map(|(index, (part, features))| PartReport {
+            index,
+            key: part.key,
+            name: part.name.clone(),
+            note_count: part.notes.len(),
+            features: features.clone(),
+            primary_score: primary_score(features, &song.features),
+            secondary_score: secondary_score(features, primary_features, &song.features),
+        })
+        .collect();
+
+    let total_duration = song.total_duration();
+    let sections = song
+        .plan
+        .sections
+        .iter()
+        .enumerate()
+        .map(|(i, section)| {
+            let end = song
+                .plan
+                .sections
+                .get(i + 1)
+                .map(|next| next.start_time)
+                .unwrap_or(total_duration);
+            let part_indices: Vec<usize> = section
+                .track_indices
+                .iter()
+                .filter_map(|&track| song.candidate_indices.get(track).copied())
+                .collect();
+            SectionReport {
+                start_secs: section.start_time.as_secs_f64(),
+                end_secs: end.as_secs_f64(),
+                part_names: part_indices
+                    .iter()
+                    .map(|&p| {
+                        song.parts[p]
+                            .name
+                            .as_deref()
+                            .unwrap_or("unnamed")
+                            .to_string()
+                    })
+                    .collect(),
+                parts: part_indices,
+            }
+        })
+        .collect();
+
+    Ok(AnalysisReport {
+        duration_secs: total_duration.as_secs_f64(),
+        parts,
+        selection: song.part_selection,
+        rumble_tracks: song.candidate_indices,
+        sections,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::test_support::{single_track, two_part_events};
+    use midly::{MetaMessage, TrackEvent, TrackEventKind};
+
+    #[test]
+    fn report_uses_part_indices_and_serializes() {
+        let mut events = vec![TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Meta(MetaMessage::TrackName(b"Lead")),
+        }];
+        events.extend(two_part_events(240));
+        let report = analyze_midi(&single_track(events), &ConversionOptions::default()).unwrap();
+
+        assert_eq!(report.parts.len(), 2);
+        assert!(report.parts.iter().all(|p| p.note_count == 4));
+        assert!(report.rumble_tracks.contains(&report.selection.primary));
+        assert!(!report.sections.is_empty());
+        for section in &report.sections {
+            assert!(section.parts.iter().all(|&p| p < report.parts.len()));
+            assert_eq!(section.parts.len(), section.part_names.len());
+        }
+        assert!((report.duration_secs - 1.75).abs() < 0.01);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["parts"][0]["features"]["note_count"], 4);
+        assert!(json["selection"]["primary_candidates"].is_array());
+        assert!(json["sections"][0]["start_secs"].is_number());
+    }
+}
@@ -0,0 +1,992 @@
+//! Rhythm game mode.
+//!
+//! The game turns a song's melody into a chart: the notes of the selected
+//! primary part (see [`PartSelection`](super::scoring::PartSelection)),
+//! or the part forced with
+//! [`ConversionOptions::primary_part`], become [`Target`]s, and each player
+//! presses any button on their controller in time with them. Every output
+//! is one player with its own [`Scorer`], playing the whole chart. Outputs
+//! are numbered like [`count_outputs`](super::playback::count_outputs): a
+//! Joy-Con is one player, and each half of a Pro Controller is a player of
+//! its own, pressing that half's buttons and feeling that half's motor.
+//!
+//! A press within [`RhythmOptions::perfect_window`] of a target is
+//! [`Judgement::Perfect`], within [`RhythmOptions::hit_window`]
+//! [`Judgement::Good`]; a target whose window passes without a press is a
+//! [`Judgement::Miss`], and a press with no target near it is counted as
+//! stray. A count-in of [`RhythmOptions::count_in`] clicks on the song's
+//! beat comes first. Then the melody plays itself out on every player's
+//! motor: each target's note is cued softly at its time, unless the player
+//! already hit it. A hit sounds the note fully, a miss a low buzz.
+//!
+//! Cues, presses and misses are timed on one
+//! [`PlaybackClock`](super::PlaybackClock), each press when its report is
+//! read. [`RhythmGame`] holds the game itself, apart from the controllers.
+//!
+//! # Difficulty
+//!
+//! Notes that start together make one target, played by their top note.
+//! [`Difficulty`] then thins the targets to a rate: the part's onset rate
+//! comes from its [`PartFeatures`], as `notes_per_sec` over `chordiness`,
+//! and only enough onsets are kept, spread evenly over the song, to bring
+//! it down to [`Difficulty::target_rate`]. Targets closer together than
+//! half the interval at that rate are dropped too, so fast runs thin out
+//! more than the average.
+//!
+//! # Example
+//!
+//! ```
+//! use musical_joycons::midi::rhythm::{Judgement, Scorer, Target};
+//! use std::time::Duration;
+//!
+//! let ms = Duration::from_millis;
+//! let targets = [500, 1000, 1500].map(|time| Target {
+//!     time: ms(time),
+//!     duration: ms(200),
+//!     note: 72,
+//! });
+//! let mut scorer = Scorer::new(&targets, ms(50), ms(150));
+//!
+//! assert_eq!(scorer.press(ms(520)).unwrap().judgement, Judgement::Perfect);
+//! // Nobody pressed for the second target.
+//! let missed = scorer.expire(ms(1200));
+//! assert_eq!(missed[0].judgement, Judgement::Miss);
+//! let late = scorer.press(ms(1600)).unwrap();
+//! assert_eq!(late.judgement, Judgement::Good);
+//! assert!((late.offset.unwrap() - 0.1).abs() < 1e-6);
+//! assert_eq!(scorer.score().hits(), 2);
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::joycon::{Buttons, DeviceBackend, JoyConManager};
+
+use super::clock::PlaybackClock;
+use super::events::{EventSender, PlaybackEvent};
+use super::interactive::{run_interactive, Step};
+use super::parts::NoteObject;
+use super::playback::OutputTarget;
+use super::rumble::{analyze_song, note_to_frequency, ConversionOptions, ParseError};
+use super::track_analysis::PartFeatures;
+
+/// Shortest time between two targets on [`Difficulty::Expert`].
+const MIN_TARGET_GAP: Duration = Duration::from_millis(80);
+
+/// Count-in beat when the song has no beat grid (120 BPM).
+const DEFAULT_BEAT: Duration = Duration::from_millis(500);
+
+/// Amplitude of the note a hit sounds.
+const HIT_AMPLITUDE: f32 = 0.8;
+
+/// Amplitude of a target's note cued at its time.
+const CUE_AMPLITUDE: f32 = 0.3;
+
+/// Longest a hit sounds its note.
+const MAX_HIT_LENGTH: Duration = Duration::from_millis(300);
+
+/// Shortest a hit sounds its note.
+const MIN_HIT_LENGTH: Duration = Duration::from_millis(60);
+
+/// Frequency, amplitude and length of the buzz for a miss.
+const MISS_BUZZ: (f32, f32, Duration) = (90.0, 0.6, Duration::from_millis(150));
+
+/// Frequency, amplitude and length of a count-in click.
+const CLICK: (f32, f32, Duration) = (640.0, 0.5, Duration::from_millis(40));
+
+/// How many of the melody's notes the player has to hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    /// About one target a second.
+    Easy,
+    /// About two targets a second.
+    #[default]
+    Normal,
+    /// About four targets a second.
+    Hard,
+    /// Every onset of the melody.
+    Expert,
+}
+
+impl Difficulty {
+    /// Targets per second this difficulty thins a part down to, or `None`
+    /// to keep every onset.
+    pub fn target_rate(self) -> Option<f32> {
+        match self {
+            Difficulty::Easy => Some(1.0),
+            Difficulty::Normal => Some(2.0),
+            Difficulty::Hard => Some(4.0),
+            Difficulty::Expert => None,
+        }
+    }
+
+    /// Fraction of a part's onsets kept as targets, from its density
+    /// features.
+    pub fn keep_ratio(self, features: &PartFeatures) -> f32 {
+        let Some(rate) = self.target_rate() else {
+            return 1.0;
+        };
+        let onsets_per_sec = features.notes_per_sec / features.chordiness.max(1.0);
+        if onsets_per_sec <= rate {
+            1.0
+        } else {
+            rate / onsets_per_sec
+        }
+    }
+
+    /// Shortest time between two targets.
+    fn min_gap(self) -> Duration {
+        self.target_rate()
+            .map(|rate| Duration::from_secs_f32(0.5 / rate))
+            .unwrap_or(MIN_TARGET_GAP)
+    }
+}
+
+/// A note the player has to hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    /// Song position of the note.
+    pub time: Duration,
+    /// How long the note lasts.
+    pub duration: Duration,
+    /// MIDI note, after transposition.
+    pub note: u8,
+}
+
+/// The targets of one song.
+#[derive(Debug, Clone)]
+pub struct Chart {
+    /// Index of the part the targets come from, as accepted by
+    /// [`ConversionOptions::primary_part`].
+    pub part: usize,
+    /// Track name of that part, if the file has one.
+    pub name: Option<String>,
+    /// The targets, in time order.
+    pub targets: Vec<Target>,
+    /// Beat times of the song's tempo map.
+    pub beats: Vec<Duration>,
+}
+
+impl Chart {
+    /// Charts the primary part of a MIDI file at a difficulty.
+    ///
+    /// `options` picks the part, transposes and scales the tempo as for
+    /// playback.
+    ///
+    /// # Errors
+    ///
+    /// The errors of
+    /// [`parse_midi_to_rumble_with`](super::parse_midi_to_rumble_with).
+    pub fn from_midi(
+        midi_data: &[u8],
+        options: &ConversionOptions,
+        difficulty: Difficulty,
+    ) -> Result<Self, ParseError> {
+        let song = analyze_song(midi_data, options)?;
+        let part = song.part_selection.primary;
+        let targets = build_targets(
+            &song.parts[part].notes,
+            &song.features[part],
+            difficulty,
+            options.transpose,
+            |tick| song.tick_time(tick),
+        );
+        log::debug!(
+            "🥁 Charted {} of {} notes of part {} on {:?}",
+            targets.len(),
+            song.parts[part].notes.len(),
+            part,
+            difficulty
+        );
+        Ok(Self {
+            part,
+            name: song.parts[part].name.clone(),
+            targets,
+            beats: song.plan.beats,
+        })
+    }
+
+    /// Time between the first two beats of the song.
+    fn beat(&self) -> Duration {
+        match self.beats.as_slice() {
+            [first, second, ..] if second > first => *second - *first,
+            _ => DEFAULT_BEAT,
+        }
+    }
+}
+
+/// Turns a part's notes into targets: one per onset, thinned for the
+/// difficulty.
+fn build_targets(
+    notes: &[NoteObject],
+    features: &PartFeatures,
+    difficulty: Difficulty,
+    transpose: i32,
+    tick_time: impl Fn(u32) -> Duration,
+) -> Vec<Target> {
+    let mut notes: Vec<&NoteObject> = notes.iter().collect();
+    notes.sort_by_key(|note| (note.start_tick, std::cmp::Reverse(note.pitch)));
+
+    let ratio = difficulty.keep_ratio(features);
+    let min_gap = difficulty.min_gap();
+    let mut targets: Vec<Target> = Vec::new();
+    // Starts full, so the first onset is always a target.
+    let mut credit = 1.0f32;
+    let mut previous_tick = None;
+    for note in notes {
+        if previous_tick == Some(note.start_tick) {
+            continue;
+        }
+        previous_tick = Some(note.start_tick);
+
+        let time = tick_time(note.start_tick);
+        let spaced = targets
+            .last()
+            .is_none_or(|last| time.saturating_sub(last.time) >= min_gap);
+        if credit >= 1.0 - 1e-4 && spaced {
+            credit -= 1.0;
+            targets.push(Target {
+                time,
+                duration: tick_time(note.end_tick).saturating_sub(time),
+                note: (i32::from(note.pitch) + transpose).clamp(0, 127) as u8,
+            });
+        }
+        credit = (credit + ratio).min(1.0);
+    }
+    targets
+}
+
+/// How well a target was hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Judgement {
+    /// Within the perfect window.
+    Perfect,
+    /// Within the hit window.
+    Good,
+    /// Not pressed for.
+    Miss,
+}
+
+/// A judged target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Judged {
+    /// Index of the target.
+    pub target: usize,
+    /// How well it was hit.
+    pub judgement: Judgement,
+    /// Time from the target to the press, in seconds: negative when early,
+    /// positive when late. `None` for a miss.
+    pub offset: Option<f32>,
+}
+
+/// A player's tally.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Score {
+    /// Targets hit within the perfect window.
+    pub perfect: usize,
+    /// Targets hit within the hit window but not the perfect one.
+    pub good: usize,
+    /// Targets not pressed for.
+    pub missed: usize,
+    /// Presses with no target near them.
+    pub stray: usize,
+    /// Targets hit since the last miss.
+    pub combo: usize,
+    /// Longest run of hits.
+    pub max_combo: usize,
+    /// Sum of the hits' offsets, in seconds.
+    offset_sum: f32,
+}
+
+impl Score {
+    /// Targets hit.
+    pub fn hits(&self) -> usize {
+        self.perfect + self.good
+    }
+
+    /// Share of the judged targets that were hit, from `0.0` to `1.0`;
+    /// `0.0` before any target is judged.
+    pub fn accuracy(&self) -> f32 {
+        let judged = self.hits() + self.missed;
+        if judged == 0 {
+            0.0
+        } else {
+            self.hits() as f32 / judged as f32
+        }
+    }
+
+    /// Average time from a target to its press, in seconds: negative when
+    /// the player tends to be early. `None` before the first hit.
+    pub fn mean_offset(&self) -> Option<f32> {
+        (self.hits() > 0).then(|| self.offset_sum / self.hits() as f32)
+    }
+
+    fn record(&mut self, judged: &Judged) {
+        match judged.judgement {
+            Judgement::Perfect => self.perfect += 1,
+            Judgement::Good => self.good += 1,
+            Judgement::Miss => {
+                self.missed += 1;
+                self.combo = 0;
+                return;
+            }
+        }
+        self.combo += 1;
+        self.max_combo = self.max_combo.max(self.combo);
+        self.offset_sum += judged.offset.unwrap_or(0.0);
+    }
+}
+
+/// Judges one player's presses against a chart.
+#[derive(Debug, Clone)]
+pub struct Scorer {
+    /// Target times, in time order.
+    times: Vec<Duration>,
+    /// Which targets have been judged.
+    judged: Vec<bool>,
+    /// First target that may still be unjudged.
+    next: usize,
+    perfect_window: Duration,
+    hit_window: Duration,
+    score: Score,
+}
+
+impl Scorer {
+    /// Creates a scorer for `targets` with the given windows either side of
+    /// each target.
+    pub fn new(targets: &[Target], perfect_window: Duration, hit_window: Duration) -> Self {
+        Self {
+            times: targets.iter().map(|target| target.time).collect(),
+            judged: vec![false; targets.len()],
+            next: 0,
+            perfect_window,
+            hit_window: hit_window.max(perfect_window),
+            score: Score::default(),
+        }
+    }
+
+    /// The tally so far.
+    pub fn score(&self) -> &Score {
+        &self.score
+    }
+
+    /// `true` once every target is judged.
+    pub fn is_finished(&self) -> bool {
+        self.next == self.times.len()
+    }
+
+    /// Judges a press at `time`: a hit on the earliest unjudged target
+    /// whose window holds it, or `None` for a stray press.
+    pub fn press(&mut self, time: Duration) -> Option<Judged> {
+        let target = (self.next..self.times.len())
+            .take_while(|&i| self.times[i] <= time + self.hit_window)
+            .find(|&i| !self.judged[i] && self.times[i].abs_diff(time) <= self.hit_window);
+        let Some(target) = target else {
+            self.score.stray += 1;
+            return None;
+        };
+        let distance = self.times[target].abs_diff(time);
+        let offset = if time >= self.times[target] {
+            distance.as_secs_f32()
+        } else {
+            -distance.as_secs_f32()
+        };
+        let judgement = if distance <= self.perfect_window {
+            Judgement::Perfect
+        } else {
+            Judgement::Good
+        };
+        Some(self.judge(Judged {
+            target,
+            judgement,
+            offset: Some(offset),
+        }))
+    }
+
+    /// Judges every target whose window has passed by `time` without a
+    /// press as a miss, returning them in time order.
+    pub fn expire(&mut self, time: Duration) -> Vec<Judged> {
+        let mut missed = Vec::new();
+        while self.next < self.times.len() && self.times[self.next] + self.hit_window < time {
+            if !self.judged[self.next] {
+                missed.push(self.judge(Judged {
+                    target: self.next,
+                    judgement: Judgement::Miss,
+                    offset: None,
+                }));
+            }
+            self.next += 1;
+        }
+        while self.next < self.times.len() && self.judged[self.next] {
+            self.next += 1;
+        }
+        missed
+    }
+
+    fn judge(&mut self, judged: Judged) -> Judged {
+        self.judged[judged.target] = true;
+        self.score.record(&judged);
+        judged
+    }
+}
+
+/// Options for [`play_rhythm_game`].
+#[derive(Debug, Clone)]
+pub struct RhythmOptions {
+    /// How many of the melody's notes become targets.
+    pub difficulty: Difficulty,
+    /// Largest distance from a target for a [`Judgement::Perfect`].
+    pub perfect_window: Duration,
+    /// Largest distance from a target for a hit at all.
+    pub hit_window: Duration,
+    /// Beats counted in before the song starts.
+    pub count_in: u32,
+    /// Part selection, transposition and tempo scaling.
+    pub conversion: ConversionOptions,
+    /// Play on at most this many controllers; all connected ones when `None`.
+    pub max_joycons: Option<usize>,
+    /// Receives a [`PlaybackEvent::TargetJudged`] for every target each
+    /// player hits or misses.
+    pub events: EventSender,
+}
+
+impl Default for RhythmOptions {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::default(),
+            perfect_window: Duration::from_millis(50),
+            hit_window: Duration::from_millis(150),
+            count_in: 4,
+            conversion: ConversionOptions::default(),
+            max_joycons: None,
+            events: EventSender::default(),
+        }
+    }
+}
+
+/// A rumble change produced by [`RhythmGame`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RhythmOutput {
+    /// Index of the player whose output to update.
+    pub player: usize,
+    /// Frequency in Hz, `0.0` for silence.
+    pub frequency: f32,
+    /// Amplitude in `0.0..=1.0`, `0.0` for silence.
+    pub amplitude: f32,
+}
+
+/// One player's side of the game.
+#[derive(Debug, Clone)]
+struct Player {
+    scorer: Scorer,
+    /// Buttons held at the last press.
+    held: Buttons,
+    /// Targets cued so far.
+    cued: usize,
+    /// When the sounding click, cue or feedback ends, if one is sounding.
+    silence_at: Option<Duration>,
+}
+
+/// Runs the game for every player: counts in, cues the targets, judges
+/// the presses and answers them.
+///
+/// This is the device-independent core of the game mode. Times are game
+/// time, which starts with the count-in, and the rumble each player's
+/// output should play is collected for [`take_rumble`](Self::take_rumble).
+/// At each target's time its note is cued softly on every player who has
+/// not hit it yet; a hit sounds it fully, a miss buzzes.
+#[derive(Debug, Clone)]
+pub struct RhythmGame {
+    /// The chart's targets in game time.
+    targets: Vec<Target>,
+    beat: Duration,
+    count_in: u32,
+    clicks: u32,
+    players: Vec<Player>,
+    rumble: Vec<RhythmOutput>,
+}
+
+impl RhythmGame {
+    /// Starts a game of `chart` for `num_players` players.
+    pub fn new(chart: &Chart, options: &RhythmOptions, num_players: usize) -> Self {
+        let beat = chart.beat();
+        let lead_in = beat * options.count_in;
+        let targets: Vec<Target> = chart
+            .targets
+            .iter()
+            .map(|target| Target {
+                time: target.time + lead_in,
+                ..*target
+            })
+            .collect();
+        let player = Player {
+            scorer: Scorer::new(&targets, options.perfect_window, options.hit_window),
+            held: Buttons::default(),
+            cued: 0,
+            silence_at: None,
+        };
+        Self {
+            targets,
+            beat,
+            count_in: options.count_in,
+            clicks: 0,
+            players: vec![player; num_players],
+            rumble: Vec::new(),
+        }
+    }
+
+    /// Game time at which the song starts, after the count-in.
+    pub fn lead_in(&self) -> Duration {
+        self.beat * self.count_in
+    }
+
+    /// Applies `player`'s held buttons at `time`. A new press after the
+    /// count-in is judged: returns the hit, or `None` for a stray press or
+    /// when nothing new was pressed.
+    pub fn press(&mut self, player: usize, buttons: Buttons, time: Duration) -> Option<Judged> {
+        let lead_in = self.lead_in();
+        let state = self.players.get_mut(player)?;
+        let pressed = buttons.pressed_since(state.held);
+        state.held = buttons;
+        if pressed.is_empty() || time < lead_in {
+            return None;
+        }
+        let judged = state.scorer.press(time)?;
+        let target = &self.targets[judged.target];
+        let length = target.duration.clamp(MIN_HIT_LENGTH, MAX_HIT_LENGTH);
+        let frequency = note_to_frequency(target.note.into());
+        self.sound(player, frequency, HIT_AMPLITUDE, time + length);
+        Some(judged)
+    }
+
+    /// Moves the game on to `time`: clicks the count-in, cues the targets
+    /// that are due, buzzes for the ones missed and ends sounds that are
+    /// over. Returns the misses as `(player, judged)` pairs.
+    pub fn advance(&mut self, time: Duration) -> Vec<(usize, Judged)> {
+        while self.clicks < self.count_in && time >= self.beat * self.clicks {
+            let (frequency, amplitude, length) = CLICK;
+            for player in 0..self.players.len() {
+                self.sound(player, frequency, amplitude, time + length);
+            }
+            self.clicks += 1;
+        }
+
+        let mut missed = Vec::new();
+        for player in 0..self.players.len() {
+            for judged in self.players[player].scorer.expire(time) {
+                let (frequency, amplitude, length) = MISS_BUZZ;
+                self.sound(player, frequency, amplitude, time + length);
+                missed.push((player, judged));
+            }
+
+            while let Some(target) = self.targets.get(self.players[player].cued).copied() {
+                if target.time > time {
+                    break;
+                }
+                let state = &mut self.players[player];
+                let hit = state.scorer.judged[state.cued];
+                state.cued += 1;
+                if !hit {
+                    let length = target.duration.clamp(MIN_HIT_LENGTH, MAX_HIT_LENGTH);
+                    let frequency = note_to_frequency(target.note.into());
+                    self.sound(player, frequency, CUE_AMPLITUDE, time + length);
+                }
+            }
+
+            let state = &mut self.players[player];
+            if state.silence_at.is_some_and(|at| time >= at) {
+                state.silence_at = None;
+                self.rumble.push(RhythmOutput {
+                    player,
+                    frequency: 0.0,
+                    amplitude: 0.0,
+                });
+            }
+        }
+        missed
+    }
+
+    /// The rumble changes since the last call, oldest first.
+    pub fn take_rumble(&mut self) -> Vec<RhythmOutput> {
+        std::mem::take(&mut self.rumble)
+    }
+
+    /// Returns `true` once every target is judged for every player and
+    /// every output is silent.
+    pub fn is_finished(&self) -> bool {
+        self.players
+            .iter()
+            .all(|player| player.scorer.is_finished() && player.silence_at.is_none())
+    }
+
+    /// Each player's tally so far.
+    pub fn scores(&self) -> Vec<Score> {
+        self.players
+            .iter()
+            .map(|player| *player.scorer.score())
+            .collect()
+    }
+
+    fn sound(&mut self, player: usize, frequency: f32, amplitude: f32, until: Duration) {
+        self.players[player].silence_at = Some(until);
+        self.rumble.push(RhythmOutput {
+            player,
+            frequency,
+            amplitude,
+        });
+    }
+}
+
+/// Plays the rhythm game on a MIDI file with connected JoyCons and returns
+/// each player's score.
+///
+/// The game ends after the last target, or early when **Q** is pressed on
+/// the keyboard or **Home** or **Capture** on a controller.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read or charted, no JoyCons can be
+/// connected or a controller stops responding.
+pub fn play_rhythm_game(
+    path: &Path,
+    options: &RhythmOptions,
+) -> Result<Vec<Score>, Box<dyn std::error::Error + Send + Sync>> {
+    let manager = JoyConManager::new()?;
+    play_rhythm_game_with(path, &manager, options)
+}
+
+/// Plays the rhythm game with the JoyCons discovered by `manager`.
+///
+/// See [`play_rhythm_game`].
+pub fn play_rhythm_game_with<B: DeviceBackend>(
+    path: &Path,
+    manager: &JoyConManager<B>,
+    options: &RhythmOptions,
+) -> Result<Vec<Score>, Box<dyn std::error::Error + Send + Sync>> {
+    let midi_data = std::fs::read(path)?;
+    let chart = Chart::from_midi(&midi_data, &options.conversion, options.difficulty)?;
+    let mut joycons = manager.connect_and_initialize_at_most(options.max_joycons)?;
+    let outputs = OutputTarget::all(&joycons);
+
+    log::info!(
+        "🥁 {} targets from part {} ({}) on {:?}, {} player(s)",
+        chart.targets.len(),
+        chart.part,
+        chart.name.as_deref().unwrap_or("unnamed"),
+        options.difficulty,
+        outputs.len()
+    );
+    log::info!("    Any button = hit, Home/Capture or Q = stop");
+
+    // Presses, cues and feedback all follow one clock in game time.
+    let clock = PlaybackClock::new();
+    let mut game = RhythmGame::new(&chart, options, outputs.len());
+    let result = run_interactive(&mut joycons, &clock, |joycons, step| {
+        match step {
+            Step::Input {
+                joycon,
+                state,
+                time,
+            } => {
+                for (player, output) in outputs.iter().enumerate() {
+                    if output.joycon != joycon {
+                        continue;
+                    }
+                    if let Some(judged) = game.press(player, output.buttons(state.buttons), time) {
+                        let target = &chart.targets[judged.target];
+                        emit_judged(&options.events, player, &judged, target);
+                    }
+                }
+            }
+            Step::Tick { time } => {
+                for (player, judged) in game.advance(time) {
+                    let target = &chart.targets[judged.target];
+                    emit_judged(&options.events, player, &judged, target);
+                }
+            }
+        }
+        for change in game.take_rumble() {
+            outputs[change.player].rumble(joycons, change.frequency, change.amplitude)?;
+        }
+        Ok(game.is_finished())
+    });
+    result?;
+
+    let scores = game.scores();
+    for (player, score) in scores.iter().enumerate() {
+        log::info!(
+            "🏁 Player {}: {:.0}% ({} perfect, {} good, {} missed, {} stray), best combo {}, average offset {}",
+            player + 1,
+            score.accuracy() * 100.0,
+            score.perfect,
+            score.good,
+            score.missed,
+            score.stray,
+            score.max_combo,
+            score
+                .mean_offset()
+                .map(|offset| format!("{:+.0} ms", offset * 1000.0))
+                .unwrap_or_else(|| "n/a".to_string())
+        );
+    }
+    Ok(scores)
+}
+
+fn emit_judged(events: &EventSender, player: usize, judged: &Judged, target: &Target) {
+    events.emit(PlaybackEvent::TargetJudged {
+        joycon: player,
+        target: judged.target,
+        judgement: judged.judgement,
+        offset: judged.offset,
+        time: target.time,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joycon::{Button, JoyConType, SimulatedDevice};
+    use crate::midi::test_support::{one_note_per_beat, simulated_manager};
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn note(start_tick: u32, pitch: u8) -> NoteObject {
+        NoteObject {
+            start_tick,
+            end_tick: start_tick + 100,
+            pitch,
+            velocity: 100,
+            channel: 0,
+            track_index: 0,
+            program: 0,
+            is_drum: false,
+        }
+    }
+
+    #[test]
+    fn chords_collapse_and_difficulty_thins() {
+        // Sixteen onsets 250 ms apart, the second one a chord.
+        let mut notes: Vec<NoteObject> = (0..16).map(|i| note(i * 250, 60)).collect();
+        notes.push(note(250, 67));
+        let features = PartFeatures {
+            notes_per_sec: 4.25,
+            chordiness: 1.0625,
+            ..PartFeatures::default()
+        };
+        let targets = |difficulty| {
+            build_targets(&notes, &features, difficulty, 12, |tick| {
+                ms(u64::from(tick))
+            })
+        };
+
+        let expert = targets(Difficulty::Expert);
+        assert_eq!(expert.len(), 16);
+        assert_eq!(expert[1].note, 79);
+        assert_eq!(expert[1].duration, ms(100));
+        assert_eq!(targets(Difficulty::Hard).len(), 16);
+        let normal = targets(Difficulty::Normal);
+        assert_eq!(normal.len(), 8);
+        assert!(normal
+            .windows(2)
+            .all(|pair| pair[1].time - pair[0].time == ms(500)));
+        let easy = targets(Difficulty::Easy);
+        assert_eq!(
+            easy.iter().map(|target| target.time).collect::<Vec<_>>(),
+            [ms(0), ms(1000), ms(2000), ms(3000)]
+        );
+
+        // A burst faster than the target rate thins out even in a part that
+        // is sparse on average.
+        let burst: Vec<NoteObject> = [0, 100, 200, 300, 2000].map(|t| note(t, 60)).into();
+        let sparse = PartFeatures {
+            notes_per_sec: 1.0,
+            chordiness: 1.0,
+            ..PartFeatures::default()
+        };
+        let kept = build_targets(&burst, &sparse, Difficulty::Normal, 0, |tick| {
+            ms(u64::from(tick))
+        });
+        assert_eq!(kept.len(), 3);
+    }
+
+    #[test]
+    fn scorer_judges_presses_and_misses() {
+        let targets: Vec<Target> = [1000, 1500, 2000, 2100]
+            .map(|time| Target {
+                time: ms(time),
+                duration: ms(100),
+                note: 60,
+            })
+            .into();
+        let mut scorer = Scorer::new(&targets, ms(50), ms(150));
+
+        // Too early for anything.
+        assert_eq!(scorer.press(ms(500)), None);
+        let early = scorer.press(ms(960)).unwrap();
+        assert_eq!((early.target, early.judgement), (0, Judgement::Perfect));
+        assert!((early.offset.unwrap() + 0.04).abs() < 1e-6);
+        // The same target cannot be hit twice.
+        assert_eq!(scorer.press(ms(1000)), None);
+        assert!(scorer.expire(ms(1600)).is_empty());
+
+        // Two targets close together: each press takes the earliest.
+        assert_eq!(scorer.expire(ms(1700)).len(), 1);
+        assert_eq!(scorer.press(ms(2090)).unwrap().target, 2);
+        let second = scorer.press(ms(2100)).unwrap();
+        assert_eq!((second.target, second.judgement), (3, Judgement::Perfect));
+        assert!(scorer.expire(ms(5000)).is_empty());
+        assert!(scorer.is_finished());
+
+        let score = scorer.score();
+        assert_eq!(
+            (score.perfect, score.good, score.missed, score.stray),
+            (2, 1, 1, 2)
+        );
+        assert_eq!((score.combo, score.max_combo), (2, 2));
+        assert!((score.accuracy() - 0.75).abs() < 1e-6);
+        assert!((score.mean_offset().unwrap() - 0.05 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn chart_times_follow_the_tempo_map() {
+        let midi = one_note_per_beat(4);
+        let chart =
+            Chart::from_midi(&midi, &ConversionOptions::default(), Difficulty::Expert).unwrap();
+        assert_eq!(chart.part, 0);
+        assert_eq!(
+            chart.targets.iter().map(|t| t.time).collect::<Vec<_>>(),
+            [ms(0), ms(500), ms(1000), ms(1500)]
+        );
+        assert_eq!(chart.targets[3].note, 63);
+        assert_eq!(chart.beat(), ms(500));
+
+        let slower = ConversionOptions {
+            tempo_scale: 0.5,
+            ..ConversionOptions::default()
+        };
+        let chart = Chart::from_midi(&midi, &slower, Difficulty::Expert).unwrap();
+        assert_eq!(chart.targets[1].time, ms(1000));
+    }
+
+    #[test]
+    fn game_cues_judges_and_answers() {
+        let chart = Chart::from_midi(
+            &one_note_per_beat(4),
+            &ConversionOptions::default(),
+            Difficulty::Expert,
+        )
+        .unwrap();
+        let options = RhythmOptions {
+            count_in: 1,
+            ..RhythmOptions::default()
+        };
+        // Targets at 500, 1000, 1500 and 2000 ms of game time.
+        let mut game = RhythmGame::new(&chart, &options, 1);
+        assert_eq!(game.lead_in(), ms(500));
+        let a: Buttons = [Button::A].into_iter().collect();
+        let sounds = |game: &mut RhythmGame| -> Vec<(f32, f32)> {
+            game.take_rumble()
+                .iter()
+                .map(|change| (change.frequency, change.amplitude))
+                .collect()
+        };
+
+        assert!(game.advance(ms(0)).is_empty());
+        assert_eq!(sounds(&mut game), [(CLICK.0, CLICK.1)]);
+        // Presses during the count-in do not count.
+        assert_eq!(game.press(0, a, ms(400)), None);
+        game.press(0, Buttons::default(), ms(450));
+
+        // The first target is cued softly, and a hit sounds it fully.
+        assert!(game.advance(ms(500)).is_empty());
+        assert_eq!(sounds(&mut game), [(note_to_frequency(60), CUE_AMPLITUDE)]);
+        let hit = game.press(0, a, ms(520)).unwrap();
+        assert_eq!((hit.target, hit.judgement), (0, Judgement::Perfect));
+        assert_eq!(sounds(&mut game), [(note_to_frequency(60), HIT_AMPLITUDE)]);
+        game.press(0, Buttons::default(), ms(600));
+
+        // The second target is cued, then buzzes once its window passes.
+        game.advance(ms(1000));
+        assert_eq!(sounds(&mut game), [(note_to_frequency(61), CUE_AMPLITUDE)]);
+        let missed = game.advance(ms(1151));
+        assert_eq!(missed.len(), 1);
+        assert_eq!(missed[0].1.judgement, Judgement::Miss);
+        assert_eq!(sounds(&mut game), [(MISS_BUZZ.0, MISS_BUZZ.1)]);
+
+        // A late hit on the third, a stray press after it, and nothing for
+        // the fourth.
+        game.advance(ms(1500));
+        let late = game.press(0, a, ms(1600)).unwrap();
+        assert_eq!((late.target, late.judgement), (2, Judgement::Good));
+        assert!((late.offset.unwrap() - 0.1).abs() < 1e-6);
+        game.press(0, Buttons::default(), ms(1700));
+        assert_eq!(game.press(0, a, ms(1800)), None);
+        game.advance(ms(2000));
+        assert_eq!(game.advance(ms(2151)).len(), 1);
+        assert!(!game.is_finished());
+        game.advance(ms(2400));
+        assert!(game.is_finished());
+
+        let score = game.scores()[0];
+        assert_eq!(
+            (score.perfect, score.good, score.missed, score.stray),
+            (1, 1, 2, 1)
+        );
+    }
+
+    #[test]
+    fn simulated_halves_are_scored_as_players() {
+        let path = std::env::temp_dir().join(format!("mj-rhythm-{}.mid", std::process::id()));
+        std::fs::write(&path, one_note_per_beat(2)).unwrap();
+        let pro = SimulatedDevice::new();
+        let manager = simulated_manager(JoyConType::ProController, &pro);
+        let (events, rx) = EventSender::channel();
+        let options = RhythmOptions {
+            difficulty: Difficulty::Expert,
+            count_in: 0,
+            events,
+            ..RhythmOptions::default()
+        };
+        // Nobody presses anything, so every target is missed by both
+        // players, whenever the game gets to it.
+        let result = play_rhythm_game_with(&path, &manager, &options);
+        let _ = std::fs::remove_file(&path);
+
+        let scores = result.unwrap();
+        assert_eq!(scores.len(), 2);
+        assert!(scores
+            .iter()
+            .all(|score| (score.hits(), score.missed, score.stray) == (0, 2, 0)));
+        let mut judged: Vec<(usize, usize, Judgement)> = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                PlaybackEvent::TargetJudged {
+                    joycon,
+                    target,
+                    judgement,
+                    ..
+                } => Some((joycon, target, judgement)),
+                _ => None,
+            })
+            .collect();
+        judged.sort_by_key(|&(player, target, _)| (player, target));
+        assert_eq!(
+            judged,
+            [
+                (0, 0, Judgement::Miss),
+                (0, 1, Judgement::Miss),
+                (1, 0, Judgement::Miss),
+                (1, 1, Judgement::Miss),
+            ]
+        );
+        // Both motors were cued and buzzed, and end silent.
+        let reports = pro.reports();
+        let last = &reports.last().unwrap().data;
+        assert_eq!(last[2..6], last[6..10]);
+        assert!(reports
+            .iter()
+            .any(|report| report.data[2..6] != last[2..6] && report.data[6..10] != last[6..10]));
+    }
+}
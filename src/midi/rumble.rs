@@ -18,7 +18,7 @@
 //! 4. Identify silent periods for potential track switching
 //! 5. Normalize amplitudes across all tracks
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::time::Duration;
 
 use midly::{Smf, TrackEventKind};
@@ -28,6 +28,7 @@ use super::parts::{normalize_to_parts, Part};
 use super::scoring::{secondary_score, select_parts, PartSelection};
 use super::track_analysis::{analyze_part, PartFeatures};
 use super::track_types::{PlaybackPlan, SectionAssignment, TrackMetrics, TrackType};
+use super::voice::{MonoVoice, VoicePolicy};
 
 /// A single rumble command to send to a JoyCon.
 ///
@@ -45,9 +46,10 @@ use super::track_types::{PlaybackPlan, SectionAssignment, TrackMetrics, TrackTyp
 ///     frequency: 440.0,           // A4 note
 ///     amplitude: 0.8,             // 80% volume
 ///     wait_before: Duration::from_millis(100),  // Wait 100ms first
+///     low_band: None,             // Same note in both bands
 /// };
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RumbleCommand {
     /// Frequency in Hz. Use `0.0` for silence.
     ///
@@ -67,6 +69,25 @@ pub struct RumbleCommand {
     /// This creates the timing between notes. A value of `Duration::ZERO`
     /// means the command executes immediately after the previous one.
     pub wait_before: Duration,
+
+    /// A second, lower note as `(frequency, amplitude)` for the
+    /// low-frequency band.
+    ///
+    /// Set in two-voice mode (see [`ConversionOptions::two_voice`]) while
+    /// two or more notes are held; `frequency` then plays in the high band
+    /// only. `None` plays `frequency` in both bands.
+    pub low_band: Option<(f32, f32)>,
+}
+
+impl RumbleCommand {
+    /// A copy with both bands' amplitudes multiplied by `gain`.
+    pub fn with_gain(&self, gain: f32) -> Self {
+        Self {
+            amplitude: self.amplitude * gain,
+            low_band: self.low_band.map(|(freq, amp)| (freq, amp * gain)),
+            ..self.clone()
+        }
+    }
 }
 
 /// A point in time where track switching may occur.
@@ -153,6 +174,140 @@ pub enum ParseError {
     /// percussion, or have no note events.
     #[error("No tracks found")]
     NoTracks,
+
+    /// A forced primary/secondary part index does not name a playable part.
+    ///
+    /// Part indices are the ones listed in the analysis output; drum parts
+    /// and parts without notes cannot be forced.
+    #[error("Part {0} does not exist or has no playable notes")]
+    InvalidPart(usize),
+
+    /// [`VoicePolicy::Arpeggiate`] was requested in two-voice mode.
+    ///
+    /// [`ConversionOptions::two_voice`] always plays a chord as its highest
+    /// and lowest notes, so there is nothing left to arpeggiate.
+    #[error("Arpeggiated parts cannot be played in two-voice mode")]
+    ArpeggioWithTwoVoice,
+}
+
+/// How much output a front end shows.
+///
+/// The library only logs and emits [`PlaybackEvent`](super::events::PlaybackEvent)s;
+/// this is the coarse level a front end maps onto them. Levels are ordered
+/// from least to most output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// Only warnings and errors.
+    Quiet,
+    /// Analysis summary and playback status.
+    #[default]
+    Normal,
+    /// Everything, including every note sent to a JoyCon.
+    Verbose,
+}
+
+impl Verbosity {
+    /// The most detailed [`log`] level shown at this verbosity.
+    pub fn level_filter(self) -> log::LevelFilter {
+        match self {
+            Self::Quiet => log::LevelFilter::Warn,
+            Self::Normal => log::LevelFilter::Info,
+            Self::Verbose => log::LevelFilter::Debug,
+        }
+    }
+}
+
+/// Options controlling MIDI-to-rumble conversion.
+///
+/// The defaults reproduce [`parse_midi_to_rumble`] for two JoyCons.
+///
+/// # Example
+///
+/// ```no_run
+/// use musical_joycons::midi::{parse_midi_to_rumble_with, ConversionOptions};
+///
+/// let midi_data = std::fs::read("song.mid")?;
+/// let options = ConversionOptions {
+///     transpose: -12,   // one octave down
+///     tempo_scale: 0.5, // half speed
+///     primary_part: Some(2),
+///     ..ConversionOptions::default()
+/// };
+/// let (tracks, plan, selection) = parse_midi_to_rumble_with(&midi_data, &options)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConversionOptions {
+    /// Number of JoyCons that will play simultaneously.
+    pub num_joycons: usize,
+    /// Semitones added to every note before frequency mapping.
+    pub transpose: i32,
+    /// Playback speed multiplier: `2.0` plays twice as fast, `0.5` half speed.
+    pub tempo_scale: f32,
+    /// Force this part (by analysis index) as the primary part.
+    pub primary_part: Option<usize>,
+    /// Force this part (by analysis index) as the secondary part.
+    pub secondary_part: Option<usize>,
+    /// Play up to two notes of a part at once: the highest held note in the
+    /// high-frequency band and the lowest in the low-frequency band (see
+    /// [`RumbleCommand::low_band`]). Cannot be combined with
+    /// [`VoicePolicy::Arpeggiate`].
+    pub two_voice: bool,
+    /// Note priority for every part; [`VoicePolicy::First`] when `None`,
+    /// unless [`auto_voice`](Self::auto_voice) is set.
+    pub voice_policy: Option<VoicePolicy>,
+    /// Note priority for individual parts (by analysis index), taking
+    /// precedence over [`voice_policy`](Self::voice_policy).
+    pub part_voice_policies: BTreeMap<usize, VoicePolicy>,
+    /// Choose the policy of parts without one from their features with
+    /// [`VoicePolicy::for_features`]. In two-voice mode chordal parts keep
+    /// [`VoicePolicy::First`] instead of arpeggiating.
+    pub auto_voice: bool,
+}
+
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        Self {
+            num_joycons: 2,
+            transpose: 0,
+            tempo_scale: 1.0,
+            primary_part: None,
+            secondary_part: None,
+            two_voice: false,
+            voice_policy: None,
+            part_voice_policies: BTreeMap::new(),
+            auto_voice: false,
+        }
+    }
+}
+
+impl ConversionOptions {
+    /// The voice policy used for the part with analysis index `part`.
+    pub fn voice_policy_for(&self, part: usize, features: &PartFeatures) -> VoicePolicy {
+        self.part_voice_policies
+            .get(&part)
+            .copied()
+            .or(self.voice_policy)
+            .unwrap_or_else(|| {
+                if !self.auto_voice {
+                    return VoicePolicy::First;
+                }
+                match VoicePolicy::for_features(features) {
+                    VoicePolicy::Arpeggiate { .. } if self.two_voice => VoicePolicy::First,
+                    policy => policy,
+                }
+            })
+    }
+
+    /// Whether an explicitly set policy arpeggiates in two-voice mode.
+    fn arpeggiates_two_voices(&self) -> bool {
+        self.two_voice
+            && self
+                .voice_policy
+                .iter()
+                .chain(self.part_voice_policies.values())
+                .any(|policy| policy.arpeggio_step().is_some())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -171,7 +326,7 @@ const RUMBLE_FREQ_MAX: f32 = 1252.0;
 /// Convert a MIDI note number to a frequency within the JoyCon rumble range.
 /// Notes below [`RUMBLE_FREQ_MIN`] are octave-shifted up; notes above
 /// [`RUMBLE_FREQ_MAX`] are octave-shifted down.
-fn note_to_frequency(note: i32) -> f32 {
+pub(crate) fn note_to_frequency(note: i32) -> f32 {
     let mut freq = 440.0 * 2.0f32.powf((note - MIDI_A4_NOTE) as f32 / 12.0);
     while freq < RUMBLE_FREQ_MIN {
         freq *= 2.0;
@@ -280,7 +435,14 @@ fn convert_part_to_rumble(
     features: &PartFeatures,
     tempo_changes: &[TempoChange],
     ticks_per_beat: f32,
+    options: &ConversionOptions,
+    policy: VoicePolicy,
 ) -> RumbleTrack {
+    let ConversionOptions {
+        transpose,
+        two_voice,
+        ..
+    } = *options;
     // Build a flat event list: (tick, is_on, pitch, velocity)
     let mut events: Vec<(u32, bool, u8, f32)> = Vec::with_capacity(part.notes.len() * 2);
     for n in &part.notes {
@@ -290,53 +452,71 @@ fn convert_part_to_rumble(
     events.sort_by_key(|e| (e.0, !e.1)); // note-offs before note-ons at same tick
 
     let mut commands = Vec::new();
-    let mut active_notes: Vec<(u8, f32)> = Vec::new();
+    let mut voice = MonoVoice::new(policy);
+    let frequency = |pitch: u8| note_to_frequency(pitch as i32 + transpose);
+    // The command for the voice's current state, timed `wait_before` after
+    // the previous one.
+    let sounding = |voice: &MonoVoice, wait_before: Duration| {
+        if let Some(((high, high_vel), (low, low_vel))) = voice.outer_notes().filter(|_| two_voice)
+        {
+            return RumbleCommand {
+                frequency: frequency(high),
+                amplitude: high_vel,
+                wait_before,
+                low_band: Some((frequency(low), low_vel)),
+            };
+        }
+        let (freq, amp) = match voice.current() {
+            Some((pitch, vel)) => (frequency(pitch), vel),
+            None => (0.0, 0.0),
+        };
+        RumbleCommand {
+            frequency: freq,
+            amplitude: amp,
+            wait_before,
+            low_band: None,
+        }
+    };
     let mut current_tick = 0u32;
 
     for (tick, is_on, pitch, vel) in &events {
         if *tick > current_tick {
-            let wait = ticks_to_duration(current_tick, *tick, tempo_changes, ticks_per_beat);
+            let mut wait = ticks_to_duration(current_tick, *tick, tempo_changes, ticks_per_beat);
+            let arpeggio_step = policy
+                .arpeggio_step()
+                .filter(|step| !step.is_zero() && voice.held_count() > 1);
+            // The arpeggio moves on every step, counted from the last note event.
+            if let Some(step) = arpeggio_step {
+                while wait > step {
+                    voice.advance();
+                    commands.push(sounding(&voice, step));
+                    wait -= step;
+                }
+            }
             if !wait.is_zero() {
-                let (freq, amp) = if active_notes.is_empty() {
-                    (0.0, 0.0)
-                } else {
-                    (
-                        note_to_frequency(active_notes[0].0 as i32),
-                        active_notes[0].1,
-                    )
-                };
-                commands.push(RumbleCommand {
-                    frequency: freq,
-                    amplitude: amp,
-                    wait_before: wait,
-                });
+                commands.push(sounding(&voice, wait));
             }
             current_tick = *tick;
         }
 
         if *is_on {
-            active_notes.push((*pitch, *vel));
-            let freq = note_to_frequency(*pitch as i32);
-            commands.push(RumbleCommand {
-                frequency: freq,
-                amplitude: *vel,
-                wait_before: Duration::ZERO,
-            });
-        } else {
-            active_notes.retain(|(p, _)| *p != *pitch);
-            let (freq, amp) = if active_notes.is_empty() {
-                (0.0, 0.0)
+            voice.note_on(*pitch, *vel);
+            let command = sounding(&voice, Duration::ZERO);
+            if command.low_band.is_some() || policy != VoicePolicy::First {
+                commands.push(command);
             } else {
-                (
-                    note_to_frequency(active_notes[0].0 as i32),
-                    active_notes[0].1,
-                )
-            };
-            commands.push(RumbleCommand {
-                frequency: freq,
-                amplitude: amp,
-                wait_before: Duration::ZERO,
-            });
+                // The attack always sounds, even though the voice holds on to
+                // the earliest note.
+                commands.push(RumbleCommand {
+                    frequency: frequency(*pitch),
+                    amplitude: *vel,
+                    wait_before: Duration::ZERO,
+                    low_band: None,
+                });
+            }
+        } else {
+            voice.note_off(*pitch);
+            commands.push(sounding(&voice, Duration::ZERO));
         }
     }
 
@@ -347,6 +527,7 @@ fn convert_part_to_rumble(
                 frequency: 0.0,
                 amplitude: 0.0,
                 wait_before: Duration::ZERO,
+                low_band: None,
             });
         }
     }
@@ -450,12 +631,10 @@ fn build_playback_plan_from_parts(
     events.sort_by_key(|e| e.tick);
 
     if events.is_empty() {
-        return PlaybackPlan {
-            sections: vec![SectionAssignment {
-                start_time: Duration::ZERO,
-                track_indices: vec![0; num_joycons],
-            }],
-        };
+        return PlaybackPlan::new(vec![SectionAssignment {
+            start_time: Duration::ZERO,
+            track_indices: vec![0; num_joycons],
+        }]);
     }
 
     // 2. Walk through events tracking which track holds the skyline (highest note).
@@ -601,7 +780,25 @@ fn build_playback_plan_from_parts(
         })
         .collect();
 
-    println!("\n🎼 Playback Plan ({} sections):", sections.len());
+    PlaybackPlan::new(sections)
+}
+
+/// Times of every beat from tick 0 to the first beat at or after
+/// `end_tick`.
+fn beat_times(end_tick: u32, tempo_changes: &[TempoChange], ticks_per_beat: f32) -> Vec<Duration> {
+    let step = ticks_per_beat.round().max(1.0) as u32;
+    (0..=end_tick.div_ceil(step))
+        .map(|beat| ticks_to_duration(0, beat * step, tempo_changes, ticks_per_beat))
+        .collect()
+}
+
+/// Logs the section layout of a playback plan.
+fn log_playback_plan(
+    sections: &[SectionAssignment],
+    candidate_parts: &[&Part],
+    total_duration: Duration,
+) {
+    log::info!("🎼 Playback Plan ({} sections):", sections.len());
     for (i, section) in sections.iter().enumerate() {
         let end = if i + 1 < sections.len() {
             sections[i + 1].start_time
@@ -619,7 +816,7 @@ fn build_playback_plan_from_parts(
                     .to_string()
             })
             .collect();
-        println!(
+        log::info!(
             "  Section {}: {:.1?} - {:.1?} → parts {:?} ({:?})",
             i + 1,
             section.start_time,
@@ -628,8 +825,6 @@ fn build_playback_plan_from_parts(
             part_names
         );
     }
-
-    PlaybackPlan { sections }
 }
 
 /// Parses MIDI data and converts it to rumble tracks with a playback plan.
@@ -659,33 +854,86 @@ pub fn parse_midi_to_rumble(
     midi_data: &[u8],
     num_joycons: usize,
 ) -> Result<(Vec<RumbleTrack>, PlaybackPlan, PartSelection), ParseError> {
-    let smf = Smf::parse(midi_data)?;
-
-    let ticks_per_beat = match smf.header.timing {
-        midly::Timing::Metrical(timing) => timing.as_int() as f32,
-        _ => 24.0,
-    };
+    parse_midi_to_rumble_with(
+        midi_data,
+        &ConversionOptions {
+            num_joycons,
+            ..ConversionOptions::default()
+        },
+    )
+}
 
-    let tempo_changes = collect_tempo_changes(&smf);
+/// Parses MIDI data like [`parse_midi_to_rumble`], applying [`ConversionOptions`].
+///
+/// Transposition and tempo scaling are applied before rumble conversion, so
+/// track durations and the playback plan reflect them. Forced primary and
+/// secondary parts replace the scored choice and move to the front of their
+/// candidate lists.
+///
+/// # Errors
+///
+/// In addition to the errors of [`parse_midi_to_rumble`]:
+///
+/// - [`ParseError::InvalidPart`] - A forced part index is out of range, a
+///   drum part, or has no notes
+/// - [`ParseError::ArpeggioWithTwoVoice`] - A part is set to arpeggiate in
+///   two-voice mode
+pub fn parse_midi_to_rumble_with(
+    midi_data: &[u8],
+    options: &ConversionOptions,
+) -> Result<(Vec<RumbleTrack>, PlaybackPlan, PartSelection), ParseError> {
+    let song = analyze_song(midi_data, options)?;
+    if log::log_enabled!(log::Level::Info) {
+        log_song_analysis(&song);
+    }
+    Ok((song.tracks, song.plan, song.selection))
+}
 
-    // --- Part-based normalization ---
-    let parts = normalize_to_parts(&smf);
-    println!("\n🎵 Normalized into {} parts", parts.len());
+/// Everything computed while converting a song.
+///
+/// Shared by [`parse_midi_to_rumble_with`] and the analysis
+/// [`report`](super::report).
+pub(crate) struct SongAnalysis {
+    /// Every part in the file.
+    pub parts: Vec<Part>,
+    /// Features of each part, index-aligned with `parts`.
+    pub features: Vec<PartFeatures>,
+    /// The selection in part index space.
+    pub part_selection: PartSelection,
+    /// Part index of each rumble track.
+    pub candidate_indices: Vec<usize>,
+    /// Converted candidate parts.
+    pub tracks: Vec<RumbleTrack>,
+    /// Section plan in rumble-track index space.
+    pub plan: PlaybackPlan,
+    /// The selection in rumble-track index space.
+    pub selection: PartSelection,
+    /// Tempo map, after tempo scaling.
+    tempo_changes: Vec<TempoChange>,
+    /// Resolution of the file's ticks.
+    ticks_per_beat: f32,
+}
 
-    let song_end_tick = parts
-        .iter()
-        .flat_map(|p| p.notes.iter())
-        .map(|n| n.end_tick)
-        .max()
-        .unwrap_or(0);
+impl SongAnalysis {
+    /// Length of the longest converted track.
+    pub fn total_duration(&self) -> Duration {
+        self.tracks
+            .iter()
+            .map(|t| t.total_duration)
+            .max()
+            .unwrap_or(Duration::ZERO)
+    }
 
-    let all_features: Vec<PartFeatures> = parts
-        .iter()
-        .map(|p| analyze_part(p, ticks_per_beat, DEFAULT_TEMPO, song_end_tick))
-        .collect();
+    /// Song position of a tick, following the tempo map.
+    pub fn tick_time(&self, tick: u32) -> Duration {
+        ticks_to_duration(0, tick, &self.tempo_changes, self.ticks_per_beat)
+    }
+}
 
-    for (i, (part, feat)) in parts.iter().zip(all_features.iter()).enumerate() {
-        println!(
+pub(crate) fn log_song_analysis(song: &SongAnalysis) {
+    log::info!("🎵 Normalized into {} parts", song.parts.len());
+    for (i, (part, feat)) in song.parts.iter().zip(song.features.iter()).enumerate() {
+        log::info!(
             "  Part {} (ch={}, prog={}): {} notes, active={:.2}, mono={:.2}, chord={:.1}, p75={:.0}, drum={}{}",
             i,
             part.key.channel,
@@ -703,23 +951,103 @@ pub fn parse_midi_to_rumble(
         );
     }
 
-    // --- Score & select primary / secondary ---
-    let selection = select_parts(&all_features).ok_or(ParseError::NoTracks)?;
-
-    println!(
-        "\n🎯 Selected primary=Part {} ({}), secondary=Part {} ({})",
+    let selection = &song.part_selection;
+    log::info!(
+        "🎯 Selected primary=Part {} ({}), secondary=Part {} ({})",
         selection.primary,
-        parts[selection.primary]
+        song.parts[selection.primary]
             .name
             .as_deref()
             .unwrap_or("unnamed"),
         selection.secondary,
-        parts[selection.secondary]
+        song.parts[selection.secondary]
             .name
             .as_deref()
             .unwrap_or("unnamed"),
     );
 
+    let candidate_parts: Vec<&Part> = song
+        .candidate_indices
+        .iter()
+        .map(|&i| &song.parts[i])
+        .collect();
+    log_playback_plan(&song.plan.sections, &candidate_parts, song.total_duration());
+}
+
+/// Runs the full conversion pipeline without printing.
+pub(crate) fn analyze_song(
+    midi_data: &[u8],
+    options: &ConversionOptions,
+) -> Result<SongAnalysis, ParseError> {
+    if options.arpeggiates_two_voices() {
+        return Err(ParseError::ArpeggioWithTwoVoice);
+    }
+    let smf = Smf::parse(midi_data)?;
+    let num_joycons = options.num_joycons.max(1);
+
+    let ticks_per_beat = match smf.header.timing {
+        midly::Timing::Metrical(timing) => timing.as_int() as f32,
+        _ => 24.0,
+    };
+
+    let mut tempo_changes = collect_tempo_changes(&smf);
+    if options.tempo_scale > 0.0 && options.tempo_scale != 1.0 {
+        for change in &mut tempo_changes {
+            change.tempo = (change.tempo as f64 / options.tempo_scale as f64).round() as u32;
+        }
+    }
+
+    // --- Part-based normalization ---
+    let parts = normalize_to_parts(&smf);
+
+    let song_end_tick = parts
+        .iter()
+        .flat_map(|p| p.notes.iter())
+        .map(|n| n.end_tick)
+        .max()
+        .unwrap_or(0);
+
+    let all_features: Vec<PartFeatures> = parts
+        .iter()
+        .map(|p| analyze_part(p, ticks_per_beat, DEFAULT_TEMPO, song_end_tick))
+        .collect();
+
+    // --- Score & select primary / secondary ---
+    let mut selection = select_parts(&all_features).ok_or(ParseError::NoTracks)?;
+
+    let is_playable = |idx: usize| {
+        all_features
+            .get(idx)
+            .is_some_and(|f| !f.is_drum && f.note_count > 0)
+    };
+    if let Some(forced) = options.primary_part {
+        if !is_playable(forced) {
+            return Err(ParseError::InvalidPart(forced));
+        }
+        let previous_primary = selection.primary;
+        selection.primary = forced;
+        selection.primary_candidates.retain(|&i| i != forced);
+        selection.primary_candidates.insert(0, forced);
+        if options.secondary_part.is_none() && selection.secondary == forced {
+            // The scored primary is the natural fallback when the forced part
+            // was the scored secondary.
+            selection.secondary = selection
+                .secondary_candidates
+                .iter()
+                .copied()
+                .find(|&i| i != forced)
+                .unwrap_or(previous_primary);
+        }
+    }
+    if let Some(forced) = options.secondary_part {
+        if !is_playable(forced) {
+            return Err(ParseError::InvalidPart(forced));
+        }
+        selection.secondary = forced;
+        selection.secondary_candidates.retain(|&i| i != forced);
+        selection.secondary_candidates.insert(0, forced);
+    }
+
     // Build candidate pool: primary + secondary + next few from primary_candidates.
     const MAX_CANDIDATES: usize = 6;
     let mut candidate_indices: Vec<usize> = Vec::new();
@@ -748,6 +1076,8 @@ pub fn parse_midi_to_rumble(
                 &all_features[part_idx],
                 &tempo_changes,
                 ticks_per_beat,
+                options,
+                options.voice_policy_for(part_idx, &all_features[part_idx]),
             )
         })
         .collect();
@@ -762,6 +1092,9 @@ pub fn parse_midi_to_rumble(
         for track in &mut rumble_tracks {
             for cmd in &mut track.commands {
                 cmd.amplitude /= max_amp;
+                if let Some((_, low_amp)) = &mut cmd.low_band {
+                    *low_amp /= max_amp;
+                }
             }
         }
     }
@@ -789,7 +1122,7 @@ pub fn parse_midi_to_rumble(
     };
 
     // Build the playback plan using skyline, constrained to the candidate pool.
-    let plan = build_playback_plan_from_parts(
+    let mut plan = build_playback_plan_from_parts(
         &candidate_parts,
         &rumble_tracks,
         &all_features,
@@ -798,6 +1131,240 @@ pub fn parse_midi_to_rumble(
         ticks_per_beat,
         &tempo_changes,
     );
+    plan.beats = beat_times(song_end_tick, &tempo_changes, ticks_per_beat);
+
+    Ok(SongAnalysis {
+        parts,
+        features: all_features,
+        part_selection: selection,
+        candidate_indices,
+        tracks: rumble_tracks,
+        plan,
+        selection: remapped_selection,
+        tempo_changes,
+        ticks_per_beat,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::test_support::{note, single_track, two_part_song};
+
+    /// Which of `pitches` the track holds for some time, in order, with
+    /// repeats collapsed.
+    fn held_pitches(track: &RumbleTrack, pitches: &[u8]) -> Vec<u8> {
+        let mut held: Vec<u8> = track
+            .commands
+            .windows(2)
+            .filter(|pair| pair[0].amplitude > 0.0 && !pair[1].wait_before.is_zero())
+            .map(|pair| {
+                *pitches
+                    .iter()
+                    .find(|&&p| note_to_frequency(p as i32) == pair[0].frequency)
+                    .unwrap()
+            })
+            .collect();
+        held.dedup();
+        held
+    }
+
+    #[test]
+    fn tempo_scale_shortens_tracks() {
+        let data = two_part_song(240);
+        let (normal, _, _) =
+            parse_midi_to_rumble_with(&data, &ConversionOptions::default()).unwrap();
+        let (fast, _, _) = parse_midi_to_rumble_with(
+            &data,
+            &ConversionOptions {
+                tempo_scale: 2.0,
+                ..ConversionOptions::default()
+            },
+        )
+        .unwrap();
+
+        let ratio = normal[0].total_duration.as_secs_f64() / fast[0].total_duration.as_secs_f64();
+        assert!((ratio - 2.0).abs() < 0.01, "ratio was {ratio}");
+    }
+
+    #[test]
+    fn beat_grid_follows_tempo_scale() {
+        let data = two_part_song(240);
+        let (_, normal, _) =
+            parse_midi_to_rumble_with(&data, &ConversionOptions::default()).unwrap();
+        let (_, fast, _) = parse_midi_to_rumble_with(
+            &data,
+            &ConversionOptions {
+                tempo_scale: 2.0,
+                ..ConversionOptions::default()
+            },
+        )
+        .unwrap();
+
+        // Four half-beat notes a beat apart end 3.5 beats in; the grid runs
+        // on to beat 4 at the default 120 BPM, twice as fast when scaled.
+        let beats = |secs: [f64; 5]| secs.map(Duration::from_secs_f64).to_vec();
+        assert_eq!(normal.beats, beats([0.0, 0.5, 1.0, 1.5, 2.0]));
+        assert_eq!(fast.beats, beats([0.0, 0.25, 0.5, 0.75, 1.0]));
+        assert_eq!(normal.beat_at(Duration::from_millis(1250)), Some(2.5));
+        assert_eq!(normal.beat_at(Duration::from_secs(3)), None);
+    }
+
+    #[test]
+    fn transpose_shifts_frequencies() {
+        let data = two_part_song(240);
+        let (tracks, _, _) = parse_midi_to_rumble_with(
+            &data,
+            &ConversionOptions {
+                transpose: 1,
+                ..ConversionOptions::default()
+            },
+        )
+        .unwrap();
+
+        let first = tracks
+            .iter()
+            .flat_map(|t| t.commands.iter())
+            .find(|c| c.amplitude > 0.0)
+            .unwrap();
+        let expected = [note_to_frequency(73), note_to_frequency(49)];
+        assert!(
+            expected.iter().any(|f| (first.frequency - f).abs() < 0.01),
+            "unexpected frequency {}",
+            first.frequency
+        );
+    }
+
+    #[test]
+    fn forced_parts_are_validated() {
+        let data = two_part_song(240);
+        let (tracks, _, selection) = parse_midi_to_rumble_with(
+            &data,
+            &ConversionOptions {
+                primary_part: Some(1),
+                ..ConversionOptions::default()
+            },
+        )
+        .unwrap();
+        // Part 1 is the channel 1 bass line.
+        let first = tracks[selection.primary]
+            .commands
+            .iter()
+            .find(|c| c.amplitude > 0.0)
+            .unwrap();
+        assert!((first.frequency - note_to_frequency(48)).abs() < 0.01);
+        assert_ne!(selection.primary, selection.secondary);
+
+        let err = parse_midi_to_rumble_with(
+            &data,
+            &ConversionOptions {
+                primary_part: Some(9),
+                ..ConversionOptions::default()
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ParseError::InvalidPart(9)));
+    }
+
+    #[test]
+    fn two_voice_splits_chords_across_bands() {
+        // C4+G4 together, then C4 alone.
+        let data = single_track(vec![
+            note(0, 0, 60, 100),
+            note(0, 0, 67, 90),
+            note(240, 0, 67, 0),
+            note(240, 0, 60, 0),
+        ]);
+
+        let (mono, _, _) = parse_midi_to_rumble_with(&data, &ConversionOptions::default()).unwrap();
+        assert!(mono[0].commands.iter().all(|c| c.low_band.is_none()));
+
+        let options = ConversionOptions {
+            two_voice: true,
+            ..ConversionOptions::default()
+        };
+        let (tracks, _, _) = parse_midi_to_rumble_with(&data, &options).unwrap();
+        let commands = &tracks[0].commands;
+        let chord = commands
+            .iter()
+            .find(|c| c.low_band.is_some())
+            .expect("no two-voice command");
+        assert_eq!(chord.frequency, note_to_frequency(67));
+        assert_eq!(chord.low_band.unwrap().0, note_to_frequency(60));
+
+        // Once G4 is released C4 plays alone in both bands.
+        let held = commands.iter().rev().find(|c| c.amplitude > 0.0).unwrap();
+        assert_eq!(held.frequency, note_to_frequency(60));
+        assert!(held.low_band.is_none());
+    }
+
+    #[test]
+    fn voice_policy_chooses_chord_tone() {
+        // G4 then C4 pressed together, held for half a second (120 BPM).
+        let data = single_track(vec![
+            note(0, 0, 67, 100),
+            note(0, 0, 60, 100),
+            note(480, 0, 67, 0),
+            note(0, 0, 60, 0),
+        ]);
+        let convert = |policy| {
+            let options = ConversionOptions {
+                part_voice_policies: BTreeMap::from([(0, policy)]),
+                ..ConversionOptions::default()
+            };
+            let (tracks, _, _) = parse_midi_to_rumble_with(&data, &options).unwrap();
+            held_pitches(&tracks[0], &[60, 67])
+        };
 
-    Ok((rumble_tracks, plan, remapped_selection))
+        // Under First the attack of the later note holds until the next event.
+        assert_eq!(convert(VoicePolicy::First), [60]);
+        assert_eq!(convert(VoicePolicy::Highest), [67]);
+        assert_eq!(convert(VoicePolicy::Lowest), [60]);
+        assert_eq!(
+            convert(VoicePolicy::Arpeggiate {
+                step: Duration::from_millis(125)
+            }),
+            [60, 67, 60, 67]
+        );
+
+        // A per-part policy wins over the global one.
+        let options = ConversionOptions {
+            voice_policy: Some(VoicePolicy::Lowest),
+            part_voice_policies: BTreeMap::from([(0, VoicePolicy::Highest)]),
+            ..ConversionOptions::default()
+        };
+        assert_eq!(
+            options.voice_policy_for(0, &PartFeatures::default()),
+            VoicePolicy::Highest
+        );
+        assert_eq!(
+            options.voice_policy_for(1, &PartFeatures::default()),
+            VoicePolicy::Lowest
+        );
+    }
+
+    #[test]
+    fn automatic_voice_policy_is_opt_in() {
+        let pad = PartFeatures {
+            accompaniment_bias: 0.6,
+            chordiness: 3.0,
+            ..PartFeatures::default()
+        };
+        let mut options = ConversionOptions::default();
+        assert_eq!(options.voice_policy_for(0, &pad), VoicePolicy::First);
+
+        options.auto_voice = true;
+        assert_eq!(
+            options.voice_policy_for(0, &pad),
+            VoicePolicy::for_features(&pad)
+        );
+        options.two_voice = true;
+        assert_eq!(options.voice_policy_for(0, &pad), VoicePolicy::First);
+
+        options.voice_policy = Some(VoicePolicy::Arpeggiate {
+            step: Duration::from_millis(125),
+        });
+        let err = parse_midi_to_rumble_with(&two_part_song(240), &options).unwrap_err();
+        assert!(matches!(err, ParseError::ArpeggioWithTwoVoice));
+    }
 }
@@ -4,10 +4,12 @@
 //! "accompaniment" (secondary) roles. A selection procedure picks the best
 //! pair with fallback and duplicate-rejection guardrails.
 
+use serde::Serialize;
+
 use super::track_analysis::PartFeatures;
 
 /// Result of the part-selection procedure.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PartSelection {
     /// Index into the `features` slice for the chosen primary part.
     pub primary: usize,
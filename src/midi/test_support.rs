@@ -0,0 +1,120 @@
+//! Fixtures shared by the tests of several modules.
+//!
+//! The MIDI files are single-track at 480 ticks per beat and, without a
+//! tempo event, 120 BPM: a beat is 480 ticks or 500 ms.
+
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use midly::{Format, Header, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+
+use crate::joycon::{JoyConManager, JoyConType, SimulatedBackend, SimulatedDevice};
+
+/// A note-on `delta` ticks after the previous event; velocity 0 ends the
+/// note.
+pub(crate) fn note(delta: u32, channel: u8, key: u8, vel: u8) -> TrackEvent<'static> {
+    TrackEvent {
+        delta: delta.into(),
+        kind: TrackEventKind::Midi {
+            channel: channel.into(),
+            message: MidiMessage::NoteOn {
+                key: key.into(),
+                vel: vel.into(),
+            },
+        },
+    }
+}
+
+/// A MIDI file of one track holding `events`.
+pub(crate) fn single_track(events: Vec<TrackEvent<'static>>) -> Vec<u8> {
+    let smf = Smf {
+        header: Header {
+            format: Format::SingleTrack,
+            timing: Timing::Metrical(480.into()),
+        },
+        tracks: vec![events],
+    };
+    let mut buf = Vec::new();
+    smf.write(&mut buf).unwrap();
+    buf
+}
+
+/// A melody on channel 0 over a bass line on channel 1, four notes each,
+/// every note `length` ticks long and followed by a rest as long.
+pub(crate) fn two_part_events(length: u32) -> Vec<TrackEvent<'static>> {
+    let mut events = Vec::new();
+    for (i, (melody, bass)) in [(72, 48), (74, 50), (76, 52), (77, 53)]
+        .into_iter()
+        .enumerate()
+    {
+        let delta = if i == 0 { 0 } else { length };
+        events.push(note(delta, 0, melody, 100));
+        events.push(note(0, 1, bass, 80));
+        events.push(note(length, 0, melody, 0));
+        events.push(note(0, 1, bass, 0));
+    }
+    events
+}
+
+/// [`two_part_events`] as a MIDI file.
+pub(crate) fn two_part_song(length: u32) -> Vec<u8> {
+    single_track(two_part_events(length))
+}
+
+/// A one-part song of `beats` half-beat notes on channel 0, one on every
+/// beat, rising a semitone at a time from middle C.
+pub(crate) fn one_note_per_beat(beats: u8) -> Vec<u8> {
+    let mut events = Vec::new();
+    for beat in 0..beats {
+        let delta = if beat == 0 { 0 } else { 240 };
+        events.push(note(delta, 0, 60 + beat, 100));
+        events.push(note(240, 0, 60 + beat, 0));
+    }
+    single_track(events)
+}
+
+/// Longest [`after_initialization`] waits for the controller.
+const INITIALIZATION_DEADLINE: Duration = Duration::from_secs(5);
+
+/// A manager that finds one controller of `device_type` on `device`.
+pub(crate) fn simulated_manager(
+    device_type: JoyConType,
+    device: &SimulatedDevice,
+) -> JoyConManager<SimulatedBackend> {
+    JoyConManager::with_backend(SimulatedBackend::new().with_device(device_type, device.clone()))
+}
+
+/// Number of reports a controller of `device_type` is sent while it is
+/// connected and initialized.
+pub(crate) fn initialization_reports(device_type: JoyConType) -> usize {
+    let reference = SimulatedDevice::new();
+    simulated_manager(device_type, &reference)
+        .connect_and_initialize_joycons()
+        .unwrap();
+    reference.reports().len()
+}
+
+/// Runs `perform` on its own thread once `device` has been initialized as
+/// a controller of `device_type`, the way a player would start pressing
+/// buttons once the controller is ready. The thread panics, failing the
+/// test that joins it, if initialization has not finished within
+/// [`INITIALIZATION_DEADLINE`].
+pub(crate) fn after_initialization(
+    device: &SimulatedDevice,
+    device_type: JoyConType,
+    perform: impl FnOnce(&SimulatedDevice) + Send + 'static,
+) -> JoinHandle<()> {
+    let initialized = initialization_reports(device_type);
+    let device = device.clone();
+    thread::spawn(move || {
+        let started = Instant::now();
+        while device.reports().len() < initialized {
+            assert!(
+                started.elapsed() < INITIALIZATION_DEADLINE,
+                "the controller was not initialized within {INITIALIZATION_DEADLINE:?}"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+        perform(&device);
+    })
+}
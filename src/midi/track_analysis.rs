@@ -26,6 +26,7 @@
 use std::collections::HashMap;
 
 use midly::TrackEventKind;
+use serde::Serialize;
 
 use super::parts::Part;
 use super::track_types::TrackMetrics;
@@ -235,7 +236,7 @@ pub fn analyze_track(
 /// Unlike [`TrackMetrics`] which is computed from raw MIDI track events,
 /// `PartFeatures` operates on pre-resolved `NoteObject`s with absolute
 /// start/end ticks, enabling polyphony and interval analysis.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PartFeatures {
     // -- activity / salience --
     pub note_count: usize,
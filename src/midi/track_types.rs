@@ -127,9 +127,20 @@ pub struct SectionAssignment {
 pub struct PlaybackPlan {
     /// Ordered list of sections sorted by `start_time`.
     pub sections: Vec<SectionAssignment>,
+    /// Time of every beat from the tempo map, starting at zero and running
+    /// to the first beat at or after the end of the song. Empty if unknown.
+    pub beats: Vec<Duration>,
 }
 
 impl PlaybackPlan {
+    /// Creates a plan of `sections` without a beat grid.
+    pub fn new(sections: Vec<SectionAssignment>) -> Self {
+        Self {
+            sections,
+            beats: Vec::new(),
+        }
+    }
+
     /// Returns the track index assigned to a JoyCon at the given time.
     pub fn track_for(&self, joycon_idx: usize, time: Duration) -> usize {
         self.sections
@@ -147,6 +158,42 @@ impl PlaybackPlan {
             .find(|s| s.start_time > time)
             .map(|s| s.start_time)
     }
+
+    /// Returns the index of the section playing at `time`.
+    pub fn section_index_at(&self, time: Duration) -> usize {
+        self.sections
+            .iter()
+            .rposition(|s| s.start_time <= time)
+            .unwrap_or(0)
+    }
+
+    /// Returns the start time of the section playing at `time`.
+    pub fn section_start_at(&self, time: Duration) -> Duration {
+        self.sections
+            .iter()
+            .rev()
+            .find(|s| s.start_time <= time)
+            .map(|s| s.start_time)
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Returns the beat playing at `time`, counting from 0 with the
+    /// fraction of the way to the next beat, or `None` without a beat grid
+    /// or past its last beat.
+    pub fn beat_at(&self, time: Duration) -> Option<f32> {
+        beat_at(&self.beats, time)
+    }
+}
+
+/// [`PlaybackPlan::beat_at`] for a bare beat grid.
+pub(crate) fn beat_at(beats: &[Duration], time: Duration) -> Option<f32> {
+    let next = beats.iter().position(|&beat| beat > time)?;
+    let Some(previous) = next.checked_sub(1) else {
+        return Some(0.0);
+    };
+    let (start, end) = (beats[previous], beats[next]);
+    let fraction = (time - start).as_secs_f32() / (end - start).as_secs_f32();
+    Some(previous as f32 + fraction)
 }
 
 pub(crate) struct TrackWeights {
@@ -0,0 +1,327 @@
+//! Monophonic voice allocation.
+//!
+//! A Joy-Con rumble motor plays one frequency at a time, so whenever several
+//! notes are held one of them has to be chosen. [`MonoVoice`] tracks the
+//! held notes and picks the sounding one according to a [`VoicePolicy`].
+//! File conversion and live input share this allocator so a part sounds the
+//! same either way.
+//!
+//! Conversion uses [`VoicePolicy::First`] unless a policy is set per part
+//! through [`ConversionOptions`](super::ConversionOptions), or
+//! [`auto_voice`](super::ConversionOptions::auto_voice) picks one from the
+//! part's features via [`VoicePolicy::for_features`].
+
+use std::time::Duration;
+
+use super::track_analysis::PartFeatures;
+
+/// Time between chord tones for an automatically chosen
+/// [`VoicePolicy::Arpeggiate`].
+pub const DEFAULT_ARPEGGIO_STEP: Duration = Duration::from_millis(125);
+
+/// Which held note a monophonic voice plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoicePolicy {
+    /// The note held longest (the conversion default).
+    #[default]
+    First,
+    /// The most recently pressed note, like most monophonic synths.
+    Last,
+    /// The highest held note, which keeps a melody on top of chords.
+    Highest,
+    /// The lowest held note, which keeps a bass line on the bottom.
+    Lowest,
+    /// Cycles through the held notes from lowest to highest, moving to the
+    /// next one every `step`.
+    ///
+    /// The voice itself has no clock: whoever drives it calls
+    /// [`MonoVoice::advance`] once per step. Only file conversion does so.
+    Arpeggiate {
+        /// Time each chord tone sounds.
+        step: Duration,
+    },
+}
+
+impl VoicePolicy {
+    /// Picks a policy that suits a part.
+    ///
+    /// Bass parts keep their lowest note, melodic parts their highest, and
+    /// chordal accompaniment (pads) is arpeggiated at
+    /// [`DEFAULT_ARPEGGIO_STEP`]. Everything else keeps the longest-held
+    /// note.
+    pub fn for_features(features: &PartFeatures) -> Self {
+        if features.is_drum {
+            Self::First
+        } else if features.bass_bias > 0.5 {
+            Self::Lowest
+        } else if features.melody_bias > 0.3 {
+            Self::Highest
+        } else if features.accompaniment_bias > 0.3 && features.chordiness >= 2.0 {
+            Self::Arpeggiate {
+                step: DEFAULT_ARPEGGIO_STEP,
+            }
+        } else {
+            Self::First
+        }
+    }
+
+    /// The arpeggio step, or `None` for policies that do not arpeggiate.
+    pub fn arpeggio_step(self) -> Option<Duration> {
+        match self {
+            Self::Arpeggiate { step } => Some(step),
+            _ => None,
+        }
+    }
+}
+
+/// Held-note tracker for one monophonic output.
+///
+/// # Example
+///
+/// ```
+/// use musical_joycons::midi::voice::{MonoVoice, VoicePolicy};
+///
+/// let mut voice = MonoVoice::new(VoicePolicy::Highest);
+/// voice.note_on(60, 0.8);
+/// voice.note_on(67, 0.5);
+/// voice.note_on(64, 0.9);
+/// assert_eq!(voice.current(), Some((67, 0.5)));
+///
+/// voice.note_off(67);
+/// assert_eq!(voice.current(), Some((64, 0.9)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MonoVoice {
+    policy: VoicePolicy,
+    /// Held notes as `(pitch, velocity)`, oldest first.
+    held: Vec<(u8, f32)>,
+    /// Arpeggio position, counted in steps; wraps over the held notes.
+    arpeggio: usize,
+}
+
+impl MonoVoice {
+    /// Creates a voice with no notes held.
+    pub fn new(policy: VoicePolicy) -> Self {
+        Self {
+            policy,
+            held: Vec::new(),
+            arpeggio: 0,
+        }
+    }
+
+    /// The allocation policy in use.
+    pub fn policy(&self) -> VoicePolicy {
+        self.policy
+    }
+
+    /// Presses `pitch` with a velocity in `0.0..=1.0`.
+    ///
+    /// Re-pressing a held pitch adds a newer press and keeps the earlier
+    /// one, as conversion always has: [`VoicePolicy::First`] stays on the
+    /// earlier press and [`VoicePolicy::Last`] moves to the newer one.
+    pub fn note_on(&mut self, pitch: u8, velocity: f32) {
+        self.held.push((pitch, velocity));
+    }
+
+    /// Releases every press of `pitch`. Releasing a pitch that is not held
+    /// does nothing.
+    pub fn note_off(&mut self, pitch: u8) {
+        self.held.retain(|&(p, _)| p != pitch);
+    }
+
+    /// Releases every held note.
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.arpeggio = 0;
+    }
+
+    /// Number of distinct held pitches.
+    pub fn held_count(&self) -> usize {
+        self.chord().len()
+    }
+
+    /// Moves an arpeggio to the next chord tone. Does nothing for other
+    /// policies.
+    pub fn advance(&mut self) {
+        if matches!(self.policy, VoicePolicy::Arpeggiate { .. }) {
+            self.arpeggio = self.arpeggio.wrapping_add(1);
+        }
+    }
+
+    /// Returns `true` if no notes are held.
+    pub fn is_silent(&self) -> bool {
+        self.held.is_empty()
+    }
+
+    /// The highest and lowest held notes as `(pitch, velocity)`, or `None`
+    /// unless at least two notes are held.
+    ///
+    /// This ignores the policy; two-voice conversion uses it to fill both
+    /// rumble bands.
+    pub fn outer_notes(&self) -> Option<((u8, f32), (u8, f32))> {
+        if self.held_count() < 2 {
+            return None;
+        }
+        let highest = self.held.iter().copied().max_by_key(|&(pitch, _)| pitch)?;
+        let lowest = self.held.iter().copied().min_by_key(|&(pitch, _)| pitch)?;
+        Some((highest, lowest))
+    }
+
+    /// The `(pitch, velocity)` that should sound, or `None` for silence.
+    pub fn current(&self) -> Option<(u8, f32)> {
+        match self.policy {
+            VoicePolicy::First => self.held.first().copied(),
+            VoicePolicy::Last => self.held.last().copied(),
+            VoicePolicy::Highest => {
+                self.held
+                    .iter()
+                    .copied()
+                    .reduce(|best, note| if note.0 > best.0 { note } else { best })
+            }
+            VoicePolicy::Lowest => self.held.iter().copied().min_by_key(|&(pitch, _)| pitch),
+            VoicePolicy::Arpeggiate { .. } => {
+                let chord = self.chord();
+                if chord.is_empty() {
+                    return None;
+                }
+                Some(chord[self.arpeggio % chord.len()])
+            }
+        }
+    }
+
+    /// The held pitches from lowest to highest, each with its newest
+    /// velocity.
+    fn chord(&self) -> Vec<(u8, f32)> {
+        let mut chord: Vec<(u8, f32)> = Vec::with_capacity(self.held.len());
+        for &(pitch, velocity) in &self.held {
+            match chord.iter_mut().find(|(p, _)| *p == pitch) {
+                Some(note) => note.1 = velocity,
+                None => chord.push((pitch, velocity)),
+            }
+        }
+        chord.sort_by_key(|&(pitch, _)| pitch);
+        chord
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies_pick_expected_note() {
+        let play = |policy| {
+            let mut voice = MonoVoice::new(policy);
+            voice.note_on(64, 0.5);
+            voice.note_on(72, 0.6);
+            voice.note_on(60, 0.7);
+            voice.current().map(|(p, _)| p)
+        };
+        assert_eq!(play(VoicePolicy::First), Some(64));
+        assert_eq!(play(VoicePolicy::Last), Some(60));
+        assert_eq!(play(VoicePolicy::Highest), Some(72));
+        assert_eq!(play(VoicePolicy::Lowest), Some(60));
+        assert_eq!(
+            play(VoicePolicy::Arpeggiate {
+                step: DEFAULT_ARPEGGIO_STEP
+            }),
+            Some(60)
+        );
+    }
+
+    #[test]
+    fn arpeggio_cycles_chord_tones_upwards() {
+        let mut voice = MonoVoice::new(VoicePolicy::Arpeggiate {
+            step: DEFAULT_ARPEGGIO_STEP,
+        });
+        voice.note_on(67, 0.5);
+        voice.note_on(60, 0.5);
+        voice.note_on(64, 0.5);
+
+        let mut pitches = Vec::new();
+        for _ in 0..4 {
+            pitches.push(voice.current().unwrap().0);
+            voice.advance();
+        }
+        assert_eq!(pitches, [60, 64, 67, 60]);
+
+        // Other policies ignore advance().
+        let mut voice = MonoVoice::new(VoicePolicy::Lowest);
+        voice.note_on(67, 0.5);
+        voice.note_on(60, 0.5);
+        voice.advance();
+        assert_eq!(voice.current(), Some((60, 0.5)));
+    }
+
+    #[test]
+    fn policy_follows_part_features() {
+        let features = |melody, accompaniment, bass, chordiness| PartFeatures {
+            melody_bias: melody,
+            accompaniment_bias: accompaniment,
+            bass_bias: bass,
+            chordiness,
+            ..PartFeatures::default()
+        };
+        assert_eq!(
+            VoicePolicy::for_features(&features(0.1, 0.2, 0.8, 1.0)),
+            VoicePolicy::Lowest
+        );
+        assert_eq!(
+            VoicePolicy::for_features(&features(0.7, 0.1, 0.0, 1.2)),
+            VoicePolicy::Highest
+        );
+        assert_eq!(
+            VoicePolicy::for_features(&features(0.1, 0.6, 0.1, 3.0)),
+            VoicePolicy::Arpeggiate {
+                step: DEFAULT_ARPEGGIO_STEP
+            }
+        );
+        assert_eq!(
+            VoicePolicy::for_features(&features(0.1, 0.6, 0.1, 1.0)),
+            VoicePolicy::First
+        );
+    }
+
+    #[test]
+    fn outer_notes_need_two_held() {
+        let mut voice = MonoVoice::new(VoicePolicy::First);
+        voice.note_on(64, 0.5);
+        assert_eq!(voice.outer_notes(), None);
+        voice.note_on(48, 0.6);
+        voice.note_on(72, 0.7);
+        assert_eq!(voice.outer_notes(), Some(((72, 0.7), (48, 0.6))));
+    }
+
+    #[test]
+    fn re_press_keeps_the_first_press() {
+        let mut voice = MonoVoice::new(VoicePolicy::First);
+        voice.note_on(60, 0.5);
+        voice.note_on(62, 0.5);
+        voice.note_on(60, 0.9);
+        assert_eq!(voice.current(), Some((60, 0.5)));
+        assert_eq!(voice.held_count(), 2);
+
+        let mut voice = MonoVoice::new(VoicePolicy::Arpeggiate {
+            step: DEFAULT_ARPEGGIO_STEP,
+        });
+        voice.note_on(60, 0.5);
+        voice.note_on(60, 0.9);
+        assert_eq!(voice.outer_notes(), None);
+        voice.advance();
+        assert_eq!(voice.current(), Some((60, 0.9)));
+    }
+
+    #[test]
+    fn release_falls_back_to_remaining_notes() {
+        let mut voice = MonoVoice::new(VoicePolicy::Last);
+        voice.note_on(60, 0.5);
+        voice.note_on(62, 0.5);
+        voice.note_on(60, 0.9); // the newer press of 60 is on top
+
+        voice.note_off(60);
+        assert_eq!(voice.current(), Some((62, 0.5)));
+        voice.note_off(62);
+        assert!(voice.is_silent());
+        assert_eq!(voice.current(), None);
+    }
+}